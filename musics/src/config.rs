@@ -1,6 +1,13 @@
+use crate::filename_pattern::FilenamePattern;
+use crate::labels::{cached_all_labels, show_label_chip};
+use crate::library::Library;
+use crate::library_table_view::LibraryTableSettings;
+use crate::query;
+use crate::radio::RadioSettings;
+use crate::smart_playlist::SmartPlaylist;
 use camino::Utf8PathBuf;
 use eframe::egui;
-use eframe::egui::Context;
+use eframe::egui::{Color32, Context, Ui};
 use rfd::FileDialog;
 use serde_derive::{Deserialize, Serialize};
 
@@ -10,6 +17,16 @@ use serde_derive::{Deserialize, Serialize};
 #[serde(default)]
 pub struct Config {
     pub library_directory: Utf8PathBuf,
+    pub radio: RadioSettings,
+    pub library_table: LibraryTableSettings,
+    pub smart_playlists: Vec<SmartPlaylist>,
+    /// Also saves ratings in the tags of the files, not only in the library.
+    pub write_ratings_to_files: bool,
+    /// Where songs without tags get their tags from, like `%artist% - %title%`.
+    /// Empty to only use the file name as title.
+    pub filename_pattern: String,
+    /// Where the organizer moves files to, see [`Template`](crate::organizer::Template).
+    pub organizer_template: String,
 }
 
 pub struct ConfigView {
@@ -21,7 +38,7 @@ impl ConfigView {
         ConfigView { show_window: false }
     }
 
-    pub fn show(&mut self, ctx: &Context, config: &mut Config) {
+    pub fn show(&mut self, ctx: &Context, config: &mut Config, library: &Library) {
         egui::Window::new("Config")
             .open(&mut self.show_window)
             .collapsible(false)
@@ -39,7 +56,36 @@ impl ConfigView {
                             }
                         }
                         ui.end_row();
-                        ui.label("(Needs a restart to take effect)");
+
+                        show_radio_settings(ui, &mut config.radio, library);
+
+                        ui.label("Ratings");
+                        ui.checkbox(&mut config.write_ratings_to_files, "Write to files")
+                            .on_hover_text(
+                                "Saves ratings in the tags of the files (POPM for mp3, \
+                                FMPS_RATING and RATING for ogg and flac), \
+                                so other players can read them.",
+                            );
+                        ui.end_row();
+
+                        ui.label("Tags from file names");
+                        ui.vertical(|ui| {
+                            ui.add(
+                                egui::TextEdit::singleline(&mut config.filename_pattern)
+                                    .hint_text("%artist% - %album%/%track% %title%"),
+                            )
+                            .on_hover_text(
+                                "Songs without tags get them from their file name and folders. \
+                                Used for songs found when scanning the library.",
+                            );
+                            if !config.filename_pattern.is_empty() {
+                                if let Err(error) = FilenamePattern::parse(&config.filename_pattern)
+                                {
+                                    ui.colored_label(Color32::LIGHT_RED, error);
+                                }
+                            }
+                        });
+                        ui.end_row();
                     });
             });
     }
@@ -48,3 +94,69 @@ impl ConfigView {
         self.show_window = true;
     }
 }
+
+fn show_radio_settings(ui: &mut Ui, settings: &mut RadioSettings, library: &Library) {
+    ui.label("Radio lookahead");
+    ui.add(egui::DragValue::new(&mut settings.lookahead).clamp_range(1..=100))
+        .on_hover_text("How many random songs are queued after the current song.");
+    ui.end_row();
+
+    ui.label("Avoid repeating songs");
+    ui.add(egui::DragValue::new(&mut settings.avoid_recent_songs).clamp_range(0..=10_000))
+        .on_hover_text("A song is not picked again until this many other songs have been picked.");
+    ui.end_row();
+
+    ui.label("Avoid repeating artists");
+    ui.add(egui::DragValue::new(&mut settings.avoid_recent_artists).clamp_range(0..=100))
+        .on_hover_text(
+            "An artist is not picked again until this many other songs have been picked.",
+        );
+    ui.end_row();
+
+    ui.label("Radio labels").on_hover_text(
+        "Only songs with one of the selected labels are picked. \
+        Without any selected, all songs can be.",
+    );
+    ui.horizontal_wrapped(|ui| {
+        for label in cached_all_labels(ui, library).keys() {
+            let selected = settings.labels.contains(label);
+            if show_label_chip(ui, label, selected) {
+                if selected {
+                    settings.labels.remove(label);
+                } else {
+                    settings.labels.insert(label.clone());
+                }
+            }
+        }
+    });
+    ui.end_row();
+
+    ui.label("Radio filter");
+    ui.vertical(|ui| {
+        ui.add(
+            egui::TextEdit::singleline(&mut settings.filter)
+                .hint_text("artist:radiohead genre:rock"),
+        )
+        .on_hover_text("Only songs this search finds are picked.");
+        if let Err(error) = query::parse(&settings.filter) {
+            ui.colored_label(Color32::LIGHT_RED, error.to_string());
+        }
+    });
+    ui.end_row();
+
+    match &settings.folder {
+        Some(folder) => ui.label(format!("Radio folder: {}", folder.as_str())),
+        None => ui.label("Radio folder: full library"),
+    };
+    ui.horizontal(|ui| {
+        if ui.button("Select radio folder").clicked() {
+            if let Some(dir) = FileDialog::new().pick_folder() {
+                settings.folder = Some(Utf8PathBuf::from_path_buf(dir).expect("Not a utf-8 path."));
+            }
+        }
+        if settings.folder.is_some() && ui.button("Clear").clicked() {
+            settings.folder = None;
+        }
+    });
+    ui.end_row();
+}
@@ -0,0 +1,198 @@
+//! Reads tags from the names of files, for files that have no tags but good names.
+//!
+//! ```text
+//! %artist% - %album%/%track% %title%
+//! ```
+//!
+//! - `%field%` matches the value of a field: title, artist, album, genre, year, track or disc.
+//!   Year, track and disc only match digits.
+//! - Everything else has to be in the name exactly.
+//! - `/` separates folders. The pattern is matched against the end of the path,
+//!   so this one looks at the folder of the file and the file name, without extension.
+
+use crate::tag_editor::TagField;
+use camino::Utf8Path;
+
+#[derive(Clone, PartialEq, Debug)]
+enum Part {
+    Text(String),
+    Field(TagField),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct FilenamePattern {
+    parts: Vec<Part>,
+    /// How many parts of the path it covers: the file, and the folders it's in.
+    path_depth: usize,
+}
+
+impl FilenamePattern {
+    pub fn parse(pattern: &str) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut rest = pattern;
+        while let Some(start) = rest.find('%') {
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].to_string()));
+            }
+            let after_start = &rest[start + 1..];
+            let Some(end) = after_start.find('%') else {
+                return Err("A % is missing, fields look like %title%.".to_string());
+            };
+            let name = &after_start[..end];
+            let field = TagField::ALL
+                .into_iter()
+                .find(|field| field.key() == name.to_lowercase())
+                .ok_or_else(|| {
+                    format!(
+                        "There is no field \"{}\". Fields are title, artist, album, genre, year, track and disc.",
+                        name
+                    )
+                })?;
+            if let Some(Part::Field(_)) = parts.last() {
+                return Err(
+                    "Fields need something in between them, it can't tell where one ends."
+                        .to_string(),
+                );
+            }
+            parts.push(Part::Field(field));
+            rest = &after_start[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_string()));
+        }
+
+        if !parts.iter().any(|part| matches!(part, Part::Field(_))) {
+            return Err("The pattern needs at least one field, like %title%.".to_string());
+        }
+
+        Ok(FilenamePattern {
+            parts,
+            path_depth: pattern.matches('/').count() + 1,
+        })
+    }
+
+    /// The fields in the pattern, in order.
+    pub fn fields(&self) -> Vec<TagField> {
+        self.parts
+            .iter()
+            .filter_map(|part| match part {
+                Part::Field(field) => Some(*field),
+                Part::Text(_) => None,
+            })
+            .collect()
+    }
+
+    /// The fields in the name of the file, in the order of the pattern.
+    /// `None` when the name doesn't fit the pattern.
+    pub fn extract(&self, path: &Utf8Path) -> Option<Vec<(TagField, String)>> {
+        let without_extension = path.with_extension("");
+        let components: Vec<&str> = without_extension.iter().collect();
+        if components.len() < self.path_depth {
+            return None;
+        }
+        let name = components[components.len() - self.path_depth..].join("/");
+        let name: Vec<char> = name.chars().collect();
+
+        let mut values = Vec::new();
+        if !match_parts(&self.parts, &name, &mut values) {
+            return None;
+        }
+
+        Some(
+            values
+                .into_iter()
+                .map(|(field, value)| (field, value.replace('_', " ").trim().to_string()))
+                .collect(),
+        )
+    }
+}
+
+/// Fields take as little as they can, as long as the rest still matches.
+fn match_parts(parts: &[Part], name: &[char], values: &mut Vec<(TagField, String)>) -> bool {
+    let Some((part, rest)) = parts.split_first() else {
+        return name.is_empty();
+    };
+
+    match part {
+        Part::Text(text) => {
+            let text: Vec<char> = text.chars().collect();
+            name.starts_with(&text) && match_parts(rest, &name[text.len()..], values)
+        }
+        Part::Field(field) => {
+            for end in 1..=name.len() {
+                let c = name[end - 1];
+                // A field can't be spread over folders.
+                if c == '/' || (field.is_number() && !c.is_ascii_digit()) {
+                    break;
+                }
+                values.push((*field, name[..end].iter().collect()));
+                if match_parts(rest, &name[end..], values) {
+                    return true;
+                }
+                values.pop();
+            }
+            false
+        }
+    }
+}
+
+/// The value of the field, if it's in `values`.
+pub fn value(values: &[(TagField, String)], field: TagField) -> Option<String> {
+    values
+        .iter()
+        .find(|(value_field, value)| *value_field == field && !value.is_empty())
+        .map(|(_, value)| value.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extract(pattern: &str, path: &str) -> Option<Vec<(TagField, String)>> {
+        FilenamePattern::parse(pattern)
+            .unwrap()
+            .extract(Utf8Path::new(path))
+    }
+
+    #[test]
+    fn test_extract() {
+        assert_eq!(
+            extract(
+                "%artist% - %album% - %track% - %title%",
+                "/music/Artist - Some Album - 03 - The Title.mp3"
+            ),
+            Some(vec![
+                (TagField::Artist, "Artist".to_string()),
+                (TagField::Album, "Some Album".to_string()),
+                (TagField::Track, "03".to_string()),
+                (TagField::Title, "The Title".to_string()),
+            ])
+        );
+
+        // Numbers only match digits, so the title can contain some as well.
+        assert_eq!(
+            extract(
+                "%artist% - %album%/%track% %title%",
+                "/music/The Band - Live - 2/12 Song_2.ogg"
+            ),
+            Some(vec![
+                (TagField::Artist, "The Band".to_string()),
+                (TagField::Album, "Live - 2".to_string()),
+                (TagField::Track, "12".to_string()),
+                (TagField::Title, "Song 2".to_string()),
+            ])
+        );
+
+        assert_eq!(extract("%track% %title%", "/music/Intro.mp3"), None);
+        assert_eq!(extract("%artist%/%album%/%title%", "Album/Song.mp3"), None);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(FilenamePattern::parse("%artist - %title%").is_err());
+        assert!(FilenamePattern::parse("%composer%").is_err());
+        assert!(FilenamePattern::parse("%artist%%title%").is_err());
+        assert!(FilenamePattern::parse("no fields").is_err());
+        assert!(FilenamePattern::parse("%TITLE%").is_ok());
+    }
+}
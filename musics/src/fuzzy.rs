@@ -0,0 +1,275 @@
+//! Fuzzy matching, in the style of fzf:
+//! https://github.com/junegunn/fzf/blob/master/src/algo/algo.go
+//!
+//! The query has to appear in the text as a subsequence, but not necessarily as one piece.
+//! Every way of matching is scored, Smith-Waterman style, and the best one is kept.
+//! Matching characters at the start of words, or right after each other, scores higher.
+//! Gaps between matching characters score lower.
+
+use crate::normalize::{lowercase, normalize, normalize_keeping_case, NormalizedText};
+
+const SCORE_MATCH: i32 = 16;
+const SCORE_GAP_START: i32 = -3;
+const SCORE_GAP_EXTENSION: i32 = -1;
+
+/// Matching at the start of the text, or after a space.
+const BONUS_BOUNDARY_WHITE: i32 = 10;
+/// Matching after a non-alphanumeric character, like `-` or `(`.
+const BONUS_BOUNDARY: i32 = 8;
+/// Matching a change from lowercase to uppercase, or from letters to numbers.
+const BONUS_CAMEL: i32 = 7;
+/// Minimum bonus for matching right after the previous match.
+/// Enough to make up for not having to start a gap.
+/// Consecutive matches also get the bonus of the first match in the row, if that is higher.
+/// So a match at the start of a word counts for the rest of the word as well.
+const BONUS_CONSECUTIVE: i32 = -(SCORE_GAP_START + SCORE_GAP_EXTENSION);
+/// The bonus of the first character in the query counts double,
+/// because where a match starts matters most.
+const BONUS_FIRST_CHAR_MULTIPLIER: i32 = 2;
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct FuzzyMatch {
+    pub score: i32,
+    /// Indices of the matched characters in the original text, in ascending order.
+    /// These are character indices, not byte indices.
+    pub positions: Vec<usize>,
+}
+
+/// Matches every whitespace-separated word in the query on it's own, and combines the result.
+/// All words have to match for the text to match.
+/// Both are normalized first, see [`normalize`]. So "bjork" matches "Björk".
+/// An empty query matches everything, with a score of 0.
+pub fn fuzzy_match(query: &str, text: &str) -> Option<FuzzyMatch> {
+    // Most titles are plain ascii, and most titles don't match. Ruling those out before
+    // normalizing saves most of the time when going through many songs.
+    let rules_out = |word: &str| word.is_ascii() && !is_ascii_subsequence(word.as_bytes(), text);
+    if text.is_ascii() && query.split_whitespace().any(rules_out) {
+        return None;
+    }
+
+    let NormalizedText {
+        chars: text,
+        original_indices,
+    } = normalize_keeping_case(text);
+    let lowercase_text: Vec<char> = text.iter().map(|c| lowercase(*c)).collect();
+
+    let mut result = FuzzyMatch {
+        score: 0,
+        positions: Vec::new(),
+    };
+
+    for word in query.split_whitespace() {
+        let word: Vec<char> = normalize(word).trim().chars().collect();
+        if word.is_empty() {
+            continue;
+        }
+        let word_match = match_word(&word, &text, &lowercase_text)?;
+
+        result.score += word_match.score;
+        result.positions.extend(
+            word_match
+                .positions
+                .iter()
+                .map(|index| original_indices[*index]),
+        );
+    }
+
+    result.positions.sort();
+    result.positions.dedup();
+
+    Some(result)
+}
+
+/// `word` should already be lowercase.
+fn match_word(word: &[char], text: &[char], lowercase_text: &[char]) -> Option<FuzzyMatch> {
+    if !is_subsequence(word, lowercase_text) {
+        return None;
+    }
+
+    let n = text.len();
+    let bonuses: Vec<i32> = (0..n).map(|index| bonus_at(text, index)).collect();
+
+    // `scores[i][j]` is the best score for matching `word[..=i]`, with `word[i]` matched
+    // at `text[j]`. `None` if that is not possible.
+    // `previous[i][j]` is where `word[i - 1]` was matched, for that best score.
+    // `chunk_bonuses[i][j]` is the bonus of the first match in the row of consecutive matches
+    // ending at `text[j]`, for that best score.
+    // The rows are stored one after the other, because a search calls this for many songs
+    // and allocating every row separately adds up.
+    let mut scores: Vec<Option<i32>> = vec![None; word.len() * n];
+    let mut previous: Vec<usize> = vec![0; word.len() * n];
+    let mut chunk_bonuses: Vec<i32> = vec![0; word.len() * n];
+    let at = |i: usize, j: usize| i * n + j;
+
+    for (i, query_char) in word.iter().enumerate() {
+        // Best score of a previous match, with the penalty for the gap between it and
+        // the current position already applied. And where that previous match was.
+        let mut best_with_gap: Option<(i32, usize)> = None;
+
+        for j in 0..n {
+            if i > 0 && j >= 2 {
+                let extended =
+                    best_with_gap.map(|(score, from)| (score + SCORE_GAP_EXTENSION, from));
+                let started =
+                    scores[at(i - 1, j - 2)].map(|score| (score + SCORE_GAP_START, j - 2));
+                best_with_gap = best_of(extended, started);
+            }
+
+            if lowercase_text[j] != *query_char {
+                continue;
+            }
+
+            chunk_bonuses[at(i, j)] = bonuses[j];
+
+            if i == 0 {
+                scores[at(i, j)] = Some(SCORE_MATCH + bonuses[j] * BONUS_FIRST_CHAR_MULTIPLIER);
+                continue;
+            }
+
+            // A boundary in the middle of a row of matches starts a new chunk.
+            let consecutive_chunk_bonus = if j > 0 && bonuses[j] < BONUS_BOUNDARY {
+                chunk_bonuses[at(i - 1, j - 1)]
+            } else {
+                bonuses[j]
+            };
+            let consecutive = if j > 0 {
+                scores[at(i - 1, j - 1)].map(|score| {
+                    let bonus = consecutive_chunk_bonus
+                        .max(bonuses[j])
+                        .max(BONUS_CONSECUTIVE);
+                    (score + SCORE_MATCH + bonus, j - 1)
+                })
+            } else {
+                None
+            };
+            let after_gap =
+                best_with_gap.map(|(score, from)| (score + SCORE_MATCH + bonuses[j], from));
+
+            if let Some((score, from)) = best_of(consecutive, after_gap) {
+                scores[at(i, j)] = Some(score);
+                previous[at(i, j)] = from;
+
+                if from + 1 == j {
+                    chunk_bonuses[at(i, j)] = consecutive_chunk_bonus;
+                }
+            }
+        }
+    }
+
+    // Prefer the earliest end position when scores are equal.
+    let last = word.len() - 1;
+    let (mut position, score) = scores[at(last, 0)..]
+        .iter()
+        .enumerate()
+        .filter_map(|(j, score)| score.map(|score| (j, score)))
+        .fold(None, |best: Option<(usize, i32)>, (j, score)| match best {
+            Some(best) if best.1 >= score => Some(best),
+            _ => Some((j, score)),
+        })?;
+
+    let mut positions = vec![position; word.len()];
+    for i in (1..word.len()).rev() {
+        position = previous[at(i, position)];
+        positions[i - 1] = position;
+    }
+
+    Some(FuzzyMatch { score, positions })
+}
+
+/// Picks the option with the highest score. Prefers `a` when both are equal.
+fn best_of(a: Option<(i32, usize)>, b: Option<(i32, usize)>) -> Option<(i32, usize)> {
+    match (a, b) {
+        (Some(a), Some(b)) if b.0 > a.0 => Some(b),
+        (a, b) => a.or(b),
+    }
+}
+
+fn is_subsequence(word: &[char], text: &[char]) -> bool {
+    let mut text = text.iter();
+    word.iter().all(|c| text.any(|t| t == c))
+}
+
+/// Ignores case, like matching the normalized texts does for ascii.
+fn is_ascii_subsequence(word: &[u8], text: &str) -> bool {
+    let mut text = text.bytes();
+    word.iter().all(|c| text.any(|t| t.eq_ignore_ascii_case(c)))
+}
+
+fn bonus_at(text: &[char], index: usize) -> i32 {
+    let current = text[index];
+    if !current.is_alphanumeric() {
+        return 0;
+    }
+
+    let Some(previous) = index.checked_sub(1).map(|i| text[i]) else {
+        return BONUS_BOUNDARY_WHITE;
+    };
+
+    if previous.is_whitespace() {
+        BONUS_BOUNDARY_WHITE
+    } else if !previous.is_alphanumeric() {
+        BONUS_BOUNDARY
+    } else if (previous.is_lowercase() && current.is_uppercase())
+        || (!previous.is_numeric() && current.is_numeric())
+    {
+        BONUS_CAMEL
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(query: &str, text: &str) -> Option<Vec<usize>> {
+        fuzzy_match(query, text).map(|m| m.positions)
+    }
+
+    fn score(query: &str, text: &str) -> i32 {
+        fuzzy_match(query, text).unwrap().score
+    }
+
+    #[test]
+    fn test_matches_subsequences() {
+        assert_eq!(positions("bh", "Blank Holes"), Some(vec![0, 6]));
+        assert_eq!(positions("BLNK", "blank holes"), Some(vec![0, 1, 3, 4]));
+        assert_eq!(
+            positions("holes blank", "Blank Holes"),
+            Some(vec![0, 1, 2, 3, 4, 6, 7, 8, 9, 10])
+        );
+        assert_eq!(positions("hb", "Blank Holes"), None);
+        assert_eq!(positions("blank x", "Blank Holes"), None);
+        assert_eq!(positions("", "Blank Holes"), Some(vec![]));
+    }
+
+    #[test]
+    fn test_matches_normalized_text() {
+        assert_eq!(positions("bjork", "Björk"), Some(vec![0, 1, 2, 3, 4]));
+        assert_eq!(
+            positions("sigur ros", "Sigur Rós").map(|p| p.len()),
+            Some(8)
+        );
+        assert_eq!(positions("Rós", "SIGUR ROS"), Some(vec![6, 7, 8]));
+        // "Æ" turns into "AE", but is only highlighted once.
+        assert_eq!(positions("aer", "Ærø"), Some(vec![0, 1]));
+        assert!(positions("カナ", "かな").is_some());
+        assert!(positions("ｆｕｌｌ", "Full width").is_some());
+    }
+
+    #[test]
+    fn test_prefers_word_starts() {
+        // The "m" of "Mystery" is a better match than the "m" in the middle of "Summer".
+        assert_eq!(positions("dm", "Dark Summer Mystery"), Some(vec![0, 12]));
+        // Skipping a single character is worth it to match the start of "Lightning".
+        assert_eq!(positions("ll", "Ball Lightning"), Some(vec![3, 5]));
+    }
+
+    #[test]
+    fn test_ranking() {
+        assert!(score("dark", "Dark Mystery") > score("dark", "Dance of the Ark"));
+        assert!(score("myst", "Dark Mystery") > score("myst", "My Stories"));
+        assert!(score("holes", "Holes") > score("holes", "Home Less"));
+        assert!(score("ab", "A Better Day") > score("ab", "Lab Report"));
+    }
+}
@@ -0,0 +1,354 @@
+//! A log of everything that happens while playing songs. Unlike the play stats, nothing is
+//! added up, so it can answer questions that weren't thought of when it was recorded.
+//! Entries are only ever appended, one json object per line, so a crash loses at most one.
+
+use crate::library::Song;
+use crate::library_table_view::format_date;
+use camino::Utf8PathBuf;
+use serde_derive::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum PlaybackEvent {
+    Start,
+    Pause,
+    Resume,
+    /// The position of the entry is where it seeked to.
+    Seek {
+        #[serde(with = "millis")]
+        from: Duration,
+    },
+    /// Another song was played before this one finished.
+    Skip,
+    Finish,
+}
+
+impl PlaybackEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PlaybackEvent::Start => "start",
+            PlaybackEvent::Pause => "pause",
+            PlaybackEvent::Resume => "resume",
+            PlaybackEvent::Seek { .. } => "seek",
+            PlaybackEvent::Skip => "skip",
+            PlaybackEvent::Finish => "finish",
+        }
+    }
+}
+
+/// Times are saved as milliseconds, which is easier to work with outside of the player.
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub struct HistoryEntry {
+    #[serde(with = "unix_millis")]
+    pub time: SystemTime,
+    #[serde(flatten)]
+    pub event: PlaybackEvent,
+    /// How far into the song it happened.
+    #[serde(with = "millis")]
+    pub position: Duration,
+    /// Title and artist are copied, so the history still makes sense after the file is gone.
+    pub path: Utf8PathBuf,
+    pub title: String,
+    pub artist: Option<String>,
+}
+
+/// A song being played from start until it was skipped or finished.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Play {
+    pub started: SystemTime,
+    pub title: String,
+    pub artist: Option<String>,
+    /// `None` when it's still playing, or the player was closed while it played.
+    pub ended: Option<PlaybackEvent>,
+}
+
+pub struct History {
+    entries: Vec<HistoryEntry>,
+    /// Where the entries are appended to. Nothing is saved without it.
+    log_path: Option<PathBuf>,
+    /// The last time saving failed, to show to the user.
+    pub error: Option<String>,
+}
+
+impl History {
+    /// A history that isn't saved.
+    pub fn new() -> Self {
+        History {
+            entries: Vec::new(),
+            log_path: None,
+            error: None,
+        }
+    }
+
+    /// Reads the log, and appends new entries to it. Lines that can't be read, like one
+    /// that was half written, are left out.
+    pub fn open(log_path: PathBuf) -> Self {
+        let entries = match File::open(&log_path) {
+            Ok(file) => BufReader::new(file)
+                .lines()
+                .map_while(Result::ok)
+                .filter_map(|line| serde_json::from_str(&line).ok())
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        History {
+            entries,
+            log_path: Some(log_path),
+            error: None,
+        }
+    }
+
+    /// The log in the data directory of the player, next to the rest of what it saves.
+    pub fn default_log_path() -> Option<PathBuf> {
+        directories_next::ProjectDirs::from("", "", "Musics")
+            .map(|dirs| dirs.data_dir().join("history.jsonl"))
+    }
+
+    /// Oldest first.
+    pub fn entries(&self) -> &[HistoryEntry] {
+        &self.entries
+    }
+
+    pub fn record(&mut self, event: PlaybackEvent, position: Duration, song: &Song) {
+        // Rounded the same as in the log, so the entries stay the same after a restart.
+        let now = unix_millis::to_time(unix_millis::from_time(SystemTime::now()));
+        let entry = HistoryEntry {
+            time: now,
+            event,
+            position,
+            path: song.path.clone(),
+            title: song.title.clone(),
+            artist: song.artist.clone(),
+        };
+        if let Err(error) = self.append_to_log(&entry) {
+            self.error = Some(format!("Could not save the history: {}", error));
+        }
+        self.entries.push(entry);
+    }
+
+    fn append_to_log(&self, entry: &HistoryEntry) -> std::io::Result<()> {
+        let Some(log_path) = &self.log_path else {
+            return Ok(());
+        };
+        if let Some(directory) = log_path.parent() {
+            std::fs::create_dir_all(directory)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log_path)?;
+        let line = serde_json::to_string(entry)?;
+        writeln!(file, "{}", line)
+    }
+
+    /// The songs that were started, oldest first, with how they ended.
+    pub fn plays(&self) -> Vec<Play> {
+        let mut plays: Vec<Play> = Vec::new();
+        // Whatever was playing before a start, was stopped by it.
+        let mut playing: Option<usize> = None;
+        for entry in &self.entries {
+            match entry.event {
+                PlaybackEvent::Start => {
+                    playing = Some(plays.len());
+                    plays.push(Play {
+                        started: entry.time,
+                        title: entry.title.clone(),
+                        artist: entry.artist.clone(),
+                        ended: None,
+                    });
+                }
+                PlaybackEvent::Skip | PlaybackEvent::Finish => {
+                    if let Some(index) = playing.take() {
+                        plays[index].ended = Some(entry.event);
+                    }
+                }
+                _ => {}
+            }
+        }
+        plays
+    }
+
+    /// Exports all entries, for analysing them elsewhere.
+    pub fn export_csv(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::write(path, to_csv(&self.entries))
+    }
+
+    pub fn export_json(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(&self.entries)?)
+    }
+}
+
+/// Groups the plays by the day they started on, in UTC like the other dates.
+/// The most recent day and play come first.
+pub fn plays_by_day(plays: Vec<Play>) -> Vec<(String, Vec<Play>)> {
+    let mut days: Vec<(String, Vec<Play>)> = Vec::new();
+    for play in plays.into_iter().rev() {
+        let day = format_date(play.started);
+        match days.last_mut() {
+            Some((last_day, day_plays)) if *last_day == day => day_plays.push(play),
+            _ => days.push((day, vec![play])),
+        }
+    }
+    days
+}
+
+/// Formats the time as "2023-02-03 14:05:09", in UTC.
+pub fn format_date_time(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_secs() % (24 * 60 * 60))
+        .unwrap_or(0);
+    let hours = seconds / 3600;
+    let minutes = seconds % 3600 / 60;
+    let seconds = seconds % 60;
+    format!(
+        "{} {hours:0>2}:{minutes:0>2}:{seconds:0>2}",
+        format_date(time)
+    )
+}
+
+fn to_csv(entries: &[HistoryEntry]) -> String {
+    let mut csv =
+        String::from("time,unix_time_ms,event,position_ms,seek_from_ms,path,title,artist\n");
+    for entry in entries {
+        let seek_from = match entry.event {
+            PlaybackEvent::Seek { from } => from.as_millis().to_string(),
+            _ => String::new(),
+        };
+        let fields = [
+            format_date_time(entry.time),
+            unix_millis::from_time(entry.time).to_string(),
+            entry.event.name().to_string(),
+            entry.position.as_millis().to_string(),
+            seek_from,
+            csv_field(entry.path.as_str()),
+            csv_field(&entry.title),
+            csv_field(entry.artist.as_deref().unwrap_or_default()),
+        ];
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Quotes the field if it has anything in it that would otherwise break up the row.
+pub fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+mod millis {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(duration.as_millis() as u64)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_millis)
+    }
+}
+
+mod unix_millis {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    pub fn from_time(time: SystemTime) -> u64 {
+        time.duration_since(UNIX_EPOCH)
+            .map(|since_epoch| since_epoch.as_millis() as u64)
+            .unwrap_or(0)
+    }
+
+    pub fn to_time(millis: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(millis)
+    }
+
+    pub fn serialize<S: Serializer>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(from_time(*time))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SystemTime, D::Error> {
+        u64::deserialize(deserializer).map(to_time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn test_log() {
+        let directory = TempDir::new("history");
+        let log_path = directory.path().join("history.jsonl").into_std_path_buf();
+        let second = Duration::from_secs(1);
+        let a = Song::named("a").with_artist("Someone, Else");
+        let b = Song::named("b");
+
+        let mut history = History::open(log_path.clone());
+        history.record(PlaybackEvent::Start, Duration::ZERO, &a);
+        history.record(PlaybackEvent::Seek { from: second }, second * 30, &a);
+        history.record(PlaybackEvent::Skip, second * 31, &a);
+        history.record(PlaybackEvent::Start, Duration::ZERO, &b);
+        assert_eq!(history.error, None);
+
+        // A half written line is left out.
+        OpenOptions::new()
+            .append(true)
+            .open(&log_path)
+            .unwrap()
+            .write_all(b"{\"time\":")
+            .unwrap();
+
+        let reopened = History::open(log_path);
+        assert_eq!(reopened.entries(), history.entries());
+
+        let plays = reopened.plays();
+        assert_eq!(plays.len(), 2);
+        assert_eq!(plays[0].ended, Some(PlaybackEvent::Skip));
+        assert_eq!(plays[1].ended, None);
+
+        let days = plays_by_day(plays.to_vec());
+        assert_eq!(days.len(), 1);
+        assert_eq!(days[0].1[0].title, "b");
+
+        let csv = to_csv(reopened.entries());
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(rows.len(), 5);
+        assert!(rows[2].ends_with(",seek,30000,1000,a.mp3,a,\"Someone, Else\""));
+    }
+
+    #[test]
+    fn test_plays_by_day() {
+        let day = Duration::from_secs(24 * 60 * 60);
+        let play = |started: SystemTime| Play {
+            started,
+            title: String::new(),
+            artist: None,
+            ended: Some(PlaybackEvent::Finish),
+        };
+        let plays = [
+            play(UNIX_EPOCH + day * 365),
+            play(UNIX_EPOCH + day * 366 - Duration::from_secs(1)),
+            play(UNIX_EPOCH + day * 366),
+        ];
+
+        let days = plays_by_day(plays.to_vec());
+        assert_eq!(days.len(), 2);
+        assert_eq!(days[0].0, "1971-01-02");
+        assert_eq!(days[1].0, "1971-01-01");
+        assert_eq!(days[1].1, vec![plays[1].clone(), plays[0].clone()]);
+        assert_eq!(
+            format_date_time(UNIX_EPOCH + day * 366 - Duration::from_secs(1)),
+            "1971-01-01 23:59:59"
+        );
+    }
+}
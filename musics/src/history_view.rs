@@ -0,0 +1,109 @@
+use crate::history::{format_date_time, plays_by_day, History, Play, PlaybackEvent};
+use eframe::egui;
+use eframe::egui::{Color32, Context};
+use rfd::FileDialog;
+use std::path::Path;
+
+/// How many days are open when the window is first shown.
+const OPEN_DAYS: usize = 3;
+
+pub struct HistoryView {
+    show_window: bool,
+    /// The plays, grouped by day, see [`plays_by_day`].
+    days: Vec<(String, Vec<Play>)>,
+    play_count: usize,
+    /// How many entries the history had when `days` was made. It's only ever appended to.
+    plays_for: usize,
+}
+
+impl HistoryView {
+    pub fn new() -> Self {
+        HistoryView {
+            show_window: false,
+            days: Vec::new(),
+            play_count: 0,
+            plays_for: 0,
+        }
+    }
+
+    pub fn open_window(&mut self) {
+        self.show_window = true;
+    }
+
+    pub fn show(&mut self, ctx: &Context, history: &mut History) {
+        if !self.show_window {
+            return;
+        }
+        if self.plays_for != history.entries().len() {
+            let plays = history.plays();
+            self.play_count = plays.len();
+            self.days = plays_by_day(plays);
+            self.plays_for = history.entries().len();
+        }
+
+        egui::Window::new("History")
+            .open(&mut self.show_window)
+            .default_height(500.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} plays", self.play_count));
+                    if ui.button("Export CSV").clicked() {
+                        export(history, "csv", History::export_csv);
+                    }
+                    if ui.button("Export JSON").clicked() {
+                        export(history, "json", History::export_json);
+                    }
+                });
+                if let Some(error) = &history.error {
+                    ui.colored_label(Color32::LIGHT_RED, error);
+                }
+
+                egui::ScrollArea::vertical()
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        for (index, (day, plays)) in self.days.iter().enumerate() {
+                            egui::CollapsingHeader::new(format!("{} ({})", day, plays.len()))
+                                .id_source(day)
+                                .default_open(index < OPEN_DAYS)
+                                .show(ui, |ui| {
+                                    for play in plays {
+                                        show_play(ui, play);
+                                    }
+                                });
+                        }
+                    });
+            });
+    }
+}
+
+fn show_play(ui: &mut egui::Ui, play: &Play) {
+    ui.horizontal(|ui| {
+        // Only the time, the day is in the header.
+        let time = format_date_time(play.started);
+        ui.weak(time.split(' ').next_back().unwrap_or_default());
+        match &play.artist {
+            Some(artist) => ui.label(format!("{} - {}", play.title, artist)),
+            None => ui.label(&play.title),
+        };
+        if play.ended == Some(PlaybackEvent::Skip) {
+            ui.weak("skipped");
+        }
+    });
+}
+
+fn export(
+    history: &mut History,
+    extension: &str,
+    write: fn(&History, &Path) -> std::io::Result<()>,
+) {
+    let Some(path) = FileDialog::new()
+        .add_filter(extension, &[extension])
+        .set_file_name(&format!("history.{}", extension))
+        .save_file()
+    else {
+        return;
+    };
+    history.error = write(history, &path)
+        .err()
+        .map(|error| format!("Could not export the history: {}", error));
+}
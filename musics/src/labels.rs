@@ -0,0 +1,158 @@
+//! Labels are the user's own tags for songs, like "workout" or "kids-ok". Unlike genres,
+//! they aren't in the files, so they can be anything and a song can have many of them.
+
+use crate::library::{Library, SongId};
+use eframe::egui;
+use eframe::egui::style::Margin;
+use eframe::egui::{Color32, Key, RichText, Ui};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum LabelChange {
+    Add(String),
+    Remove(String),
+}
+
+/// Adds or removes the label for all the songs.
+pub fn change_labels(library: &mut Library, songs: &[SongId], change: &LabelChange) {
+    for id in songs {
+        library.update_song_data(*id, |data| match change {
+            LabelChange::Add(label) => {
+                data.labels.insert(label.clone());
+            }
+            LabelChange::Remove(label) => {
+                data.labels.remove(label);
+            }
+        });
+    }
+}
+
+/// Every label in the library, with how many songs have it.
+pub fn all_labels(library: &Library) -> BTreeMap<String, usize> {
+    let mut labels = BTreeMap::new();
+    for (_, data) in library.songs_with_data() {
+        for label in &data.labels {
+            *labels.entry(label.clone()).or_insert(0) += 1;
+        }
+    }
+    labels
+}
+
+/// [`all_labels`], only counted again when the song data changed. It's shown every frame in
+/// menus and the settings, so it's kept in egui's memory in between.
+pub fn cached_all_labels(ui: &Ui, library: &Library) -> Arc<BTreeMap<String, usize>> {
+    let id = egui::Id::new("all_labels");
+    let cached: Option<(u64, Arc<BTreeMap<String, usize>>)> = ui.data().get_temp(id);
+    match cached {
+        Some((data_version, labels)) if data_version == library.data_version() => labels,
+        _ => {
+            let labels = Arc::new(all_labels(library));
+            ui.data()
+                .insert_temp(id, (library.data_version(), labels.clone()));
+            labels
+        }
+    }
+}
+
+/// A submenu to add labels to the songs, or remove them.
+#[must_use]
+pub fn show_labels_menu(ui: &mut Ui, library: &Library, songs: &[SongId]) -> Option<LabelChange> {
+    let mut change = None;
+
+    ui.menu_button("Labels", |ui| {
+        for label in cached_all_labels(ui, library).keys() {
+            let with_label = songs
+                .iter()
+                .filter(|id| library.get_song_data(**id).labels.contains(label))
+                .count();
+
+            let mut checked = with_label > 0 && with_label == songs.len();
+            let text = if with_label > 0 && !checked {
+                format!("{} (some)", label)
+            } else {
+                label.clone()
+            };
+            if ui.checkbox(&mut checked, text).clicked() {
+                change = Some(if checked {
+                    LabelChange::Add(label.clone())
+                } else {
+                    LabelChange::Remove(label.clone())
+                });
+            }
+        }
+
+        ui.separator();
+
+        // The menu is shown anew every frame, so the text being typed is kept in egui's memory.
+        let id = ui.id().with("new_label");
+        let mut new_label: String = ui.data().get_temp(id).unwrap_or_default();
+        let response = egui::TextEdit::singleline(&mut new_label)
+            .hint_text("New label")
+            .show(ui)
+            .response;
+        if response.lost_focus() && ui.input().key_pressed(Key::Enter) {
+            let label = new_label.trim();
+            if !label.is_empty() {
+                change = Some(LabelChange::Add(label.to_string()));
+            }
+            new_label.clear();
+        }
+        ui.data().insert_temp(id, new_label);
+    });
+
+    change
+}
+
+/// Shows the label as a small rounded chip. Returns whether it was clicked.
+pub fn show_label_chip(ui: &mut Ui, label: &str, selected: bool) -> bool {
+    let (fill, text_color) = if selected {
+        (ui.visuals().selection.bg_fill, Color32::WHITE)
+    } else {
+        (ui.visuals().faint_bg_color, ui.visuals().text_color())
+    };
+
+    egui::Frame::none()
+        .fill(fill)
+        .rounding(8.0)
+        .inner_margin(Margin::symmetric(6.0, 0.0))
+        .show(ui, |ui| {
+            ui.add(
+                egui::Label::new(RichText::new(label).small().color(text_color))
+                    .sense(egui::Sense::click()),
+            )
+        })
+        .inner
+        .clicked()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library::Song;
+
+    #[test]
+    fn test_change_labels() {
+        let mut library = Library::new();
+        let a = library.insert_song(Song::named("a"));
+        let b = library.insert_song(Song::named("b"));
+
+        change_labels(
+            &mut library,
+            &[a, b],
+            &LabelChange::Add("focus".to_string()),
+        );
+        change_labels(&mut library, &[a], &LabelChange::Add("workout".to_string()));
+        let labels = all_labels(&library);
+        assert_eq!(labels.get("focus"), Some(&2));
+        assert_eq!(labels.get("workout"), Some(&1));
+
+        change_labels(
+            &mut library,
+            &[a, b],
+            &LabelChange::Remove("focus".to_string()),
+        );
+        assert_eq!(all_labels(&library).len(), 1);
+        assert!(library.get_song_data(b).is_empty());
+    }
+}
@@ -1,66 +1,583 @@
+use crate::filename_pattern::{self, FilenamePattern};
+use crate::search_index::SearchIndex;
+use crate::song_data::{SongData, EMPTY_SONG_DATA};
+use crate::tag_editor::TagField;
 use camino::{Utf8Path, Utf8PathBuf};
+use lofty::{Accessor, AudioFile, ItemKey, TaggedFileExt};
+use serde_derive::{Deserialize, Serialize};
 use slotmap::basic::Iter;
-use slotmap::{new_key_type, SlotMap};
+use slotmap::{new_key_type, SecondaryMap, SlotMap};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
-#[derive(Default)]
-pub struct Library(SlotMap<SongId, Song>);
+/// Cloning is cheap, the songs are only copied once one of the clones changes.
+/// That way a search can keep working on a snapshot of the library, in the background.
+#[derive(Default, Clone)]
+pub struct Library {
+    songs: Arc<SlotMap<SongId, Song>>,
+    search_index: Arc<SearchIndex>,
+    /// Kept apart from the songs, because it changes all the time while playing, and what is
+    /// built from the tags and paths of the songs shouldn't be built again every time.
+    /// Only songs that have data are in it.
+    data: Arc<SecondaryMap<SongId, SongData>>,
+    /// The data of songs that aren't in the library, by path. So it isn't lost when
+    /// a song is missing for a while, like when its drive isn't connected.
+    orphaned_data: Arc<HashMap<Utf8PathBuf, SongData>>,
+    /// Goes up every time songs are inserted, removed or changed.
+    version: u64,
+    /// Goes up every time the data of a song changes, see [`Library::version`] for the songs.
+    data_version: u64,
+    /// Songs of which the file is gone, since they were checked.
+    missing: Arc<HashSet<SongId>>,
+    /// Gives songs without tags their tags from their file names, when scanning.
+    filename_pattern: Option<FilenamePattern>,
+}
 
 impl Library {
     pub fn new() -> Self {
         Default::default()
     }
 
+    /// Only songs scanned after this use the pattern.
+    pub fn set_filename_pattern(&mut self, pattern: Option<FilenamePattern>) {
+        self.filename_pattern = pattern;
+    }
+
     /// Scans the given directory and inserts any songs it encounters.
     /// TODO (2023-02-03): What should we do with potential duplicates?
-    /// TODO (2023-02-03): Error handling and logging.
     pub fn insert_from_directory(&mut self, directory: &Utf8Path) {
-        if !directory.is_dir() {
-            return;
+        let mut paths = Vec::new();
+        find_songs(directory, &mut paths);
+
+        for path in paths {
+            self.insert_song(Song::from_file(path, self.filename_pattern.as_ref()));
+        }
+    }
+
+    /// Makes the library match the given directory.
+    /// Removes the songs that are no longer in it, and inserts the ones that are new.
+    /// Songs that were already in the library keep their id.
+    pub fn rescan_directory(&mut self, directory: &Utf8Path) {
+        let mut paths = Vec::new();
+        find_songs(directory, &mut paths);
+        let mut new_paths: HashSet<Utf8PathBuf> = paths.into_iter().collect();
+
+        let removed: Vec<SongId> = self
+            .songs()
+            // Whatever is left in `new_paths` afterwards isn't in the library yet.
+            .filter(|(_, song)| !new_paths.remove(&song.path))
+            .map(|(id, _)| id)
+            .collect();
+        for id in removed {
+            self.remove_song(id);
+        }
+
+        for path in new_paths {
+            self.insert_song(Song::from_file(path, self.filename_pattern.as_ref()));
+        }
+    }
+
+    /// The song gets the data that was saved for its path, if there is any.
+    pub fn insert_song(&mut self, song: Song) -> SongId {
+        let data = Arc::make_mut(&mut self.orphaned_data).remove(&song.path);
+        let id = Arc::make_mut(&mut self.songs).insert(song);
+        Arc::make_mut(&mut self.search_index).insert(id, &self.songs[id]);
+        if let Some(data) = data {
+            Arc::make_mut(&mut self.data).insert(id, data);
+            self.data_version += 1;
+        }
+        self.version += 1;
+        id
+    }
+
+    pub fn remove_song(&mut self, id: SongId) -> Option<Song> {
+        let song = Arc::make_mut(&mut self.songs).remove(id)?;
+        Arc::make_mut(&mut self.missing).remove(&id);
+        Arc::make_mut(&mut self.search_index).remove(id, &song);
+        if let Some(data) = Arc::make_mut(&mut self.data).remove(id) {
+            Arc::make_mut(&mut self.orphaned_data).insert(song.path.clone(), data);
+            self.data_version += 1;
         }
+        self.version += 1;
+        Some(song)
+    }
+
+    /// Reads the file of the song again, after its tags changed.
+    /// The song keeps its id, data and the date it was added, which writing changes.
+    pub fn reload_song(&mut self, id: SongId) {
+        let Some(old_song) = self.songs.get(id) else {
+            return;
+        };
+        let mut song = Song::from_file(old_song.path.clone(), self.filename_pattern.as_ref());
+        song.date_added = old_song.date_added;
+
+        let old_song = std::mem::replace(&mut Arc::make_mut(&mut self.songs)[id], song);
+        let search_index = Arc::make_mut(&mut self.search_index);
+        search_index.remove(id, &old_song);
+        search_index.insert(id, &self.songs[id]);
+        self.version += 1;
+    }
+
+    /// After the file of the song was moved. The song keeps its id, so playlists keep it too.
+    pub fn move_song(&mut self, id: SongId, path: Utf8PathBuf) {
+        let Some(song) = self.songs.get(id) else {
+            return;
+        };
+        let mut song = song.clone();
+        song.path = path;
+        Arc::make_mut(&mut self.missing).remove(&id);
+
+        let old_song = std::mem::replace(&mut Arc::make_mut(&mut self.songs)[id], song);
+        let search_index = Arc::make_mut(&mut self.search_index);
+        search_index.remove(id, &old_song);
+        search_index.insert(id, &self.songs[id]);
+        self.version += 1;
+    }
+
+    pub fn is_missing(&self, id: SongId) -> bool {
+        self.missing.contains(&id)
+    }
+
+    /// The songs of which the file is gone, in no particular order.
+    pub fn missing_songs(&self) -> Vec<SongId> {
+        self.missing.iter().copied().collect()
+    }
+
+    pub fn missing_count(&self) -> usize {
+        self.missing.len()
+    }
 
-        let paths = directory.read_dir().expect("Could not read dir");
+    /// Marks the song as missing or found. See [`crate::missing_files`] to check all songs.
+    pub fn set_missing(&mut self, id: SongId, missing: bool) {
+        if !self.songs.contains_key(id) || self.is_missing(id) == missing {
+            return;
+        }
+        let missing_songs = Arc::make_mut(&mut self.missing);
+        if missing {
+            missing_songs.insert(id);
+        } else {
+            missing_songs.remove(&id);
+        }
+        self.version += 1;
+    }
 
-        for entry in paths.filter_map(|entry| entry.ok()) {
-            let path = Utf8PathBuf::from_path_buf(entry.path().to_owned())
-                .expect("Path is not a utf-8 path");
+    /// Only changes the data version, not the version of the songs.
+    pub fn update_song_data(&mut self, id: SongId, update: impl FnOnce(&mut SongData)) {
+        if !self.songs.contains_key(id) {
+            return;
+        }
+        let all_data = Arc::make_mut(&mut self.data);
+        let data = all_data
+            .entry(id)
+            .expect("The song is in the library")
+            .or_default();
+        update(data);
+        if data.is_empty() {
+            all_data.remove(id);
+        }
+        self.data_version += 1;
+    }
 
-            if path.is_dir() {
-                self.insert_from_directory(&path);
-            } else if let Some(extension) = path.extension() {
-                if sound::SUPPORTED_EXTENSIONS.contains(&extension) {
-                    // Found a song.
-                    let song = Song::from_file(path);
-                    self.0.insert(song);
-                }
+    /// Gives the songs their saved data. Data of songs that aren't in the library
+    /// is kept, for when they are inserted.
+    pub fn load_song_data(&mut self, mut data: HashMap<Utf8PathBuf, SongData>) {
+        let all_data = Arc::make_mut(&mut self.data);
+        for (id, song) in self.songs.iter() {
+            if let Some(song_data) = data.remove(&song.path) {
+                all_data.insert(id, song_data);
             }
         }
+        Arc::make_mut(&mut self.orphaned_data).extend(data);
+        self.data_version += 1;
+    }
+
+    /// What isn't in the file of the song, like its play stats.
+    /// Songs without data, or that aren't in the library, get empty data.
+    pub fn get_song_data(&self, id: SongId) -> &SongData {
+        self.data.get(id).unwrap_or(&EMPTY_SONG_DATA)
+    }
+
+    /// Only the songs that have data.
+    pub fn songs_with_data(&self) -> slotmap::secondary::Iter<'_, SongId, SongData> {
+        self.data.iter()
+    }
+
+    /// The data of all songs, including those that aren't in the library, to be saved.
+    pub fn song_data(&self) -> HashMap<Utf8PathBuf, SongData> {
+        let mut data = (*self.orphaned_data).clone();
+        data.extend(self.data.iter().filter_map(|(id, song_data)| {
+            let song = self.songs.get(id)?;
+            Some((song.path.clone(), song_data.clone()))
+        }));
+        data
     }
 
     pub fn songs(&self) -> Iter<SongId, Song> {
-        self.0.iter()
+        self.songs.iter()
     }
 
     pub fn song_count(&self) -> usize {
-        self.0.len()
+        self.songs.len()
     }
 
     pub fn get_song(&self, id: SongId) -> Option<&Song> {
-        self.0.get(id)
+        self.songs.get(id)
+    }
+
+    pub fn search_index(&self) -> &SearchIndex {
+        &self.search_index
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Views that show or pick songs by their data should check this too.
+    pub fn data_version(&self) -> u64 {
+        self.data_version
+    }
+}
+
+/// Collects the paths of all songs in the directory, including subdirectories.
+/// TODO (2023-02-03): Error handling and logging.
+pub fn find_songs(directory: &Utf8Path, paths: &mut Vec<Utf8PathBuf>) {
+    if !directory.is_dir() {
+        return;
+    }
+
+    let entries = directory.read_dir().expect("Could not read dir");
+
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path =
+            Utf8PathBuf::from_path_buf(entry.path().to_owned()).expect("Path is not a utf-8 path");
+
+        if path.is_dir() {
+            find_songs(&path, paths);
+        } else if let Some(extension) = path.extension() {
+            if sound::SUPPORTED_EXTENSIONS.contains(&extension) {
+                // Found a song.
+                paths.push(path);
+            }
+        }
     }
 }
 
 new_key_type! { pub struct SongId; }
 
+#[derive(Clone, Default)]
 pub struct Song {
     pub title: String,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    /// The artist of the whole album, for compilations. Most files don't have it.
+    pub album_artist: Option<String>,
+    pub genre: Option<String>,
+    pub year: Option<u32>,
+    pub track_number: Option<u32>,
+    pub disc_number: Option<u32>,
+    pub duration: Option<Duration>,
+    /// When the file was added to the library folder.
+    pub date_added: Option<SystemTime>,
+    /// In bytes, when it was scanned. Helps finding the file again after it moved.
+    pub file_size: Option<u64>,
     pub path: Utf8PathBuf,
 }
 
 impl Song {
-    fn from_file(path: Utf8PathBuf) -> Self {
-        // TODO (2023-02-03): Load metadata from the file?
-        let title = path.file_stem().unwrap_or("Unnamed").replace('_', " ");
-        Self { title, path }
+    pub fn from_file(path: Utf8PathBuf, filename_pattern: Option<&FilenamePattern>) -> Self {
+        // TODO (2023-02-03): Log files with unreadable tags?
+        let tagged_file = lofty::read_from_path(&path).ok();
+        let tag = tagged_file.as_ref().and_then(|file| file.primary_tag());
+
+        // Files without a title count as untagged, and get what's missing from their file name.
+        let from_name = match (tag.and_then(|tag| tag.title()), filename_pattern) {
+            (None, Some(pattern)) => pattern.extract(&path).unwrap_or_default(),
+            _ => Vec::new(),
+        };
+        let from_name = |field| filename_pattern::value(&from_name, field);
+        let number_from_name = |field| from_name(field).and_then(|value| value.parse().ok());
+
+        // Untagged files still get a usable title from their file name.
+        let title = tag
+            .and_then(|tag| tag.title())
+            .map(|title| title.into_owned())
+            .or_else(|| from_name(TagField::Title))
+            .unwrap_or_else(|| path.file_stem().unwrap_or("Unnamed").replace('_', " "));
+        let artist = tag
+            .and_then(|tag| tag.artist())
+            .map(|artist| artist.into_owned())
+            .or_else(|| from_name(TagField::Artist));
+        let album = tag
+            .and_then(|tag| tag.album())
+            .map(|album| album.into_owned())
+            .or_else(|| from_name(TagField::Album));
+        let album_artist = tag
+            .and_then(|tag| tag.get_string(&ItemKey::AlbumArtist))
+            .map(|album_artist| album_artist.to_string());
+        let genre = tag
+            .and_then(|tag| tag.genre())
+            .map(|genre| genre.into_owned())
+            .or_else(|| from_name(TagField::Genre));
+        let duration = tagged_file
+            .as_ref()
+            .map(|file| file.properties().duration())
+            .filter(|duration| !duration.is_zero());
+
+        // Not every filesystem keeps track of when a file was created.
+        // The last modification is the next best thing.
+        let metadata = path.metadata().ok();
+        let date_added = metadata
+            .as_ref()
+            .and_then(|metadata| metadata.created().or_else(|_| metadata.modified()).ok());
+
+        Self {
+            title,
+            artist,
+            album,
+            album_artist,
+            genre,
+            year: tag
+                .and_then(|tag| tag.year())
+                .or_else(|| number_from_name(TagField::Year)),
+            track_number: tag
+                .and_then(|tag| tag.track())
+                .or_else(|| number_from_name(TagField::Track)),
+            disc_number: tag
+                .and_then(|tag| tag.disk())
+                .or_else(|| number_from_name(TagField::Disc)),
+            duration,
+            date_added,
+            file_size: metadata.map(|metadata| metadata.len()),
+            path,
+        }
+    }
+
+    /// The file extension in uppercase, like "MP3".
+    pub fn format(&self) -> Option<String> {
+        self.path
+            .extension()
+            .map(|extension| extension.to_uppercase())
+    }
+}
+
+/// For tests, so they only have to fill in what they are about.
+#[cfg(test)]
+impl Song {
+    /// The path is made from the title, so songs with different titles are different files.
+    pub fn named(title: &str) -> Self {
+        Song {
+            title: title.to_string(),
+            path: Utf8PathBuf::from(format!("{}.mp3", title)),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_artist(self, artist: &str) -> Self {
+        Song {
+            artist: Some(artist.to_string()),
+            ..self
+        }
+    }
+
+    pub fn with_album(self, album: &str) -> Self {
+        Song {
+            album: Some(album.to_string()),
+            ..self
+        }
+    }
+
+    pub fn with_album_artist(self, album_artist: &str) -> Self {
+        Song {
+            album_artist: Some(album_artist.to_string()),
+            ..self
+        }
+    }
+
+    pub fn with_genre(self, genre: &str) -> Self {
+        Song {
+            genre: Some(genre.to_string()),
+            ..self
+        }
+    }
+
+    pub fn with_year(self, year: u32) -> Self {
+        Song {
+            year: Some(year),
+            ..self
+        }
+    }
+
+    pub fn with_track(self, track_number: u32) -> Self {
+        Song {
+            track_number: Some(track_number),
+            ..self
+        }
+    }
+
+    pub fn with_duration(self, duration: Duration) -> Self {
+        Song {
+            duration: Some(duration),
+            ..self
+        }
+    }
+
+    pub fn with_path(self, path: &str) -> Self {
+        Song {
+            path: Utf8PathBuf::from(path),
+            ..self
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Deserialize, Serialize)]
+pub enum SortKey {
+    Artist,
+    Album,
+    Track,
+    Title,
+    Year,
+    Genre,
+    Duration,
+    Path,
+    Format,
+    DateAdded,
+    PlayCount,
+    SkipCount,
+    LastPlayed,
+    FirstPlayed,
+    ListeningTime,
+    Rating,
+    Favourite,
+}
+
+impl SortKey {
+    pub const ALL: [SortKey; 17] = [
+        SortKey::Artist,
+        SortKey::Album,
+        SortKey::Track,
+        SortKey::Title,
+        SortKey::Year,
+        SortKey::Genre,
+        SortKey::Duration,
+        SortKey::Path,
+        SortKey::Format,
+        SortKey::DateAdded,
+        SortKey::PlayCount,
+        SortKey::SkipCount,
+        SortKey::LastPlayed,
+        SortKey::FirstPlayed,
+        SortKey::ListeningTime,
+        SortKey::Rating,
+        SortKey::Favourite,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SortKey::Artist => "Artist",
+            SortKey::Album => "Album",
+            SortKey::Track => "Track number",
+            SortKey::Title => "Title",
+            SortKey::Year => "Year",
+            SortKey::Genre => "Genre",
+            SortKey::Duration => "Duration",
+            SortKey::Path => "Path",
+            SortKey::Format => "Format",
+            SortKey::DateAdded => "Date added",
+            SortKey::PlayCount => "Play count",
+            SortKey::SkipCount => "Skips",
+            SortKey::LastPlayed => "Last played",
+            SortKey::FirstPlayed => "First played",
+            SortKey::ListeningTime => "Listening time",
+            SortKey::Rating => "Rating",
+            SortKey::Favourite => "Favourite",
+        }
+    }
+
+    /// Whether songs are sorted by their data, instead of by their tags or files.
+    pub fn uses_song_data(&self) -> bool {
+        matches!(
+            self,
+            SortKey::PlayCount
+                | SortKey::SkipCount
+                | SortKey::LastPlayed
+                | SortKey::FirstPlayed
+                | SortKey::ListeningTime
+                | SortKey::Rating
+                | SortKey::Favourite
+        )
+    }
+
+    /// Songs with the same artist are sorted by album,
+    /// and songs on the same album are sorted by track number.
+    /// Songs missing the value are sorted last, as are ids that aren't in the library.
+    pub fn compare(&self, library: &Library, a: SongId, b: SongId) -> Ordering {
+        match (library.get_song(a), library.get_song(b)) {
+            (Some(a_song), Some(b_song)) => self.compare_songs(
+                (a_song, library.get_song_data(a)),
+                (b_song, library.get_song_data(b)),
+            ),
+            (a, b) => a.is_none().cmp(&b.is_none()),
+        }
+    }
+
+    fn compare_songs(
+        &self,
+        (a, a_data): (&Song, &SongData),
+        (b, b_data): (&Song, &SongData),
+    ) -> Ordering {
+        let by_track = || {
+            compare_missing_last(&a.disc_number, &b.disc_number)
+                .then_with(|| compare_missing_last(&a.track_number, &b.track_number))
+        };
+        let by_album =
+            || compare_missing_last(&lowercase(&a.album), &lowercase(&b.album)).then_with(by_track);
+
+        match self {
+            SortKey::Artist => compare_missing_last(&lowercase(&a.artist), &lowercase(&b.artist))
+                .then_with(by_album),
+            SortKey::Album => by_album(),
+            SortKey::Track => by_track(),
+            SortKey::Title => a.title.to_lowercase().cmp(&b.title.to_lowercase()),
+            SortKey::Year => compare_missing_last(&a.year, &b.year).then_with(by_album),
+            SortKey::Genre => compare_missing_last(&lowercase(&a.genre), &lowercase(&b.genre)),
+            SortKey::Duration => compare_missing_last(&a.duration, &b.duration),
+            SortKey::Path => a.path.cmp(&b.path),
+            SortKey::Format => compare_missing_last(&a.format(), &b.format()),
+            SortKey::DateAdded => compare_missing_last(&a.date_added, &b.date_added),
+            SortKey::PlayCount => a_data.stats.play_count.cmp(&b_data.stats.play_count),
+            SortKey::SkipCount => a_data.stats.skip_count.cmp(&b_data.stats.skip_count),
+            SortKey::LastPlayed => {
+                compare_missing_last(&a_data.stats.last_played, &b_data.stats.last_played)
+            }
+            SortKey::FirstPlayed => {
+                compare_missing_last(&a_data.stats.first_played, &b_data.stats.first_played)
+            }
+            SortKey::ListeningTime => a_data
+                .stats
+                .listening_time
+                .cmp(&b_data.stats.listening_time),
+            // Unrated songs go last, favourites first.
+            SortKey::Rating => compare_missing_last(
+                &(a_data.rating > 0).then_some(a_data.rating),
+                &(b_data.rating > 0).then_some(b_data.rating),
+            ),
+            SortKey::Favourite => b_data.favourite.cmp(&a_data.favourite),
+        }
+    }
+}
+
+fn lowercase(text: &Option<String>) -> Option<String> {
+    text.as_ref().map(|text| text.to_lowercase())
+}
+
+fn compare_missing_last<T: Ord>(a: &Option<T>, b: &Option<T>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => a.cmp(b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
     }
 }
 
@@ -77,4 +594,23 @@ mod tests {
 
         assert_eq!(songs.len(), 2);
     }
+
+    #[test]
+    fn test_rescan_directory() {
+        let mut library = Library::new();
+        library.insert_from_directory(Utf8Path::new("../example_audio/subfolder"));
+        let (kept_id, _) = library.songs().next().unwrap();
+
+        library.rescan_directory(Utf8Path::new("../example_audio"));
+        assert_eq!(library.song_count(), 2);
+        assert_eq!(
+            library.get_song(kept_id).unwrap().title,
+            "Dark Mystery".to_string()
+        );
+
+        let version = library.version();
+        library.remove_song(kept_id);
+        assert!(library.get_song(kept_id).is_none());
+        assert!(library.version() > version);
+    }
 }
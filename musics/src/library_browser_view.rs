@@ -0,0 +1,373 @@
+use crate::library::{Library, Song, SongId, SortKey};
+use crate::library_search_view::{show_songs_menu, LibraryViewCommand};
+use crate::selection::{song_row_interaction, Selection};
+use eframe::egui;
+use eframe::egui::{Ui, Widget};
+use std::collections::HashSet;
+
+/// The panes that narrow down the songs, from left to right.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum Pane {
+    Genre,
+    Artist,
+    Album,
+}
+
+impl Pane {
+    const ALL: [Pane; 3] = [Pane::Genre, Pane::Artist, Pane::Album];
+
+    fn all_text(&self) -> &'static str {
+        match self {
+            Pane::Genre => "All genres",
+            Pane::Artist => "All artists",
+            Pane::Album => "All albums",
+        }
+    }
+
+    fn unknown_text(&self) -> &'static str {
+        match self {
+            Pane::Genre => "Unknown genre",
+            Pane::Artist => "Unknown artist",
+            Pane::Album => "Unknown album",
+        }
+    }
+
+    /// Songs without a value are grouped under "".
+    fn value<'a>(&self, song: &'a Song) -> &'a str {
+        let value = match self {
+            Pane::Genre => &song.genre,
+            Pane::Artist => &song.artist,
+            Pane::Album => &song.album,
+        };
+        value.as_deref().unwrap_or("")
+    }
+}
+
+/// What is selected in each pane. `None` selects everything.
+#[derive(Clone, Default, PartialEq, Debug)]
+struct Filter {
+    genre: Option<String>,
+    artist: Option<String>,
+    album: Option<String>,
+}
+
+impl Filter {
+    fn get(&self, pane: Pane) -> &Option<String> {
+        match pane {
+            Pane::Genre => &self.genre,
+            Pane::Artist => &self.artist,
+            Pane::Album => &self.album,
+        }
+    }
+
+    /// Selecting something in a pane, resets the panes to the right of it.
+    fn select(&mut self, pane: Pane, value: Option<String>) {
+        match pane {
+            Pane::Genre => {
+                self.genre = value;
+                self.artist = None;
+                self.album = None;
+            }
+            Pane::Artist => {
+                self.artist = value;
+                self.album = None;
+            }
+            Pane::Album => self.album = value,
+        }
+    }
+
+    /// Whether the song passes the selection of the pane, and of the panes left of it.
+    fn matches_up_to(&self, pane: Pane, song: &Song) -> bool {
+        Pane::ALL
+            .iter()
+            .take_while(|other| **other != pane)
+            .chain(std::iter::once(&pane))
+            .all(|pane| {
+                self.get(*pane)
+                    .as_ref()
+                    .is_none_or(|value| pane.value(song) == value)
+            })
+    }
+
+    fn matches(&self, song: &Song) -> bool {
+        self.matches_up_to(Pane::Album, song)
+    }
+}
+
+/// The contents of all panes for a filter.
+#[derive(Default, Debug, PartialEq)]
+struct Browsed {
+    genres: Vec<String>,
+    /// Of the selected genre.
+    artists: Vec<String>,
+    /// Of the selected genre and artist.
+    albums: Vec<String>,
+    /// Of the selected genre, artist and album, sorted by artist, album and track.
+    tracks: Vec<SongId>,
+}
+
+impl Browsed {
+    fn values(&self, pane: Pane) -> &[String] {
+        match pane {
+            Pane::Genre => &self.genres,
+            Pane::Artist => &self.artists,
+            Pane::Album => &self.albums,
+        }
+    }
+}
+
+/// Fills all panes, in a single pass over the library.
+fn browse(library: &Library, filter: &Filter) -> Browsed {
+    let mut browsed = Browsed::default();
+    let mut tracks: Vec<(SongId, &Song)> = Vec::new();
+
+    let mut genres: HashSet<&str> = HashSet::new();
+    let mut artists: HashSet<&str> = HashSet::new();
+    let mut albums: HashSet<&str> = HashSet::new();
+
+    for (id, song) in library.songs() {
+        genres.insert(Pane::Genre.value(song));
+        if !filter.matches_up_to(Pane::Genre, song) {
+            continue;
+        }
+        artists.insert(Pane::Artist.value(song));
+        if !filter.matches_up_to(Pane::Artist, song) {
+            continue;
+        }
+        albums.insert(Pane::Album.value(song));
+        if filter.matches(song) {
+            tracks.push((id, song));
+        }
+    }
+
+    browsed.genres = sorted_values(genres);
+    browsed.artists = sorted_values(artists);
+    browsed.albums = sorted_values(albums);
+
+    tracks.sort_by(|(a, _), (b, _)| SortKey::Artist.compare(library, *a, *b));
+    browsed.tracks = tracks.into_iter().map(|(id, _)| id).collect();
+
+    browsed
+}
+
+/// Ignores case, and puts the unknown value last.
+fn sorted_values(values: HashSet<&str>) -> Vec<String> {
+    let mut values: Vec<String> = values.into_iter().map(str::to_string).collect();
+    values.sort_by_cached_key(|value| (value.is_empty(), value.to_lowercase(), value.clone()));
+    values
+}
+
+/// Songs in the library, in the order an album is listened to.
+fn songs_in_order(library: &Library, filter: &Filter) -> Vec<SongId> {
+    let mut songs: Vec<SongId> = library
+        .songs()
+        .filter(|(_, song)| filter.matches(song))
+        .map(|(id, _)| id)
+        .collect();
+    songs.sort_by(|a, b| SortKey::Artist.compare(library, *a, *b));
+    songs
+}
+
+/// Browses the library by genre, then artist, then album. Selecting something in a pane
+/// narrows down the panes to the right of it.
+pub struct LibraryBrowserView {
+    filter: Filter,
+    browsed: Browsed,
+    /// The library version and filter `browsed` is for.
+    browsed_for: Option<(u64, Filter)>,
+    /// Indices of the selected songs in the tracks pane.
+    track_selection: Selection,
+}
+
+impl LibraryBrowserView {
+    pub fn new() -> Self {
+        LibraryBrowserView {
+            filter: Filter::default(),
+            browsed: Browsed::default(),
+            browsed_for: None,
+            track_selection: Selection::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn show(&mut self, ui: &mut Ui, library: &Library) -> LibraryViewCommand {
+        let mut command = LibraryViewCommand::None;
+
+        let browsed_for = (library.version(), self.filter.clone());
+        if self.browsed_for.as_ref() != Some(&browsed_for) {
+            self.browsed = browse(library, &self.filter);
+            self.browsed_for = Some(browsed_for);
+            self.track_selection.clear();
+        }
+
+        ui.columns(4, |columns| {
+            for (pane, ui) in Pane::ALL.iter().zip(columns.iter_mut()) {
+                if let Some(pane_command) = self.show_pane(ui, library, *pane) {
+                    command = pane_command;
+                }
+            }
+            if let Some(tracks_command) = self.show_tracks(&mut columns[3], library) {
+                command = tracks_command;
+            }
+        });
+
+        command
+    }
+
+    fn show_pane(
+        &mut self,
+        ui: &mut Ui,
+        library: &Library,
+        pane: Pane,
+    ) -> Option<LibraryViewCommand> {
+        let mut command = None;
+        let row_height = ui.spacing().interact_size.y;
+        let row_count = self.browsed.values(pane).len() + 1;
+
+        egui::ScrollArea::vertical()
+            .id_source(pane)
+            .auto_shrink([false, false])
+            .show_rows(ui, row_height, row_count, |ui, row_range| {
+                for row in row_range {
+                    // The first row selects everything.
+                    let value = row
+                        .checked_sub(1)
+                        .and_then(|index| self.browsed.values(pane).get(index))
+                        .cloned();
+                    let text = match &value {
+                        None => format!("{} ({})", pane.all_text(), row_count - 1),
+                        Some(value) if value.is_empty() => pane.unknown_text().to_string(),
+                        Some(value) => value.clone(),
+                    };
+
+                    let response =
+                        egui::SelectableLabel::new(self.filter.get(pane) == &value, text).ui(ui);
+                    if response.clicked() {
+                        self.filter.select(pane, value.clone());
+                    }
+
+                    let mut filter = self.filter.clone();
+                    filter.select(pane, value);
+                    if pane == Pane::Album && response.double_clicked() {
+                        command = Some(LibraryViewCommand::PlaySongsNext(songs_in_order(
+                            library, &filter,
+                        )));
+                    }
+                    response.context_menu(|ui| {
+                        if let Some(menu_command) =
+                            show_songs_menu(ui, || songs_in_order(library, &filter))
+                        {
+                            command = Some(menu_command);
+                            ui.close_menu();
+                        }
+                    });
+                }
+            });
+
+        command
+    }
+
+    fn show_tracks(&mut self, ui: &mut Ui, library: &Library) -> Option<LibraryViewCommand> {
+        let mut command = None;
+        let row_height = ui.spacing().interact_size.y;
+
+        egui::ScrollArea::vertical()
+            .id_source("tracks")
+            .auto_shrink([false, false])
+            .show_rows(
+                ui,
+                row_height,
+                self.browsed.tracks.len(),
+                |ui, row_range| {
+                    for index in row_range {
+                        let Some(song) = self
+                            .browsed
+                            .tracks
+                            .get(index)
+                            .and_then(|id| library.get_song(*id))
+                        else {
+                            continue;
+                        };
+
+                        let text = match song.track_number {
+                            Some(track) => format!("{}. {}", track, song.title),
+                            None => song.title.clone(),
+                        };
+                        let response =
+                            egui::SelectableLabel::new(self.track_selection.contains(index), text)
+                                .ui(ui);
+                        if let Some(row_command) = song_row_interaction(
+                            &mut self.track_selection,
+                            response,
+                            index,
+                            &self.browsed.tracks,
+                        ) {
+                            command = Some(row_command);
+                        }
+                    }
+                },
+            );
+
+        command
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_browse() {
+        let mut library = Library::new();
+        let rock_2 = library.insert_song(
+            Song::named("First 2")
+                .with_genre("Rock")
+                .with_artist("Band")
+                .with_album("First")
+                .with_track(2),
+        );
+        let rock_1 = library.insert_song(
+            Song::named("First 1")
+                .with_genre("Rock")
+                .with_artist("Band")
+                .with_album("First")
+                .with_track(1),
+        );
+        let other = library.insert_song(
+            Song::named("Second 1")
+                .with_genre("Rock")
+                .with_artist("Other")
+                .with_album("Second")
+                .with_track(1),
+        );
+        library.insert_song(
+            Song::named("Quiet 1")
+                .with_genre("ambient")
+                .with_artist("Band")
+                .with_album("Quiet")
+                .with_track(1),
+        );
+        library.insert_song(Song::named("Untagged").with_track(1));
+
+        let browsed = browse(&library, &Filter::default());
+        assert_eq!(browsed.genres, vec!["ambient", "Rock", ""]);
+        assert_eq!(browsed.artists, vec!["Band", "Other", ""]);
+        assert_eq!(browsed.tracks.len(), 5);
+
+        let mut filter = Filter::default();
+        filter.select(Pane::Genre, Some("Rock".to_string()));
+        let browsed = browse(&library, &filter);
+        assert_eq!(browsed.genres.len(), 3);
+        assert_eq!(browsed.artists, vec!["Band", "Other"]);
+        assert_eq!(browsed.albums, vec!["First", "Second"]);
+        assert_eq!(browsed.tracks, vec![rock_1, rock_2, other]);
+
+        filter.select(Pane::Artist, Some("Band".to_string()));
+        filter.select(Pane::Album, Some("First".to_string()));
+        assert_eq!(songs_in_order(&library, &filter), vec![rock_1, rock_2]);
+
+        // Picking another genre forgets the artist and album.
+        filter.select(Pane::Genre, None);
+        assert_eq!(filter, Filter::default());
+    }
+}
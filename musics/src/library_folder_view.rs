@@ -0,0 +1,224 @@
+use crate::library::{Library, SongId};
+use crate::library_search_view::{show_songs_menu, LibraryViewCommand};
+use crate::natural_sort::natural_cmp;
+use camino::{Utf8Path, Utf8PathBuf};
+use eframe::egui;
+use eframe::egui::{Ui, Widget};
+use std::collections::HashMap;
+
+/// A folder in the library directory, with everything in it sorted in natural order.
+#[derive(Default, Debug)]
+struct Folder {
+    name: String,
+    /// Relative to the library directory.
+    path: Utf8PathBuf,
+    folders: Vec<Folder>,
+    /// Where each folder is in `folders`, by name. Only while building the tree, sorting
+    /// moves the folders.
+    folder_indices: HashMap<String, usize>,
+    /// The songs directly in this folder, with their file names.
+    songs: Vec<(String, SongId)>,
+    /// Of this folder and all folders in it.
+    song_count: usize,
+}
+
+impl Folder {
+    /// Builds the tree of folders under `root`. Songs outside of `root` are left out.
+    fn from_library(library: &Library, root: &Utf8Path) -> Self {
+        let mut root_folder = Folder::default();
+
+        for (id, song) in library.songs() {
+            let Ok(relative_path) = song.path.strip_prefix(root) else {
+                continue;
+            };
+            let Some(file_name) = relative_path.file_name() else {
+                continue;
+            };
+
+            let mut folder = &mut root_folder;
+            for name in relative_path.parent().into_iter().flat_map(Utf8Path::iter) {
+                folder = folder.subfolder(name);
+            }
+            folder.songs.push((file_name.to_string(), id));
+        }
+
+        root_folder.sort_and_count();
+        root_folder
+    }
+
+    fn subfolder(&mut self, name: &str) -> &mut Folder {
+        let index = match self.folder_indices.get(name) {
+            Some(index) => *index,
+            None => {
+                self.folders.push(Folder {
+                    name: name.to_string(),
+                    path: self.path.join(name),
+                    ..Default::default()
+                });
+                self.folder_indices
+                    .insert(name.to_string(), self.folders.len() - 1);
+                self.folders.len() - 1
+            }
+        };
+        &mut self.folders[index]
+    }
+
+    fn sort_and_count(&mut self) {
+        self.folder_indices = HashMap::new();
+        self.folders.sort_by(|a, b| natural_cmp(&a.name, &b.name));
+        self.songs.sort_by(|(a, _), (b, _)| natural_cmp(a, b));
+
+        self.song_count = self.songs.len();
+        for folder in &mut self.folders {
+            folder.sort_and_count();
+            self.song_count += folder.song_count;
+        }
+    }
+
+    /// All songs in this folder and the folders in it, in natural order.
+    /// The songs of a folder come before the folders in it, the same as they are shown.
+    fn songs_recursive(&self) -> Vec<SongId> {
+        let mut songs = Vec::with_capacity(self.song_count);
+        self.collect_songs(&mut songs);
+        songs
+    }
+
+    fn collect_songs(&self, songs: &mut Vec<SongId>) {
+        songs.extend(self.songs.iter().map(|(_, id)| *id));
+        for folder in &self.folders {
+            folder.collect_songs(songs);
+        }
+    }
+}
+
+/// Shows the library the way it is laid out in folders on disk.
+/// Helps with songs that have bad tags, or none at all.
+pub struct LibraryFolderView {
+    root: Folder,
+    /// The library version and directory `root` was built for.
+    built_for: Option<(u64, Utf8PathBuf)>,
+}
+
+impl LibraryFolderView {
+    pub fn new() -> Self {
+        LibraryFolderView {
+            root: Folder::default(),
+            built_for: None,
+        }
+    }
+
+    #[must_use]
+    pub fn show(
+        &mut self,
+        ui: &mut Ui,
+        library: &Library,
+        library_directory: &Utf8Path,
+    ) -> LibraryViewCommand {
+        let mut command = LibraryViewCommand::None;
+
+        if library_directory == "" {
+            ui.label("Select a library directory in the config first.");
+            return command;
+        }
+
+        let built_for = (library.version(), library_directory.to_owned());
+        if self.built_for.as_ref() != Some(&built_for) {
+            self.root = Folder::from_library(library, library_directory);
+            self.built_for = Some(built_for);
+        }
+
+        let header_response = ui.label(format!(
+            "{} ({} songs)",
+            library_directory, self.root.song_count
+        ));
+        header_response.context_menu(|ui| {
+            if let Some(menu_command) = show_songs_menu(ui, || self.root.songs_recursive()) {
+                command = menu_command;
+                ui.close_menu();
+            }
+        });
+
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                if let Some(folder_command) = show_folder_contents(ui, &self.root) {
+                    command = folder_command;
+                }
+            });
+
+        command
+    }
+}
+
+fn show_folder_contents(ui: &mut Ui, folder: &Folder) -> Option<LibraryViewCommand> {
+    let mut command = None;
+
+    for (file_name, id) in &folder.songs {
+        let response = egui::Label::new(file_name)
+            .sense(egui::Sense::click())
+            .ui(ui)
+            .on_hover_text("Double-click to add to the playlist.");
+        if response.double_clicked() {
+            command = Some(LibraryViewCommand::AddSongsToPlaylist(vec![*id]));
+        }
+        response.context_menu(|ui| {
+            if let Some(menu_command) = show_songs_menu(ui, || vec![*id]) {
+                command = Some(menu_command);
+                ui.close_menu();
+            }
+        });
+    }
+
+    for subfolder in &folder.folders {
+        // Only the folders that are open show what is in them.
+        let collapsing =
+            egui::CollapsingHeader::new(format!("{} ({})", subfolder.name, subfolder.song_count))
+                .id_source(&subfolder.path)
+                .show(ui, |ui| show_folder_contents(ui, subfolder));
+
+        if let Some(Some(folder_command)) = collapsing.body_returned {
+            command = Some(folder_command);
+        }
+        collapsing.header_response.context_menu(|ui| {
+            if let Some(menu_command) = show_songs_menu(ui, || subfolder.songs_recursive()) {
+                command = Some(menu_command);
+                ui.close_menu();
+            }
+        });
+    }
+
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library::Song;
+
+    #[test]
+    fn test_folder_tree() {
+        let mut library = Library::new();
+        let ten = library.insert_song(
+            Song::named("/music/Album/10 - y.mp3").with_path("/music/Album/10 - y.mp3"),
+        );
+        let two = library
+            .insert_song(Song::named("/music/Album/2 - x.mp3").with_path("/music/Album/2 - x.mp3"));
+        let disc_2 = library.insert_song(
+            Song::named("/music/Album/CD 2/1 - a.mp3").with_path("/music/Album/CD 2/1 - a.mp3"),
+        );
+        let loose =
+            library.insert_song(Song::named("/music/loose.ogg").with_path("/music/loose.ogg"));
+        library.insert_song(Song::named("/elsewhere/other.mp3").with_path("/elsewhere/other.mp3"));
+
+        let root = Folder::from_library(&library, Utf8Path::new("/music"));
+        assert_eq!(root.song_count, 4);
+        assert_eq!(root.songs, vec![("loose.ogg".to_string(), loose)]);
+
+        let album = &root.folders[0];
+        assert_eq!(album.name, "Album");
+        assert_eq!(album.song_count, 3);
+        assert_eq!(album.folders[0].path, "Album/CD 2");
+        assert_eq!(album.songs_recursive(), vec![two, ten, disc_2]);
+        assert_eq!(root.songs_recursive(), vec![loose, two, ten, disc_2]);
+    }
+}
@@ -1,25 +1,48 @@
-use crate::library::{Library, SongId};
+use crate::labels::{show_labels_menu, LabelChange};
+use crate::library::{Library, Song, SongId};
+use crate::query;
+use crate::search::{BackgroundSearch, SearchResults, RANK_AT_A_TIME};
+use crate::selection::Selection;
+use crate::song_data::SongData;
 use eframe::egui;
-use eframe::egui::{Color32, Key, Modifiers, RichText, Sense, Ui, Widget};
+use eframe::egui::text::LayoutJob;
+use eframe::egui::{Color32, Key, Modifiers, TextFormat, Ui, Widget};
 
 pub struct LibrarySearchView {
-    /// String used to filter song titles.
+    /// String used to filter songs, see [`query`] for what it can contain.
     filter_string: String,
-    /// Ordered list of found songs that we can display.
-    found_songs: Vec<SongId>,
+    /// Why the current filter string could not be parsed, if it couldn't.
+    query_error: Option<String>,
+    /// The search that is still running for the latest query, if any,
+    /// and the versions of the library it searches.
+    pending_search: Option<((u64, u64), BackgroundSearch)>,
+    /// The search that found `results`, while it's still finding the rest of them.
+    completing_search: Option<BackgroundSearch>,
+    /// Found songs that we can display. More are found when scrolling down.
+    results: SearchResults,
+    /// The [`Library::version`] and [`Library::data_version`] `results` were found in.
+    found_in_library_version: (u64, u64),
     /// Whether to show the list of songs or not.
     show_results: bool,
-    /// The song in the list that will be added when pressing "enter".
+    /// The song in the list that will be added when pressing "enter", when none are selected.
+    /// Pressing "up" and "down" moves it.
     highlighted_song_index: usize,
+    /// Indices of the selected songs in `found_songs`.
+    selection: Selection,
 }
 
 impl LibrarySearchView {
     pub fn new() -> Self {
         LibrarySearchView {
             filter_string: String::new(),
-            found_songs: Vec::new(),
+            query_error: None,
+            pending_search: None,
+            completing_search: None,
+            results: SearchResults::empty(),
+            found_in_library_version: (0, 0),
             show_results: false,
             highlighted_song_index: 0,
+            selection: Selection::new(),
         }
     }
 
@@ -33,10 +56,23 @@ impl LibrarySearchView {
         ui.label(format!("{} songs", library.song_count()));
 
         let previous_filter = self.filter_string.clone();
-        let search_response = egui::TextEdit::singleline(&mut self.filter_string)
-            .hint_text("Search library")
-            .show(ui)
-            .response;
+        let search_response = ui
+            .vertical(|ui| {
+                let response = egui::TextEdit::singleline(&mut self.filter_string)
+                    .hint_text("Search library")
+                    .show(ui)
+                    .response
+                    .on_hover_text(
+                        "Search titles, or filter like: artist:name year:>=2000 dur:<5m -live",
+                    );
+
+                if let Some(error) = &self.query_error {
+                    ui.colored_label(Color32::LIGHT_RED, error);
+                }
+
+                response
+            })
+            .inner;
 
         // Ctrl-F focuses on the search bar, and clears it's contents.
         // TODO: Have an app-wide method of detecting shortcuts?
@@ -65,9 +101,18 @@ impl LibrarySearchView {
         }
         if lost_focus && ui.input().key_pressed(Key::Enter) {
             // Pressing "enter" automatically drops focus from the search bar.
-            // But we also want to add the current selected search result to the playlist.
-            if let Some(id) = self.found_songs.get(self.highlighted_song_index) {
-                command = LibraryViewCommand::AddSongToPlaylist(*id)
+            // But we also want to add the selected search results to the playlist.
+            // Or play them next when shift is held, or right away when ctrl is held.
+            let songs = self.selected_songs();
+            if !songs.is_empty() {
+                let modifiers = ui.input().modifiers;
+                command = if modifiers.command {
+                    LibraryViewCommand::PlaySongsNow(songs)
+                } else if modifiers.shift {
+                    LibraryViewCommand::PlaySongsNext(songs)
+                } else {
+                    LibraryViewCommand::AddSongsToPlaylist(songs)
+                };
             }
             // Because it auto-drops focus, but we actually don't want to drop focus at all, we need to re-aquire focus.
             search_response.request_focus();
@@ -75,76 +120,326 @@ impl LibrarySearchView {
             self.highlighted_song_index = 0;
         }
 
-        if search_response.has_focus() && ui.input().key_pressed(Key::ArrowUp) {
-            self.highlighted_song_index = (self.highlighted_song_index - 1).max(0);
-        } else if search_response.has_focus() && ui.input().key_pressed(Key::ArrowDown) {
-            self.highlighted_song_index =
-                (self.highlighted_song_index + 1).min(self.found_songs.len() - 1);
+        if search_response.has_focus() {
+            let last_index = self.results.found().len().saturating_sub(1);
+            let new_index = if ui.input().key_pressed(Key::ArrowUp) {
+                Some(self.highlighted_song_index.saturating_sub(1))
+            } else if ui.input().key_pressed(Key::ArrowDown) {
+                Some((self.highlighted_song_index + 1).min(last_index))
+            } else {
+                None
+            };
+
+            if let Some(new_index) = new_index {
+                self.move_highlight(new_index, ui.input().modifiers.shift);
+            }
         }
 
         if previous_filter != self.filter_string || search_gained_focus {
-            self.update_found_songs(library);
+            self.start_search(ui.ctx(), library);
         }
 
+        self.receive_search_results();
+
         command
     }
 
-    /// The search is case-insensitive.
-    fn update_found_songs(&mut self, library: &Library) {
-        let lowercase_filter = self.filter_string.to_lowercase();
-        self.found_songs = library
-            .songs()
-            .filter(|(_, song)| song.title.to_lowercase().contains(&lowercase_filter))
-            .map(|(id, _)| id)
-            .collect();
+    /// Searches in the background, so typing doesn't have to wait for it.
+    /// While the filter string can't be parsed, the previous results stay.
+    fn start_search(&mut self, ctx: &egui::Context, library: &Library) {
+        let query = match query::parse(&self.filter_string) {
+            Ok(query) => query,
+            Err(error) => {
+                self.query_error = Some(error.to_string());
+                return;
+            }
+        };
+        self.query_error = None;
 
+        // When more is typed, only the songs that were already found can still match.
+        // Play stats change all the time, which only matters to queries that look at them.
+        let (library_version, data_version) = self.found_in_library_version;
+        let same_library = library_version == library.version()
+            && (data_version == library.data_version() || !query.uses_song_data());
+        let narrow_to = self
+            .results
+            .narrowing()
+            .filter(|_| query.is_narrower_than(self.results.query()) && same_library);
+
+        // Replacing the previous searches cancels them.
+        let versions = (library.version(), library.data_version());
+        let search = BackgroundSearch::start(
+            library.clone(),
+            query,
+            narrow_to,
+            RANK_AT_A_TIME,
+            ctx.clone(),
+        );
+        self.pending_search = Some((versions, search));
+        self.completing_search = None;
+    }
+
+    fn receive_search_results(&mut self) {
+        // All results start with the ones that are shown already, unless more were found
+        // while scrolling in the meantime. So the selection stays.
+        let all_found = self
+            .completing_search
+            .as_ref()
+            .and_then(|search| search.try_take_results());
+        if let Some(results) = all_found {
+            self.results = results;
+            self.completing_search = None;
+        }
+
+        let Some((_, search)) = &self.pending_search else {
+            return;
+        };
+        let Some(results) = search.try_take_results() else {
+            return;
+        };
+
+        if let Some((versions, search)) = self.pending_search.take() {
+            self.found_in_library_version = versions;
+            self.completing_search = (!results.is_complete()).then_some(search);
+        }
+        self.results = results;
         self.highlighted_song_index = 0;
+        self.selection.clear();
+    }
+
+    /// The selected songs, in the order they were found.
+    /// Or the highlighted song, if none are selected.
+    fn selected_songs(&self) -> Vec<SongId> {
+        if self.selection.is_empty() {
+            return self
+                .results
+                .found()
+                .get(self.highlighted_song_index)
+                .cloned()
+                .into_iter()
+                .collect();
+        }
+
+        self.selection
+            .indices()
+            .filter_map(|index| self.results.found().get(*index))
+            .cloned()
+            .collect()
+    }
+
+    /// When `extend_selection` is set, selects everything between the anchor and the
+    /// highlighted song. Otherwise only the highlighted song is selected.
+    fn move_highlight(&mut self, index: usize, extend_selection: bool) {
+        if extend_selection {
+            self.selection.extend_to(self.highlighted_song_index, index);
+        } else {
+            self.selection.select_only(index);
+        }
+        self.highlighted_song_index = index;
     }
 
     #[must_use]
-    pub fn show_search_results(&self, ui: &mut Ui, library: &Library) -> LibraryViewCommand {
+    pub fn show_search_results(&mut self, ui: &mut Ui, library: &Library) -> LibraryViewCommand {
         let mut command = LibraryViewCommand::None;
 
-        ui.label(format!(
-            "{} / {} songs",
-            self.found_songs.len(),
-            library.song_count()
-        ));
+        ui.horizontal(|ui| {
+            if self.results.is_complete() {
+                ui.label(format!(
+                    "{} / {} songs",
+                    self.results.found().len(),
+                    library.song_count()
+                ));
+            } else {
+                ui.label(format!(
+                    "{}+ / {} songs",
+                    self.results.found().len(),
+                    library.song_count()
+                ))
+                .on_hover_text("More are found when scrolling down.");
+            }
+            if !self.selection.is_empty() {
+                ui.label(format!("({} selected)", self.selection.len()));
+            }
+        });
+        ui.horizontal(|ui| {
+            if ui.button("Add all").clicked() {
+                command = LibraryViewCommand::AddSongsToPlaylist(self.all_found(library));
+            }
+            if ui
+                .button("Replace playlist")
+                .on_hover_text("Replaces the songs in the playlist with all found songs.")
+                .clicked()
+            {
+                command = LibraryViewCommand::ReplacePlaylist(self.all_found(library));
+            }
+        });
 
-        let text_style = egui::TextStyle::Body;
-        let row_height = ui.text_style_height(&text_style);
+        let row_height = ui.spacing().interact_size.y;
+        let found_count = self.results.found().len();
 
         egui::ScrollArea::both()
             .auto_shrink([false, false])
-            .show_rows(ui, row_height, self.found_songs.len(), |ui, row_range| {
-                for (index, id, song) in self
-                    .found_songs
-                    .iter()
-                    .enumerate()
-                    .skip(row_range.start)
-                    .take(row_range.len())
-                    .filter_map(|(index, id)| library.get_song(*id).map(|song| (index, id, song)))
-                {
-                    let mut title_text = RichText::new(&song.title);
-                    if self.highlighted_song_index == index {
-                        title_text = title_text.color(Color32::LIGHT_GREEN);
-                    }
+            .show_rows(ui, row_height, found_count, |ui, row_range| {
+                // Finds the next screenful before scrolling gets to the end.
+                if !self.results.is_complete() {
+                    self.results
+                        .find_more(library, row_range.end + RANK_AT_A_TIME);
+                }
+
+                for index in row_range {
+                    let Some((id, song)) = self
+                        .results
+                        .found()
+                        .get(index)
+                        .and_then(|id| library.get_song(*id).map(|song| (*id, song)))
+                    else {
+                        continue;
+                    };
+
+                    let text_color = if self.highlighted_song_index == index {
+                        Color32::LIGHT_GREEN
+                    } else {
+                        ui.visuals().text_color()
+                    };
+                    let data = library.get_song_data(id);
+                    let title_text = self.highlighted_title(ui, song, data, text_color);
 
-                    let song_response = egui::Label::new(title_text)
-                        .wrap(false)
-                        .sense(Sense::click())
-                        .ui(ui);
-                    if song_response.clicked() {
-                        command = LibraryViewCommand::AddSongToPlaylist(*id);
+                    let song_response =
+                        egui::SelectableLabel::new(self.selection.contains(index), title_text)
+                            .ui(ui);
+                    if song_response.clicked() || song_response.secondary_clicked() {
+                        self.highlighted_song_index = index;
                     }
+                    // A plain click adds the song to the playlist.
+                    if self.selection.handle_row_clicks(&song_response, index) {
+                        command = LibraryViewCommand::AddSongsToPlaylist(vec![id]);
+                    }
+                    song_response.context_menu(|ui| {
+                        if let Some(menu_command) = self.show_context_menu(ui, library) {
+                            command = menu_command;
+                            ui.close_menu();
+                        }
+                    });
                 }
             });
 
         command
     }
+
+    /// Returns the command for the clicked menu item, if any.
+    fn show_context_menu(&mut self, ui: &mut Ui, library: &Library) -> Option<LibraryViewCommand> {
+        if let Some(command) = show_songs_menu(ui, || self.selected_songs()) {
+            return Some(command);
+        }
+        let selected = self.selected_songs();
+        if let Some(change) = show_labels_menu(ui, library, &selected) {
+            return Some(LibraryViewCommand::ChangeLabels(selected, change));
+        }
+
+        ui.separator();
+
+        if ui.button("Add all results").clicked() {
+            return Some(LibraryViewCommand::AddSongsToPlaylist(
+                self.all_found(library),
+            ));
+        }
+        if ui.button("Replace playlist with results").clicked() {
+            return Some(LibraryViewCommand::ReplacePlaylist(self.all_found(library)));
+        }
+
+        None
+    }
+
+    /// Also the ones that haven't been shown yet.
+    fn all_found(&mut self, library: &Library) -> Vec<SongId> {
+        self.results.find_all(library).to_vec()
+    }
+
+    /// Colors the characters of the title that match the search.
+    /// Only done for the rows that are visible, so the positions aren't kept around.
+    fn highlighted_title(
+        &self,
+        ui: &Ui,
+        song: &Song,
+        data: &SongData,
+        text_color: Color32,
+    ) -> LayoutJob {
+        let title = song.title.as_str();
+        let positions = self
+            .results
+            .query()
+            .evaluate(song, data)
+            .map(|found| found.positions)
+            .unwrap_or_default();
+
+        let font_id = egui::TextStyle::Body.resolve(ui.style());
+        let format = |color| TextFormat::simple(font_id.clone(), color);
+
+        let mut job = LayoutJob::default();
+        let mut matched = positions.iter().peekable();
+        let mut run_start = 0;
+        let mut run_is_match = false;
+
+        for (index, (byte_index, _)) in title.char_indices().enumerate() {
+            let is_match = matched.next_if_eq(&&index).is_some();
+            if is_match != run_is_match {
+                let color = if run_is_match {
+                    Color32::GOLD
+                } else {
+                    text_color
+                };
+                job.append(&title[run_start..byte_index], 0.0, format(color));
+                run_start = byte_index;
+                run_is_match = is_match;
+            }
+        }
+
+        let color = if run_is_match {
+            Color32::GOLD
+        } else {
+            text_color
+        };
+        job.append(&title[run_start..], 0.0, format(color));
+
+        job
+    }
 }
 
 pub enum LibraryViewCommand {
     None,
-    AddSongToPlaylist(SongId),
+    AddSongsToPlaylist(Vec<SongId>),
+    /// Queues the songs to play after the current song, before the playlist continues.
+    PlaySongsNext(Vec<SongId>),
+    /// Queues the songs in front of the rest of the queue, and skips to the first of them.
+    PlaySongsNow(Vec<SongId>),
+    /// Replaces the songs in the playlist, as a single edit that can be undone.
+    ReplacePlaylist(Vec<SongId>),
+    ChangeLabels(Vec<SongId>, LabelChange),
+    /// Opens the tag editor for the songs.
+    EditTags(Vec<SongId>),
+}
+
+/// The menu items for acting on some songs of the library.
+/// Returns the command for the clicked menu item, if any.
+///
+/// The songs are only asked for when an item is clicked, as the menu is shown every frame
+/// while it's open, and finding the songs can take a while.
+pub fn show_songs_menu(
+    ui: &mut Ui,
+    songs: impl FnOnce() -> Vec<SongId>,
+) -> Option<LibraryViewCommand> {
+    let command: fn(Vec<SongId>) -> LibraryViewCommand = if ui.button("Play now").clicked() {
+        LibraryViewCommand::PlaySongsNow
+    } else if ui.button("Play next").clicked() {
+        LibraryViewCommand::PlaySongsNext
+    } else if ui.button("Add to playlist").clicked() {
+        LibraryViewCommand::AddSongsToPlaylist
+    } else if ui.button("Replace playlist").clicked() {
+        LibraryViewCommand::ReplacePlaylist
+    } else if ui.button("Edit tags").clicked() {
+        LibraryViewCommand::EditTags
+    } else {
+        return None;
+    };
+    Some(command(songs()))
 }
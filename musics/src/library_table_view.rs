@@ -0,0 +1,401 @@
+use crate::library::{Library, Song, SongId, SortKey};
+use crate::library_search_view::LibraryViewCommand;
+use crate::selection::{song_row_interaction, Selection};
+use crate::song_data::SongData;
+use eframe::egui;
+use eframe::egui::{Align2, Response, RichText, Sense, TextStyle, Ui};
+use egui_extras::{Column, TableBuilder};
+use serde_derive::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The columns of a new config: which are shown, and how wide they are.
+const DEFAULT_COLUMNS: [(SortKey, bool, f32); 17] = [
+    (SortKey::Title, true, 250.0),
+    (SortKey::Artist, true, 150.0),
+    (SortKey::Album, true, 150.0),
+    (SortKey::Track, true, 50.0),
+    (SortKey::Year, true, 50.0),
+    (SortKey::Genre, false, 100.0),
+    (SortKey::Duration, true, 70.0),
+    (SortKey::Rating, true, 70.0),
+    (SortKey::Favourite, false, 30.0),
+    (SortKey::PlayCount, true, 50.0),
+    (SortKey::SkipCount, false, 50.0),
+    (SortKey::LastPlayed, false, 90.0),
+    (SortKey::FirstPlayed, false, 90.0),
+    (SortKey::ListeningTime, false, 70.0),
+    (SortKey::DateAdded, false, 90.0),
+    (SortKey::Path, false, 300.0),
+    (SortKey::Format, false, 60.0),
+];
+
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub struct ColumnSettings {
+    pub key: SortKey,
+    pub visible: bool,
+    pub width: f32,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+pub struct LibraryTableSettings {
+    /// In the order they are shown in.
+    pub columns: Vec<ColumnSettings>,
+    pub sort_key: SortKey,
+    pub sort_descending: bool,
+}
+
+impl Default for LibraryTableSettings {
+    fn default() -> Self {
+        LibraryTableSettings {
+            columns: DEFAULT_COLUMNS
+                .iter()
+                .map(|(key, visible, width)| ColumnSettings {
+                    key: *key,
+                    visible: *visible,
+                    width: *width,
+                })
+                .collect(),
+            sort_key: SortKey::Artist,
+            sort_descending: false,
+        }
+    }
+}
+
+impl LibraryTableSettings {
+    /// Configs saved by older versions don't know about columns that were added later.
+    fn add_missing_columns(&mut self) {
+        for (key, visible, width) in DEFAULT_COLUMNS {
+            if !self.columns.iter().any(|column| column.key == key) {
+                self.columns.push(ColumnSettings {
+                    key,
+                    visible,
+                    width,
+                });
+            }
+        }
+    }
+
+    fn click_header(&mut self, key: SortKey) {
+        if self.sort_key == key {
+            self.sort_descending = !self.sort_descending;
+        } else {
+            self.sort_key = key;
+            self.sort_descending = false;
+        }
+    }
+}
+
+/// Shows the whole library as a table, sorted by one of the columns.
+pub struct LibraryTableView {
+    /// The songs of the library, in the order they are shown in.
+    sorted_songs: Vec<SongId>,
+    /// The library version, data version (only when sorted by data), sort key and direction
+    /// `sorted_songs` was sorted for.
+    sorted_for: Option<(u64, Option<u64>, SortKey, bool)>,
+    /// Indices of the selected songs in `sorted_songs`.
+    selection: Selection,
+}
+
+impl LibraryTableView {
+    pub fn new() -> Self {
+        LibraryTableView {
+            sorted_songs: Vec::new(),
+            sorted_for: None,
+            selection: Selection::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn show(
+        &mut self,
+        ui: &mut Ui,
+        library: &Library,
+        settings: &mut LibraryTableSettings,
+    ) -> LibraryViewCommand {
+        let mut command = LibraryViewCommand::None;
+
+        settings.add_missing_columns();
+        self.sort(library, settings);
+
+        ui.horizontal(|ui| {
+            ui.label(format!("{} songs", self.sorted_songs.len()));
+            if !self.selection.is_empty() {
+                ui.label(format!("({} selected)", self.selection.len()));
+            }
+            ui.menu_button("Columns", |ui| show_column_menu(ui, settings));
+        });
+
+        let visible: Vec<usize> = (0..settings.columns.len())
+            .filter(|index| settings.columns[*index].visible)
+            .collect();
+        let visible_keys: Vec<SortKey> = visible
+            .iter()
+            .map(|index| settings.columns[*index].key)
+            .collect();
+
+        let row_height = ui.spacing().interact_size.y;
+
+        // The table remembers the column widths under its id. Showing other columns
+        // under another id, makes it start from the widths in the settings again.
+        ui.push_id(&visible_keys, |ui| {
+            let mut table = TableBuilder::new(ui)
+                .striped(true)
+                .resizable(true)
+                .auto_shrink([false, false]);
+            for index in &visible {
+                table = table.column(
+                    Column::initial(settings.columns[*index].width)
+                        .at_least(20.0)
+                        .clip(true),
+                );
+            }
+
+            table
+                .header(row_height, |mut header| {
+                    for key in &visible_keys {
+                        header.col(|ui| {
+                            let arrow = match (settings.sort_key == *key, settings.sort_descending)
+                            {
+                                (true, false) => " ⏶",
+                                (true, true) => " ⏷",
+                                (false, _) => "",
+                            };
+                            let text = RichText::new(format!("{}{}", key.name(), arrow)).strong();
+
+                            let response = ui
+                                .add(egui::Label::new(text).sense(Sense::click()))
+                                .on_hover_text("Click to sort, right-click to pick columns.");
+                            if response.clicked() {
+                                settings.click_header(*key);
+                            }
+                            response.context_menu(|ui| show_column_menu(ui, settings));
+                        });
+                    }
+                })
+                .body(|body| {
+                    // Widths can only be read back here, after the user has resized them.
+                    for (index, width) in visible.iter().zip(body.widths()) {
+                        settings.columns[*index].width = *width;
+                    }
+
+                    body.rows(row_height, self.sorted_songs.len(), |row_index, mut row| {
+                        let Some((id, song)) = self
+                            .sorted_songs
+                            .get(row_index)
+                            .and_then(|id| library.get_song(*id).map(|song| (*id, song)))
+                        else {
+                            return;
+                        };
+                        let selected = self.selection.contains(row_index);
+                        let data = library.get_song_data(id);
+
+                        for key in &visible_keys {
+                            let (_, cell) = row.col(|ui| {
+                                let response =
+                                    show_cell(ui, selected, &column_text(*key, song, data));
+
+                                if let Some(row_command) = song_row_interaction(
+                                    &mut self.selection,
+                                    response,
+                                    row_index,
+                                    &self.sorted_songs,
+                                ) {
+                                    command = row_command;
+                                }
+                            });
+                            cell.on_hover_text_at_pointer(song.path.as_str());
+                        }
+                    });
+                });
+        });
+
+        command
+    }
+
+    fn sort(&mut self, library: &Library, settings: &LibraryTableSettings) {
+        // Only sorted again when play stats change, if it's sorted by them.
+        let sorted_for = (
+            library.version(),
+            settings
+                .sort_key
+                .uses_song_data()
+                .then(|| library.data_version()),
+            settings.sort_key,
+            settings.sort_descending,
+        );
+        if self.sorted_for == Some(sorted_for) {
+            return;
+        }
+
+        self.sorted_songs = sorted_songs(library, settings.sort_key, settings.sort_descending);
+        self.sorted_for = Some(sorted_for);
+        // The indices now point at other songs.
+        self.selection.clear();
+    }
+}
+
+fn sorted_songs(library: &Library, key: SortKey, descending: bool) -> Vec<SongId> {
+    let mut songs: Vec<SongId> = library.songs().map(|(id, _)| id).collect();
+    if descending {
+        songs.sort_by(|a, b| key.compare(library, *b, *a));
+    } else {
+        songs.sort_by(|a, b| key.compare(library, *a, *b));
+    }
+    songs
+}
+
+fn show_column_menu(ui: &mut Ui, settings: &mut LibraryTableSettings) {
+    let visible_count = settings
+        .columns
+        .iter()
+        .filter(|column| column.visible)
+        .count();
+
+    for column in &mut settings.columns {
+        // Without any columns, there would be no header left to right-click.
+        let can_toggle = !column.visible || visible_count > 1;
+        ui.add_enabled(
+            can_toggle,
+            egui::Checkbox::new(&mut column.visible, column.key.name()),
+        );
+    }
+}
+
+/// Fills the whole cell, so that clicking anywhere in the row selects it.
+fn show_cell(ui: &mut Ui, selected: bool, text: &str) -> Response {
+    let (rect, response) = ui.allocate_exact_size(ui.available_size(), Sense::click());
+
+    let visuals = ui.visuals();
+    let text_color = if selected {
+        ui.painter()
+            .rect_filled(rect, 0.0, visuals.selection.bg_fill);
+        visuals.selection.stroke.color
+    } else {
+        visuals.text_color()
+    };
+    ui.painter().text(
+        rect.left_center(),
+        Align2::LEFT_CENTER,
+        text,
+        TextStyle::Body.resolve(ui.style()),
+        text_color,
+    );
+
+    response
+}
+
+fn column_text(key: SortKey, song: &Song, data: &SongData) -> String {
+    let text = match key {
+        SortKey::Title => Some(song.title.clone()),
+        SortKey::Artist => song.artist.clone(),
+        SortKey::Album => song.album.clone(),
+        SortKey::Track => song.track_number.map(|track| track.to_string()),
+        SortKey::Year => song.year.map(|year| year.to_string()),
+        SortKey::Genre => song.genre.clone(),
+        SortKey::Duration => song.duration.map(crate::duration_to_time_display),
+        SortKey::Path => Some(song.path.to_string()),
+        SortKey::Format => song.format(),
+        SortKey::DateAdded => song.date_added.map(format_date),
+        SortKey::PlayCount => Some(data.stats.play_count.to_string()),
+        SortKey::SkipCount => Some(data.stats.skip_count.to_string()),
+        SortKey::LastPlayed => data.stats.last_played.map(format_date),
+        SortKey::FirstPlayed => data.stats.first_played.map(format_date),
+        SortKey::ListeningTime => Some(crate::duration_to_time_display(data.stats.listening_time)),
+        SortKey::Rating => Some("★".repeat(usize::from(data.rating))),
+        SortKey::Favourite => data.favourite.then(|| "♥".to_string()),
+    };
+    text.unwrap_or_default()
+}
+
+/// Formats the date as "2023-02-03", in UTC.
+pub fn format_date(time: SystemTime) -> String {
+    let days = time
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_secs() / (24 * 60 * 60))
+        .unwrap_or(0);
+
+    // Converts days since 1970-01-01 to a date in the gregorian calendar.
+    // See http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    format!("{year}-{month:0>2}-{day:0>2}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_sorted_songs() {
+        let mut library = Library::new();
+        let b = library.insert_song(Song::named("b").with_year(2001));
+        let a = library.insert_song(Song::named("A"));
+        let c = library.insert_song(Song::named("c").with_year(1999));
+
+        assert_eq!(sorted_songs(&library, SortKey::Title, false), vec![a, b, c]);
+        assert_eq!(sorted_songs(&library, SortKey::Title, true), vec![c, b, a]);
+        assert_eq!(sorted_songs(&library, SortKey::Year, false), vec![c, b, a]);
+
+        library.update_song_data(a, |data| data.rating = 5);
+        library.update_song_data(c, |data| {
+            data.rating = 2;
+            data.favourite = true;
+        });
+        assert_eq!(
+            sorted_songs(&library, SortKey::Rating, false),
+            vec![c, a, b]
+        );
+        assert_eq!(sorted_songs(&library, SortKey::Rating, true), vec![b, a, c]);
+        assert_eq!(sorted_songs(&library, SortKey::Favourite, false)[0], c);
+    }
+
+    #[test]
+    fn test_settings() {
+        let mut settings = LibraryTableSettings {
+            columns: vec![ColumnSettings {
+                key: SortKey::Path,
+                visible: true,
+                width: 400.0,
+            }],
+            ..Default::default()
+        };
+        settings.add_missing_columns();
+        assert_eq!(settings.columns.len(), DEFAULT_COLUMNS.len());
+        assert_eq!(settings.columns[0].width, 400.0);
+
+        settings.click_header(SortKey::Title);
+        assert_eq!(
+            (settings.sort_key, settings.sort_descending),
+            (SortKey::Title, false)
+        );
+        settings.click_header(SortKey::Title);
+        assert_eq!(
+            (settings.sort_key, settings.sort_descending),
+            (SortKey::Title, true)
+        );
+    }
+
+    #[test]
+    fn test_format_date() {
+        let date = |seconds| format_date(UNIX_EPOCH + Duration::from_secs(seconds));
+        assert_eq!(date(0), "1970-01-01");
+        assert_eq!(date(951_782_400), "2000-02-29");
+        assert_eq!(date(1_675_382_400), "2023-02-03");
+        assert_eq!(date(1_704_067_199), "2023-12-31");
+    }
+}
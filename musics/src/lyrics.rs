@@ -0,0 +1,284 @@
+//! Lyrics of songs, from an `.lrc` file next to the song, or from its tags:
+//! SYLT (synced) and USLT frames in ID3v2, and LYRICS in Vorbis comments.
+//!
+//! Synced lyrics are in the LRC format, with one or more timestamps in front of each line:
+//!
+//! ```text
+//! [offset:+250]
+//! [00:12.00][01:30.50]The chorus, sung twice
+//! [00:17.20]The next line
+//! ```
+
+use camino::Utf8Path;
+use lofty::id3::v2::{SynchronizedText, TimestampFormat};
+use lofty::{ItemKey, TaggedFileExt};
+use std::time::Duration;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct LyricLine {
+    /// When the line starts. `None` when the lyrics aren't synced.
+    pub time: Option<Duration>,
+    pub text: String,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Lyrics {
+    /// Synced lines are sorted by time.
+    pub lines: Vec<LyricLine>,
+}
+
+impl Lyrics {
+    /// Lines without timestamps are only kept when no line has one, then they're plain lyrics.
+    pub fn parse_lrc(text: &str) -> Self {
+        let mut synced = Vec::new();
+        let mut plain = Vec::new();
+        // In milliseconds. Positive offsets make the lines come earlier.
+        let mut offset: i64 = 0;
+
+        for line in text.lines() {
+            let mut rest = line.trim();
+            let mut times = Vec::new();
+            let mut has_tags = false;
+            while let Some((tag, after)) =
+                rest.strip_prefix('[').and_then(|tag| tag.split_once(']'))
+            {
+                if let Some(time) = parse_timestamp(tag) {
+                    times.push(time);
+                } else if let Some(value) = tag.strip_prefix("offset:") {
+                    offset = value.trim().parse().unwrap_or(offset);
+                } else if !is_metadata(tag) {
+                    // Not a tag after all, but part of the text.
+                    break;
+                }
+                has_tags = true;
+                rest = after.trim_start();
+            }
+
+            let text = remove_word_timestamps(rest);
+            if times.is_empty() {
+                if !has_tags {
+                    plain.push(LyricLine { time: None, text });
+                }
+            } else {
+                synced.extend(times.into_iter().map(|time| LyricLine {
+                    time: Some(time),
+                    text: text.clone(),
+                }));
+            }
+        }
+
+        if synced.is_empty() {
+            return Lyrics { lines: plain };
+        }
+
+        for line in &mut synced {
+            if let Some(time) = &mut line.time {
+                let millis = i64::try_from(time.as_millis()).unwrap_or(i64::MAX);
+                *time = Duration::from_millis(millis.saturating_sub(offset).max(0) as u64);
+            }
+        }
+        // Stable, so lines at the same time stay in order.
+        synced.sort_by_key(|line| line.time);
+        Lyrics { lines: synced }
+    }
+
+    pub fn is_synced(&self) -> bool {
+        self.lines.iter().any(|line| line.time.is_some())
+    }
+
+    /// The index of the line that is sung at `elapsed`. `None` before the first line.
+    pub fn current_line(&self, elapsed: Duration) -> Option<usize> {
+        if !self.is_synced() {
+            return None;
+        }
+        let after = self
+            .lines
+            .partition_point(|line| line.time.is_some_and(|time| time <= elapsed));
+        after.checked_sub(1)
+    }
+
+    fn from_sylt(sylt: &SynchronizedText) -> Option<Self> {
+        // MPEG frame timestamps would need the frame length, which the tag doesn't know.
+        if sylt.information.timestamp_format != TimestampFormat::MS {
+            return None;
+        }
+        let mut lines: Vec<LyricLine> = sylt
+            .content
+            .iter()
+            .map(|(millis, text)| LyricLine {
+                time: Some(Duration::from_millis(u64::from(*millis))),
+                // Each entry often starts with the newline of the line before.
+                text: text.trim().to_string(),
+            })
+            .collect();
+        lines.sort_by_key(|line| line.time);
+        Some(Lyrics { lines })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.lines.iter().all(|line| line.text.is_empty())
+    }
+}
+
+/// Like `mm:ss.xx`, `mm:ss:xx`, `mm:ss.xxx` or `mm:ss`.
+fn parse_timestamp(tag: &str) -> Option<Duration> {
+    let (minutes, seconds) = tag.split_once(':')?;
+    let minutes: u64 = minutes.trim().parse().ok()?;
+    let (seconds, fraction) = match seconds.split_once(['.', ':']) {
+        Some((seconds, fraction)) => (seconds, fraction),
+        None => (seconds, ""),
+    };
+    let seconds: u64 = seconds.parse().ok()?;
+    if !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let millis = match fraction.len() {
+        0 => 0,
+        length @ 1..=3 => fraction.parse::<u64>().ok()? * 10u64.pow(3 - length as u32),
+        _ => fraction.get(..3)?.parse().ok()?,
+    };
+    let millis = minutes
+        .checked_mul(60)?
+        .checked_add(seconds)?
+        .checked_mul(1000)?
+        .checked_add(millis)?;
+    Some(Duration::from_millis(millis))
+}
+
+/// Tags like `[ar:Artist]`, which say something about the lyrics instead of being part of them.
+fn is_metadata(tag: &str) -> bool {
+    tag.split_once(':')
+        .is_some_and(|(key, _)| !key.is_empty() && key.chars().all(|c| c.is_ascii_alphabetic()))
+}
+
+/// Enhanced LRC has timestamps for every word, like `<00:12.50>word`. Only lines are shown.
+fn remove_word_timestamps(text: &str) -> String {
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        let Some(end) = rest[start..].find('>') else {
+            break;
+        };
+        let tag = &rest[start + 1..start + end];
+        result.push_str(&rest[..start]);
+        if parse_timestamp(tag).is_none() {
+            result.push_str(&rest[start..=start + end]);
+        }
+        rest = &rest[start + end + 1..];
+    }
+    result.push_str(rest);
+    result.trim().to_string()
+}
+
+/// Looks for an `.lrc` file with the same name as the song first, then in the tags.
+/// Synced lyrics in the tags go before plain ones.
+pub fn load(path: &Utf8Path) -> Option<Lyrics> {
+    if let Ok(text) = std::fs::read_to_string(path.with_extension("lrc")) {
+        let lyrics = Lyrics::parse_lrc(&text);
+        if !lyrics.is_empty() {
+            return Some(lyrics);
+        }
+    }
+
+    let tagged_file = lofty::read_from_path(path).ok()?;
+    let sylt_key = ItemKey::Unknown("SYLT".to_string());
+    let synced = tagged_file
+        .tags()
+        .iter()
+        .filter_map(|tag| tag.get_binary(&sylt_key, false))
+        .filter_map(|sylt| SynchronizedText::parse(sylt).ok())
+        .filter_map(|sylt| Lyrics::from_sylt(&sylt));
+    // USLT and LYRICS can have LRC timestamps as well.
+    let embedded = tagged_file
+        .tags()
+        .iter()
+        .filter_map(|tag| tag.get_string(&ItemKey::Lyrics))
+        .map(Lyrics::parse_lrc);
+
+    synced.chain(embedded).find(|lyrics| !lyrics.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    fn line(millis: u64, text: &str) -> LyricLine {
+        LyricLine {
+            time: Some(Duration::from_millis(millis)),
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_lrc() {
+        let lyrics = Lyrics::parse_lrc(
+            "[ti:Song]\n\
+            [ar:Artist]\n\
+            [offset:+500]\n\
+            [00:12.00][01:30.5]Chorus\n\
+            [00:17.25] <00:17.25>Next <00:18.00>line\n\
+            [1:02:003]Third\n\
+            [02:00]\n",
+        );
+        assert_eq!(
+            lyrics.lines,
+            vec![
+                line(11_500, "Chorus"),
+                line(16_750, "Next line"),
+                line(61_503, "Third"),
+                line(90_000, "Chorus"),
+                line(119_500, ""),
+            ]
+        );
+        assert!(lyrics.is_synced());
+
+        assert_eq!(lyrics.current_line(Duration::from_secs(5)), None);
+        assert_eq!(lyrics.current_line(Duration::from_millis(11_500)), Some(0));
+        assert_eq!(lyrics.current_line(Duration::from_secs(70)), Some(2));
+        assert_eq!(lyrics.current_line(Duration::from_secs(500)), Some(4));
+    }
+
+    #[test]
+    fn test_parse_broken_lrc() {
+        // Fractions with characters of more than one byte.
+        assert_eq!(parse_timestamp("00:01.1€"), None);
+        assert_eq!(parse_timestamp("00:01.1234€"), None);
+        assert_eq!(
+            Lyrics::parse_lrc("[00:01.00]Price <00:01.1€>10").lines,
+            vec![line(1000, "Price <00:01.1€>10")]
+        );
+        // Timestamps too large to count in milliseconds.
+        assert_eq!(parse_timestamp("99999999999999999:00"), None);
+        assert_eq!(parse_timestamp(&format!("00:{}", u64::MAX)), None);
+        // Offsets as far as they go, both ways.
+        assert_eq!(
+            Lyrics::parse_lrc("[offset:-9223372036854775808]\n[00:01.00]Late").lines,
+            vec![line(i64::MAX as u64, "Late")]
+        );
+        assert_eq!(
+            Lyrics::parse_lrc("[offset:9223372036854775807]\n[00:01.00]Early").lines,
+            vec![line(0, "Early")]
+        );
+    }
+
+    #[test]
+    fn test_plain_lyrics() {
+        let lyrics = Lyrics::parse_lrc("First line\n\n[Chorus]\nSecond line");
+        assert!(!lyrics.is_synced());
+        assert_eq!(lyrics.lines.len(), 4);
+        assert_eq!(lyrics.lines[2].text, "[Chorus]");
+        assert_eq!(lyrics.current_line(Duration::from_secs(10)), None);
+    }
+
+    #[test]
+    fn test_load_lrc_file() {
+        let directory = TempDir::new("lyrics");
+        let song = directory.path().join("song.ogg");
+        std::fs::copy("../example_audio/blank_holes_snippet.ogg", &song).unwrap();
+
+        assert_eq!(load(&song), None);
+        std::fs::write(directory.path().join("song.lrc"), "[00:01.00]Hello").unwrap();
+        assert_eq!(load(&song).unwrap().lines, vec![line(1000, "Hello")]);
+    }
+}
@@ -0,0 +1,81 @@
+use crate::lyrics::{self, Lyrics};
+use camino::{Utf8Path, Utf8PathBuf};
+use eframe::egui;
+use eframe::egui::{Align, Color32, RichText, Sense, Ui};
+use std::time::Duration;
+
+pub struct LyricsView {
+    /// The lyrics of the song at this path. Loaded again when another song plays.
+    lyrics: Option<(Utf8PathBuf, Option<Lyrics>)>,
+    /// Only scrolls to the current line when it changes, so the lyrics can be scrolled by hand.
+    scrolled_to: Option<usize>,
+}
+
+impl LyricsView {
+    pub fn new() -> Self {
+        LyricsView {
+            lyrics: None,
+            scrolled_to: None,
+        }
+    }
+
+    /// Returns where to seek to, when a line is clicked.
+    #[must_use]
+    pub fn show(
+        &mut self,
+        ui: &mut Ui,
+        song_path: Option<&Utf8Path>,
+        elapsed: Duration,
+        playing: bool,
+    ) -> Option<Duration> {
+        let Some(song_path) = song_path else {
+            ui.weak("Nothing is playing.");
+            return None;
+        };
+        if self.lyrics.as_ref().map(|(path, _)| path.as_path()) != Some(song_path) {
+            self.lyrics = Some((song_path.to_path_buf(), lyrics::load(song_path)));
+            self.scrolled_to = None;
+        }
+        let Some((_, Some(lyrics))) = &self.lyrics else {
+            ui.weak("No lyrics found.");
+            return None;
+        };
+
+        let current_line = lyrics.current_line(elapsed);
+        if playing && lyrics.is_synced() {
+            // The rest of the app only repaints every second while playing.
+            ui.ctx().request_repaint_after(Duration::from_millis(200));
+        }
+
+        let mut seek = None;
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                for (index, line) in lyrics.lines.iter().enumerate() {
+                    let is_current = current_line == Some(index);
+                    let mut text = RichText::new(&line.text);
+                    if is_current {
+                        text = text.strong().color(Color32::LIGHT_GREEN);
+                    }
+
+                    let sense = match line.time {
+                        Some(_) => Sense::click(),
+                        None => Sense::hover(),
+                    };
+                    let response = ui.add(egui::Label::new(text).sense(sense));
+                    if let (Some(time), true) = (line.time, response.clicked()) {
+                        seek = Some(time);
+                    }
+                    if let Some(time) = line.time {
+                        response.on_hover_text(crate::duration_to_time_display(time));
+                    }
+
+                    if is_current && self.scrolled_to != current_line {
+                        ui.scroll_to_cursor(Some(Align::Center));
+                        self.scrolled_to = current_line;
+                    }
+                }
+            });
+        seek
+    }
+}
@@ -1,19 +1,74 @@
 mod config;
+mod filename_pattern;
+mod fuzzy;
+mod history;
+mod history_view;
+mod labels;
 mod library;
+mod library_browser_view;
+mod library_folder_view;
 mod library_search_view;
+mod library_table_view;
+mod lyrics;
+mod lyrics_view;
+mod missing_files;
+mod missing_files_view;
+mod natural_sort;
+mod normalize;
+mod organizer;
+mod organizer_view;
 mod playlist;
+mod playlist_view;
+mod query;
+mod radio;
+mod rating;
+mod search;
+mod search_index;
+mod selection;
+mod shuffle;
+mod smart_playlist;
+mod smart_playlist_view;
+mod song_data;
+mod tag_editor;
+mod tag_editor_view;
+#[cfg(test)]
+mod test_util;
+mod verify;
+mod verify_view;
 
 use crate::config::{Config, ConfigView};
+use crate::filename_pattern::FilenamePattern;
+use crate::history::{History, PlaybackEvent};
+use crate::history_view::HistoryView;
+use crate::labels::{change_labels, show_label_chip};
 use crate::library::{Library, SongId};
+use crate::library_browser_view::LibraryBrowserView;
+use crate::library_folder_view::LibraryFolderView;
 use crate::library_search_view::{LibrarySearchView, LibraryViewCommand};
+use crate::library_table_view::LibraryTableView;
+use crate::lyrics_view::LyricsView;
+use crate::missing_files_view::MissingFilesView;
+use crate::organizer_view::OrganizerView;
 use crate::playlist::Playlist;
+use crate::playlist_view::{PlaylistView, PlaylistViewCommand};
+use crate::rating::{show_rating, write_rating_to_file, RatingChange, MAX_RATING};
+use crate::shuffle::ShuffleMode;
+use crate::smart_playlist_view::{SmartPlaylistView, SmartPlaylistViewCommand};
+use crate::song_data::{Listening, SongData};
+use crate::tag_editor_view::TagEditorView;
+use crate::verify_view::VerifyView;
+use camino::Utf8PathBuf;
 use eframe::egui::{
-    Color32, Context, CursorIcon, Id, ProgressBar, RichText, Sense, Ui, Visuals, Widget,
+    Color32, Context, CursorIcon, Key, Modifiers, ProgressBar, Sense, Ui, Visuals, Widget,
 };
 use eframe::{egui, App, Frame, IconData, Storage};
 use sound::Player;
+use std::collections::HashMap;
 use std::time::Duration;
 
+/// Song data is saved apart from the config, because it can get large.
+const SONG_DATA_KEY: &str = "song_data";
+
 fn main() {
     // TODO (2023-02-06): Package the icon with the executable?
     let icon = image::open("icon.png")
@@ -36,15 +91,45 @@ fn main() {
     );
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CentralView {
+    Playlist,
+    Library,
+    Browser,
+    Folders,
+    /// The smart playlist at this index in the config.
+    SmartPlaylist(usize),
+}
+
 struct MusicsApp {
     config: Config,
     config_view: ConfigView,
     player: Player,
+    /// Tracks the play stats of the song that is playing.
+    listening: Option<Listening>,
+    /// Why the last song couldn't be played.
+    play_error: Option<String>,
+    /// Why the last rating couldn't be written to the file of the song.
+    rating_error: Option<String>,
+    history: History,
+    history_view: HistoryView,
     library: Library,
     library_search_view: LibrarySearchView,
+    library_browser_view: LibraryBrowserView,
+    library_folder_view: LibraryFolderView,
+    library_table_view: LibraryTableView,
+    /// What is shown in the middle of the window.
+    central_view: CentralView,
     playlist: Playlist,
-    /// Records whether the user is currently dragging a song in the playlist.
-    dragged_playlist_index: Option<usize>,
+    playlist_view: PlaylistView,
+    smart_playlist_view: SmartPlaylistView,
+    tag_editor_view: TagEditorView,
+    organizer_view: OrganizerView,
+    missing_files_view: MissingFilesView,
+    verify_view: VerifyView,
+    lyrics_view: LyricsView,
+    /// Whether the lyrics of the current song are shown next to the central view.
+    show_lyrics: bool,
     /// In overlay mode, the program only shows the playlist controls, and becomes very small.
     overlay_mode: bool,
     /// When in overlay mode, this remembers how large the ui was when it _wasn't_ in overlay mode.
@@ -59,6 +144,10 @@ impl MusicsApp {
         } else {
             Default::default()
         };
+        let song_data: HashMap<Utf8PathBuf, SongData> = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, SONG_DATA_KEY))
+            .unwrap_or_default();
 
         let visuals = Visuals::dark();
         cc.egui_ctx.set_visuals(visuals);
@@ -67,7 +156,14 @@ impl MusicsApp {
         //    set to 1.0 (the default), the ui is instead shown at 2x the scale it should be.
         cc.egui_ctx.set_pixels_per_point(0.9999);
 
+        let history = match History::default_log_path() {
+            Some(log_path) => History::open(log_path),
+            None => History::new(),
+        };
+
         let mut library = Library::new();
+        library.set_filename_pattern(FilenamePattern::parse(&config.filename_pattern).ok());
+        library.load_song_data(song_data);
         if config.library_directory != "" {
             library.insert_from_directory(&config.library_directory);
         }
@@ -76,43 +172,91 @@ impl MusicsApp {
             config,
             config_view: ConfigView::new(),
             player: Player::new(),
+            listening: None,
+            play_error: None,
+            rating_error: None,
+            history,
+            history_view: HistoryView::new(),
             library,
             library_search_view: LibrarySearchView::new(),
+            library_browser_view: LibraryBrowserView::new(),
+            library_folder_view: LibraryFolderView::new(),
+            library_table_view: LibraryTableView::new(),
+            central_view: CentralView::Playlist,
             playlist: Playlist::new(),
-            dragged_playlist_index: None,
+            playlist_view: PlaylistView::new(),
+            smart_playlist_view: SmartPlaylistView::new(),
+            tag_editor_view: TagEditorView::new(),
+            organizer_view: OrganizerView::new(),
+            missing_files_view: MissingFilesView::new(),
+            verify_view: VerifyView::new(),
+            lyrics_view: LyricsView::new(),
+            show_lyrics: false,
             overlay_mode: false,
             ui_size: egui::Vec2::new(0., 0.),
         }
     }
 
     fn play_next_song(&mut self) {
-        if let Some(song) = self
-            .playlist
-            .select_next_song(true)
-            .and_then(|id| self.library.get_song(id))
-        {
-            self.player.play_file(&song.path)
-        }
+        let next_song = self.playlist.select_next_song(true);
+        self.start_playing(next_song, true);
     }
 
     fn play_previous_song(&mut self) {
-        if let Some(song) = self
-            .playlist
-            .select_previous_song(true)
-            .and_then(|id| self.library.get_song(id))
-        {
-            self.player.play_file(&song.path)
-        }
+        let previous_song = self.playlist.select_previous_song(true);
+        self.start_playing(previous_song, false);
     }
 
     fn play_song_by_playlists_index(&mut self, index: usize) {
-        if let Some(song) = self
-            .playlist
-            .select_song(index)
-            .and_then(|id| self.library.get_song(id))
-        {
-            self.player.play_file(&song.path)
+        let song = self.playlist.select_song(index);
+        self.start_playing(song, false);
+    }
+
+    /// Plays the song, if there is one. `skipped` when the song that is playing now
+    /// is skipped for the next one, which counts towards its skip count.
+    fn start_playing(&mut self, id: Option<SongId>, skipped: bool) {
+        let Some((id, song)) = id.and_then(|id| self.library.get_song(id).map(|song| (id, song)))
+        else {
+            return;
+        };
+        let path = song.path.clone();
+        let title = song.title.clone();
+
+        // The song that was playing didn't get to finish.
+        if self.listening.is_some() {
+            self.record(PlaybackEvent::Skip, self.player.time_elapsed());
         }
+        if let Err(error) = self.player.play_file(&path) {
+            // Stops instead of trying the next song, so it doesn't skip through a whole
+            // playlist of missing files.
+            self.player.stop();
+            if let Some(listening) = self.listening.take() {
+                listening.finish(skipped, &mut self.library);
+            }
+            if !path.is_file() {
+                self.library.set_missing(id, true);
+            }
+            self.play_error = Some(format!("Could not play {}. {}", title, error));
+            return;
+        }
+        self.play_error = None;
+
+        if let Some(listening) = self.listening.replace(Listening::start(id)) {
+            listening.finish(skipped, &mut self.library);
+        }
+        self.record(PlaybackEvent::Start, Duration::ZERO);
+    }
+
+    /// Adds the event to the history, for the song that is playing.
+    fn record(&mut self, event: PlaybackEvent, position: Duration) {
+        let Some(song) = self
+            .listening
+            .as_ref()
+            .and_then(|listening| self.library.get_song(listening.song()))
+        else {
+            return;
+        };
+        self.history.record(event, position, song);
     }
 
     fn show_play_controls(&mut self, ui: &mut Ui, frame: &mut Frame) {
@@ -135,7 +279,9 @@ impl MusicsApp {
                         let fraction = x_on_bar / bar_width;
 
                         let seek_duration = (duration.as_secs_f32() * fraction).max(0.);
-                        self.player.seek(Duration::from_secs_f32(seek_duration));
+                        let seek_duration = Duration::from_secs_f32(seek_duration);
+                        self.record(PlaybackEvent::Seek { from: elapsed }, seek_duration);
+                        self.player.seek(seek_duration);
                     }
                 }
             });
@@ -167,12 +313,14 @@ impl MusicsApp {
             if self.player.is_playing() {
                 if ui.button("||").clicked() {
                     self.player.pause();
+                    self.record(PlaybackEvent::Pause, self.player.time_elapsed());
                 }
             } else if ui.button(">").clicked() {
                 if self.player.empty() {
                     self.play_next_song();
                 } else {
                     self.player.resume();
+                    self.record(PlaybackEvent::Resume, self.player.time_elapsed());
                 }
             }
 
@@ -190,153 +338,236 @@ impl MusicsApp {
             }
 
             ui.toggle_value(&mut self.overlay_mode, "Overlay");
+            ui.toggle_value(&mut self.show_lyrics, "Lyrics");
 
-            if let Some(current_song) = self
+            if let Some((id, current_song)) = self
                 .playlist
                 .current_song_id()
-                .and_then(|id| self.library.get_song(id))
+                .and_then(|id| self.library.get_song(id).map(|song| (id, song)))
             {
                 ui.label(&current_song.title);
+                let data = self.library.get_song_data(id);
+                let rating_response = ui.horizontal(|ui| show_rating(ui, data));
+                for label in &data.labels {
+                    show_label_chip(ui, label, false);
+                }
+                if let Some(change) = rating_response.inner {
+                    self.change_rating(id, change);
+                }
+            }
+
+            for error in [&self.play_error, &self.rating_error].into_iter().flatten() {
+                ui.colored_label(Color32::LIGHT_RED, error);
             }
         });
     }
 
-    fn show_playlist(&mut self, ui: &mut Ui) {
-        let current_song = self.playlist.current_song_index();
-
-        if !ui.memory().is_anything_being_dragged() {
-            self.dragged_playlist_index = None
-        }
-
-        if self.dragged_playlist_index.is_some() {
-            ui.output().cursor_icon = CursorIcon::Grabbing;
+    fn handle_playlist_view_command(&mut self, command: PlaylistViewCommand) {
+        match command {
+            PlaylistViewCommand::None => {}
+            PlaylistViewCommand::PlaySong(index) => {
+                self.play_song_by_playlists_index(index);
+            }
+            PlaylistViewCommand::RemoveSongs(indices) => {
+                self.playlist.remove_songs_by_index(&indices);
+            }
+            PlaylistViewCommand::MoveSongs { indices, target } => {
+                self.playlist.move_songs(&indices, target);
+            }
+            PlaylistViewCommand::Crop(indices) => {
+                self.playlist.crop(&indices);
+            }
+            PlaylistViewCommand::RemoveSongsBeforeCurrent => {
+                self.playlist.remove_songs_before_current();
+            }
+            PlaylistViewCommand::RemoveSongsAfterCurrent => {
+                self.playlist.remove_songs_after_current();
+            }
+            PlaylistViewCommand::Sort(key) => {
+                self.playlist.sort(&self.library, key);
+            }
+            PlaylistViewCommand::Reverse => {
+                self.playlist.reverse();
+            }
+            PlaylistViewCommand::RemoveDuplicates => {
+                self.playlist.remove_duplicates();
+            }
+            PlaylistViewCommand::RemoveMissingSongs => {
+                self.playlist.remove_missing_songs(&self.library);
+            }
+            PlaylistViewCommand::RemoveQueuedSong(index) => {
+                self.playlist.remove_queued_song_by_index(index);
+            }
+            PlaylistViewCommand::ClearQueue => {
+                self.playlist.clear_queue();
+            }
+            PlaylistViewCommand::ChangeRating(id, change) => {
+                self.change_rating(id, change);
+            }
+            PlaylistViewCommand::ChangeLabels(songs, change) => {
+                change_labels(&mut self.library, &songs, &change);
+            }
+            PlaylistViewCommand::EditTags(songs) => {
+                self.tag_editor_view.open(&self.library, songs);
+            }
         }
+    }
 
-        let mut maybe_song_index_to_play = None;
-        let mut move_dragged_song_to_target_index = None;
-        let mut remove_song = None;
-
-        let button_height = ui.spacing().interact_size.y;
-
-        egui::ScrollArea::both()
-            .auto_shrink([false, false])
-            .show_rows(
-                ui,
-                button_height,
-                self.playlist.song_count(),
-                |ui, row_range| {
-                    for (index, id) in self
-                        .playlist
-                        .songs()
-                        .enumerate()
-                        .skip(row_range.start)
-                        .take(row_range.len())
-                    {
-                        if let Some(song) = self.library.get_song(*id) {
-                            ui.horizontal(|ui| {
-                                let id_source = "playlist_drag";
-                                let drag_id = Id::new(id_source).with(index);
-
-                                let drag_rect = ui.label("::").rect;
-                                let drag_response = ui.interact(drag_rect, drag_id, Sense::drag());
-
-                                if drag_response.drag_started() {
-                                    self.dragged_playlist_index = Some(index);
-                                } else if drag_response.hovered()
-                                    && !ui.memory().is_anything_being_dragged()
-                                {
-                                    ui.output().cursor_icon = CursorIcon::Grab;
-                                }
-
-                                if let Some(dragged_index) = self.dragged_playlist_index {
-                                    if dragged_index != index {
-                                        if let Some(last_pos) = ui.input().pointer.hover_pos() {
-                                            if last_pos.y >= drag_rect.top()
-                                                && last_pos.y <= drag_rect.bottom()
-                                            {
-                                                move_dragged_song_to_target_index = Some(index);
-                                            }
-                                        }
-                                    }
-                                }
-
-                                if ui.button("X").clicked() {
-                                    remove_song = Some(index);
-                                }
-
-                                let mut title_text = RichText::new(&song.title);
-
-                                if self.dragged_playlist_index == Some(index) {
-                                    title_text = title_text.color(Color32::LIGHT_BLUE);
-                                } else if current_song == Some(index) {
-                                    title_text = title_text.color(Color32::LIGHT_GREEN);
-                                }
-
-                                if egui::Label::new(title_text)
-                                    .wrap(false)
-                                    .sense(Sense::click())
-                                    .ui(ui)
-                                    .clicked()
-                                {
-                                    maybe_song_index_to_play = Some(index);
-                                }
+    fn change_rating(&mut self, id: SongId, change: RatingChange) {
+        match change {
+            RatingChange::Rating(rating) => {
+                self.library
+                    .update_song_data(id, |data| data.rating = rating);
+                if self.config.write_ratings_to_files {
+                    if let Some(song) = self.library.get_song(id) {
+                        self.rating_error =
+                            write_rating_to_file(&song.path, rating).err().map(|error| {
+                                format!("Could not save the rating to {}: {}", song.path, error)
                             });
-                        }
                     }
-                },
-            );
-
-        if let Some(index) = maybe_song_index_to_play {
-            self.play_song_by_playlists_index(index);
-        }
-
-        if let (Some(source_index), Some(target_index)) = (
-            self.dragged_playlist_index,
-            move_dragged_song_to_target_index,
-        ) {
-            self.playlist
-                .switch_songs_by_index(source_index, target_index);
-            self.dragged_playlist_index = Some(target_index);
-        }
-
-        if let Some(remove_index) = remove_song {
-            let removed_current_song = self.playlist.remove_song_by_index(remove_index);
-
-            if removed_current_song {
-                if let Some(index) = self.playlist.current_song_index() {
-                    let was_playing = self.player.is_playing();
-
-                    self.play_song_by_playlists_index(index);
-
-                    if !was_playing {
-                        self.player.pause();
-                    }
-                } else {
-                    self.player.stop();
                 }
             }
+            RatingChange::Favourite(favourite) => {
+                self.library
+                    .update_song_data(id, |data| data.favourite = favourite);
+            }
         }
     }
 
     fn handle_library_view_command(&mut self, command: LibraryViewCommand) {
         match command {
             LibraryViewCommand::None => {}
-            LibraryViewCommand::AddSongToPlaylist(id) => {
-                self.playlist.append_song(id);
+            LibraryViewCommand::AddSongsToPlaylist(songs) => {
+                self.playlist.append_songs(&songs);
+            }
+            LibraryViewCommand::PlaySongsNext(songs) => {
+                self.playlist.queue_songs(&songs);
+            }
+            LibraryViewCommand::PlaySongsNow(songs) => {
+                self.playlist.queue_songs_first(&songs);
+                self.play_next_song();
+            }
+            LibraryViewCommand::ReplacePlaylist(songs) => {
+                self.playlist.replace_songs(&songs);
+                self.playlist_view.clear_selection();
+            }
+            LibraryViewCommand::ChangeLabels(songs, change) => {
+                change_labels(&mut self.library, &songs, &change);
+            }
+            LibraryViewCommand::EditTags(songs) => {
+                self.tag_editor_view.open(&self.library, songs);
             }
         }
     }
+
+    /// Ctrl + 0 to 5 rates the song that is playing, ctrl + L makes it a favourite.
+    fn handle_rating_shortcuts(&mut self, ctx: &Context) {
+        let Some(id) = self
+            .playlist
+            .current_song_id()
+            .filter(|id| self.library.get_song(*id).is_some())
+        else {
+            return;
+        };
+        let favourite = self.library.get_song_data(id).favourite;
+
+        const NUMBER_KEYS: [Key; MAX_RATING as usize + 1] = [
+            Key::Num0,
+            Key::Num1,
+            Key::Num2,
+            Key::Num3,
+            Key::Num4,
+            Key::Num5,
+        ];
+        let mut change = None;
+        {
+            let input = ctx.input();
+            if input.modifiers.matches(Modifiers::COMMAND) {
+                for (rating, key) in NUMBER_KEYS.into_iter().enumerate() {
+                    if input.key_pressed(key) {
+                        change = Some(RatingChange::Rating(rating as u8));
+                    }
+                }
+                if input.key_pressed(Key::L) {
+                    change = Some(RatingChange::Favourite(!favourite));
+                }
+            }
+        }
+
+        if let Some(change) = change {
+            self.change_rating(id, change);
+        }
+    }
 }
 
 impl App for MusicsApp {
     fn update(&mut self, ctx: &Context, frame: &mut Frame) {
         let previous_overlay_value = self.overlay_mode.clone();
 
+        if self
+            .playlist
+            .top_up_radio(&self.library, &self.config.radio)
+            > 0
+        {
+            self.playlist_view.clear_selection();
+        }
+
+        if let Some(listening) = &mut self.listening {
+            listening.update(self.player.is_playing(), &mut self.library);
+        }
+
         if self.player.song_finished_playing() {
+            // Listened to the end, so not skipped.
+            self.record(PlaybackEvent::Finish, self.player.song_duration());
+            if let Some(listening) = self.listening.take() {
+                listening.finish(false, &mut self.library);
+            }
             self.play_next_song();
         }
 
-        self.config_view.show(ctx, &mut self.config);
+        let previous_library_directory = self.config.library_directory.clone();
+        let previous_filename_pattern = self.config.filename_pattern.clone();
+        self.config_view.show(ctx, &mut self.config, &self.library);
+        if self.config.filename_pattern != previous_filename_pattern {
+            self.library
+                .set_filename_pattern(FilenamePattern::parse(&self.config.filename_pattern).ok());
+        }
+        self.history_view.show(ctx, &mut self.history);
+        self.tag_editor_view.show(ctx, &mut self.library);
+        self.organizer_view.show(
+            ctx,
+            &mut self.library,
+            &self.config.library_directory,
+            &mut self.config.organizer_template,
+        );
+        self.missing_files_view
+            .show(ctx, &mut self.library, &self.config.library_directory);
+        self.verify_view.show(ctx, &self.library);
+        self.smart_playlist_view
+            .show_editor(ctx, &self.library, &mut self.config.smart_playlists);
+        if self.config.library_directory != previous_library_directory {
+            self.library
+                .rescan_directory(&self.config.library_directory);
+        }
+
+        // Text fields have their own undo, so only undo playlist edits when no text field
+        // is being edited.
+        if ctx.memory().focus().is_none() {
+            self.handle_rating_shortcuts(ctx);
+
+            let input = ctx.input();
+            if input.key_pressed(Key::Z) && input.modifiers.matches(Modifiers::COMMAND) {
+                self.playlist.undo();
+                self.playlist_view.clear_selection();
+            } else if input.key_pressed(Key::Z)
+                && input
+                    .modifiers
+                    .matches(Modifiers::COMMAND | Modifiers::SHIFT)
+            {
+                self.playlist.redo();
+                self.playlist_view.clear_selection();
+            }
+        }
 
         if self.overlay_mode {
             egui::CentralPanel::default().show(ctx, |ui| {
@@ -348,17 +579,84 @@ impl App for MusicsApp {
                     if ui.button("Config").clicked() {
                         self.config_view.open_window();
                     }
+                    if ui.button("History").clicked() {
+                        self.history_view.open_window();
+                    }
+                    if ui.button("Organize").clicked() {
+                        self.organizer_view.open_window();
+                    }
+                    let missing = self.library.missing_count();
+                    let text = match missing {
+                        0 => "Missing files".to_string(),
+                        count => format!("Missing files ({})", count),
+                    };
+                    if ui.button(text).clicked() {
+                        self.missing_files_view.open_window();
+                    }
+                    if ui.button("Verify").clicked() {
+                        self.verify_view.open_window();
+                    }
+
+                    ui.selectable_value(&mut self.central_view, CentralView::Playlist, "Playlist");
+                    ui.selectable_value(&mut self.central_view, CentralView::Library, "Library");
+                    ui.selectable_value(&mut self.central_view, CentralView::Browser, "Browse")
+                        .on_hover_text("Browse the library by genre, artist and album.");
+                    ui.selectable_value(&mut self.central_view, CentralView::Folders, "Folders")
+                        .on_hover_text("Browse the library by the folders it is in.");
+
+                    ui.separator();
+
+                    let mut radio_enabled = self.playlist.is_radio_enabled();
+                    ui.toggle_value(&mut radio_enabled, "Radio").on_hover_text(
+                        "Keeps the playlist filled with random songs from the library.",
+                    );
+                    self.playlist.set_radio_enabled(radio_enabled);
+
+                    ui.menu_button("Shuffle", |ui| {
+                        for mode in ShuffleMode::ALL {
+                            if ui.button(mode.name()).clicked() {
+                                self.playlist.shuffle(&self.library, mode);
+                                self.playlist_view.clear_selection();
+                                ui.close_menu();
+                            }
+                        }
+
+                        ui.separator();
+
+                        if ui
+                            .add_enabled(
+                                self.playlist.is_shuffled(),
+                                egui::Button::new("Un-shuffle"),
+                            )
+                            .clicked()
+                        {
+                            self.playlist.unshuffle();
+                            self.playlist_view.clear_selection();
+                            ui.close_menu();
+                        }
+                    });
+
+                    if ui.button("Clear").clicked() {
+                        self.playlist.clear();
+                        self.playlist_view.clear_selection();
+                    }
 
-                    let add_full_library_response = ui.button("+ Full library");
-                    if add_full_library_response.clicked() {
-                        // TODO (2023-02-06): Create an infinite, self-filling random playlist instead.
-                        let mut songs: Vec<SongId> =
-                            self.library.songs().map(|(id, _)| id).collect();
-                        fastrand::shuffle(&mut songs);
-                        self.playlist.append_songs(&songs);
+                    if ui
+                        .add_enabled(self.playlist.can_undo(), egui::Button::new("Undo"))
+                        .on_hover_text("Ctrl + Z")
+                        .clicked()
+                    {
+                        self.playlist.undo();
+                        self.playlist_view.clear_selection();
+                    }
+                    if ui
+                        .add_enabled(self.playlist.can_redo(), egui::Button::new("Redo"))
+                        .on_hover_text("Ctrl + Shift + Z")
+                        .clicked()
+                    {
+                        self.playlist.redo();
+                        self.playlist_view.clear_selection();
                     }
-                    add_full_library_response
-                        .on_hover_text("Adds the full library randomized to the playlist.");
 
                     ui.separator();
 
@@ -380,8 +678,85 @@ impl App for MusicsApp {
                 });
             }
 
-            egui::CentralPanel::default().show(ctx, |ui| {
-                self.show_playlist(ui);
+            if self.show_lyrics {
+                egui::SidePanel::right("lyrics").show(ctx, |ui| {
+                    let song_path = self
+                        .playlist
+                        .current_song_id()
+                        .and_then(|id| self.library.get_song(id))
+                        .map(|song| song.path.as_path());
+                    let elapsed = self.player.time_elapsed();
+                    let playing = self.player.is_playing();
+                    if let Some(time) = self.lyrics_view.show(ui, song_path, elapsed, playing) {
+                        self.record(PlaybackEvent::Seek { from: elapsed }, time);
+                        self.player.seek(time);
+                    }
+                });
+            }
+
+            egui::SidePanel::left("smart_playlists").show(ctx, |ui| {
+                let open_index = match self.central_view {
+                    CentralView::SmartPlaylist(index) => Some(index),
+                    _ => None,
+                };
+                let command = self.smart_playlist_view.show_list(
+                    ui,
+                    &self.library,
+                    &mut self.config.smart_playlists,
+                    open_index,
+                );
+                match command {
+                    SmartPlaylistViewCommand::None => {}
+                    SmartPlaylistViewCommand::Open(index) => {
+                        self.central_view = CentralView::SmartPlaylist(index);
+                    }
+                    SmartPlaylistViewCommand::Library(command) => {
+                        self.handle_library_view_command(command);
+                    }
+                }
+            });
+
+            // The open smart playlist could have just been deleted.
+            if let CentralView::SmartPlaylist(index) = self.central_view {
+                if index >= self.config.smart_playlists.len() {
+                    self.central_view = CentralView::Playlist;
+                }
+            }
+
+            egui::CentralPanel::default().show(ctx, |ui| match self.central_view {
+                CentralView::Playlist => {
+                    let command = self.playlist_view.show(ui, &self.playlist, &self.library);
+                    self.handle_playlist_view_command(command);
+                }
+                CentralView::Library => {
+                    let command = self.library_table_view.show(
+                        ui,
+                        &self.library,
+                        &mut self.config.library_table,
+                    );
+                    self.handle_library_view_command(command);
+                }
+                CentralView::Browser => {
+                    let command = self.library_browser_view.show(ui, &self.library);
+                    self.handle_library_view_command(command);
+                }
+                CentralView::SmartPlaylist(index) => {
+                    let command = self.smart_playlist_view.show_songs(
+                        ui,
+                        &self.library,
+                        index,
+                        &mut self.config.smart_playlists[index],
+                    );
+                    self.handle_library_view_command(command);
+                }
+                CentralView::Folders => {
+                    let command = self.library_folder_view.show(
+                        ui,
+                        &self.library,
+                        &self.config.library_directory,
+                    );
+                    self.handle_library_view_command(command);
+                }
             });
         }
 
@@ -415,6 +790,11 @@ impl App for MusicsApp {
 
     fn save(&mut self, storage: &mut dyn Storage) {
         eframe::set_value(storage, eframe::APP_KEY, &self.config);
+
+        if let Some(listening) = &mut self.listening {
+            listening.save(&mut self.library);
+        }
+        eframe::set_value(storage, SONG_DATA_KEY, &self.library.song_data());
     }
 }
 
@@ -0,0 +1,212 @@
+//! Finds songs of which the file is gone, and looks for the file in the library folder again.
+//! A file counts as the same when at least two of its name, size and tags match the song's,
+//! and no other file matches as well. Relocated songs keep their id, so they stay in playlists
+//! and keep their data.
+
+use crate::library::{find_songs, Library, Song, SongId};
+use camino::{Utf8Path, Utf8PathBuf};
+use std::cell::OnceCell;
+use std::collections::HashSet;
+use std::time::Duration;
+
+/// How much the durations of the song and a file can differ, and still count as the same.
+const DURATION_TOLERANCE: Duration = Duration::from_secs(1);
+
+pub struct Report {
+    /// The songs that were found somewhere else, with where they were.
+    pub relocated: Vec<(SongId, Utf8PathBuf)>,
+}
+
+/// What [`check_files`] found. It only looks at the files, so it can run on another thread,
+/// and the library is changed afterwards with [`Check::apply`].
+pub struct Check {
+    /// Every song, the path that was checked, and whether its file is gone.
+    missing: Vec<(SongId, Utf8PathBuf, bool)>,
+    /// The missing songs that were found somewhere else, with the path that was checked and
+    /// where they were found.
+    found: Vec<(SongId, Utf8PathBuf, Utf8PathBuf)>,
+}
+
+impl Check {
+    /// Marks the songs of which the file is gone as missing, and relocates the ones that were
+    /// found. Songs of which the file is back are no longer missing.
+    ///
+    /// Songs that got another path since the check started, like by organizing or locating
+    /// them, are left alone. What was found out about their old path doesn't count anymore.
+    pub fn apply(self, library: &mut Library) -> Report {
+        let has_path = |library: &Library, id: SongId, path: &Utf8Path| {
+            library.get_song(id).is_some_and(|song| song.path == path)
+        };
+
+        for (id, path, missing) in self.missing {
+            if has_path(library, id, &path) {
+                library.set_missing(id, missing);
+            }
+        }
+
+        let mut relocated = Vec::new();
+        for (id, old_path, path) in self.found {
+            if has_path(library, id, &old_path) {
+                relocate(library, id, path);
+                relocated.push((id, old_path));
+            }
+        }
+        Report { relocated }
+    }
+}
+
+/// A file in the library folder that isn't a song in the library.
+struct Candidate {
+    path: Utf8PathBuf,
+    size: Option<u64>,
+    /// Read when first needed, and only once, as many missing songs can have the same size.
+    tags: OnceCell<Song>,
+}
+
+/// Looks for the songs of which the file is gone, and for where they went in `root`.
+pub fn check_files(library: &Library, root: &Utf8Path) -> Check {
+    let missing: Vec<(SongId, Utf8PathBuf, bool)> = library
+        .songs()
+        .map(|(id, song)| (id, song.path.clone(), !song.path.is_file()))
+        .collect();
+
+    let mut found = Vec::new();
+    if missing.iter().any(|(_, _, missing)| *missing) && root.is_dir() {
+        let mut paths = Vec::new();
+        find_songs(root, &mut paths);
+        let known: HashSet<&Utf8PathBuf> = library.songs().map(|(_, song)| &song.path).collect();
+        let mut candidates: Vec<Candidate> = paths
+            .into_iter()
+            .filter(|path| !known.contains(path))
+            .map(|path| Candidate {
+                size: path.metadata().ok().map(|metadata| metadata.len()),
+                path,
+                tags: OnceCell::new(),
+            })
+            .collect();
+
+        for (id, path, _) in missing.iter().filter(|(_, _, missing)| *missing) {
+            let Some(song) = library.get_song(*id) else {
+                continue;
+            };
+            let Some(index) = find_moved_file(song, &candidates) else {
+                continue;
+            };
+            // One file can't be two songs.
+            found.push((*id, path.clone(), candidates.swap_remove(index).path));
+        }
+    }
+
+    Check { missing, found }
+}
+
+/// Points the song to the file, and reads it again in case its tags changed.
+pub fn relocate(library: &mut Library, id: SongId, path: Utf8PathBuf) {
+    library.move_song(id, path);
+    library.reload_song(id);
+}
+
+/// The index of the file that matches the song best, if there is only one.
+fn find_moved_file(song: &Song, candidates: &[Candidate]) -> Option<usize> {
+    let name = song.path.file_name()?.to_lowercase();
+
+    let mut best_score = 2;
+    let mut best = Vec::new();
+    for (index, candidate) in candidates.iter().enumerate() {
+        let same_name = candidate.path.file_name().map(str::to_lowercase).as_ref() == Some(&name);
+        let same_size = song.file_size.is_some() && candidate.size == song.file_size;
+        // Tags alone aren't enough, and reading them for every file would take long.
+        if !same_name && !same_size {
+            continue;
+        }
+        let tags = candidate
+            .tags
+            .get_or_init(|| Song::from_file(candidate.path.clone(), None));
+        let same_tags = tags_match(song, tags);
+
+        let score = u8::from(same_name) + u8::from(same_size) + u8::from(same_tags);
+        if score > best_score {
+            best_score = score;
+            best = vec![index];
+        } else if score == best_score {
+            best.push(index);
+        }
+    }
+
+    match best.as_slice() {
+        [index] => Some(*index),
+        _ => None,
+    }
+}
+
+fn tags_match(a: &Song, b: &Song) -> bool {
+    let durations_match = match (a.duration, b.duration) {
+        (Some(a), Some(b)) => a.abs_diff(b) <= DURATION_TOLERANCE,
+        (None, None) => true,
+        _ => false,
+    };
+    a.title == b.title && a.artist == b.artist && a.album == b.album && durations_match
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn test_check_files() {
+        let directory = TempDir::new("missing");
+        let root = directory.path();
+        std::fs::create_dir_all(root.join("old")).unwrap();
+        for name in [
+            "blank_holes_snippet.ogg",
+            "subfolder/dark_mystery_snippet.mp3",
+        ] {
+            let example = Utf8Path::new("../example_audio").join(name);
+            std::fs::copy(
+                &example,
+                root.join("old").join(example.file_name().unwrap()),
+            )
+            .unwrap();
+        }
+
+        let mut library = Library::new();
+        library.insert_from_directory(root);
+        let id_of = |library: &Library, title: &str| {
+            library
+                .songs()
+                .find(|(_, song)| song.title == title)
+                .map(|(id, _)| id)
+                .unwrap()
+        };
+        let blank_holes = id_of(&library, "Blank Holes");
+        let dark_mystery = id_of(&library, "Dark Mystery");
+        library.update_song_data(blank_holes, |data| data.rating = 4);
+
+        // Moved to another folder, and renamed: the size and tags still match.
+        std::fs::create_dir(root.join("new")).unwrap();
+        std::fs::rename(
+            root.join("old/blank_holes_snippet.ogg"),
+            root.join("new/Blank Holes.ogg"),
+        )
+        .unwrap();
+        // Gone.
+        std::fs::remove_file(root.join("old/dark_mystery_snippet.mp3")).unwrap();
+
+        let report = check_files(&library, root).apply(&mut library);
+        assert_eq!(report.relocated.len(), 1);
+        assert_eq!(library.missing_count(), 1);
+
+        let song = library.get_song(blank_holes).unwrap();
+        assert_eq!(song.path, root.join("new/Blank Holes.ogg"));
+        assert_eq!(library.get_song_data(blank_holes).rating, 4);
+        assert!(!library.is_missing(blank_holes));
+        assert!(library.is_missing(dark_mystery));
+
+        // Moved while checking, so what the check found out about it is outdated.
+        let check = check_files(&library, root);
+        library.move_song(dark_mystery, root.join("elsewhere.mp3"));
+        check.apply(&mut library);
+        assert!(!library.is_missing(dark_mystery));
+    }
+}
@@ -0,0 +1,176 @@
+use crate::library::{Library, SongId};
+use crate::missing_files::{check_files, relocate, Check, Report};
+use camino::{Utf8Path, Utf8PathBuf};
+use eframe::egui;
+use eframe::egui::{Context, Ui};
+use rfd::FileDialog;
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::thread;
+
+pub struct MissingFilesView {
+    show_window: bool,
+    /// Of the last check.
+    report: Option<Report>,
+    /// Looking at every file takes a while, so the check runs on another thread.
+    checking: Option<Receiver<Check>>,
+}
+
+impl MissingFilesView {
+    pub fn new() -> Self {
+        MissingFilesView {
+            show_window: false,
+            report: None,
+            checking: None,
+        }
+    }
+
+    fn start_check(&mut self, ctx: &Context, library: &Library, root: &Utf8Path) {
+        let (sender, receiver) = channel();
+        let library = library.clone();
+        let root = root.to_owned();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            if sender.send(check_files(&library, &root)).is_ok() {
+                ctx.request_repaint();
+            }
+        });
+        self.checking = Some(receiver);
+    }
+
+    /// Also when the window is closed, so the check isn't lost.
+    fn receive_check(&mut self, library: &mut Library) {
+        let Some(receiver) = &self.checking else {
+            return;
+        };
+        match receiver.try_recv() {
+            Ok(check) => {
+                self.report = Some(check.apply(library));
+                self.checking = None;
+            }
+            Err(TryRecvError::Empty) => {}
+            Err(TryRecvError::Disconnected) => self.checking = None,
+        }
+    }
+
+    pub fn open_window(&mut self) {
+        self.show_window = true;
+    }
+
+    /// `root` is the library folder, where moved files are looked for.
+    pub fn show(&mut self, ctx: &Context, library: &mut Library, root: &Utf8Path) {
+        self.receive_check(library);
+
+        let mut show_window = self.show_window;
+        egui::Window::new("Missing files")
+            .open(&mut show_window)
+            .default_width(600.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    let checking = self.checking.is_some();
+                    if ui
+                        .add_enabled(!checking, egui::Button::new("Check library"))
+                        .on_hover_text(
+                            "Looks for songs of which the file is gone, and tries to find them \
+                            in the library folder by their file name, size and tags.",
+                        )
+                        .clicked()
+                    {
+                        self.start_check(ui.ctx(), library, root);
+                    }
+                    if checking {
+                        ui.spinner();
+                    }
+                });
+
+                if let Some(report) = &self.report {
+                    if !report.relocated.is_empty() {
+                        let title = format!("Found {} moved songs", report.relocated.len());
+                        egui::CollapsingHeader::new(title).show(ui, |ui| {
+                            for (id, old_path) in &report.relocated {
+                                if let Some(song) = library.get_song(*id) {
+                                    ui.label(format!(
+                                        "{}: {} → {}",
+                                        song.title, old_path, song.path
+                                    ));
+                                }
+                            }
+                        });
+                    }
+                }
+
+                // Read from the library instead of the report, so it stays up to date.
+                let mut missing = library.missing_songs();
+                if missing.is_empty() {
+                    if self.report.is_some() {
+                        ui.label("No songs are missing.");
+                    }
+                    return;
+                }
+                missing.sort_by_key(|id| library.get_song(*id).map(|song| song.path.clone()));
+
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} songs are missing.", missing.len()));
+                    if ui
+                        .button("Remove all")
+                        .on_hover_text("Removes the missing songs from the library.")
+                        .clicked()
+                    {
+                        for id in &missing {
+                            library.remove_song(*id);
+                        }
+                    }
+                });
+
+                egui::ScrollArea::vertical()
+                    .max_height(400.0)
+                    .auto_shrink([false, true])
+                    .show(ui, |ui| {
+                        egui::Grid::new("missing_files_grid")
+                            .striped(true)
+                            .num_columns(3)
+                            .show(ui, |ui| {
+                                for id in missing {
+                                    show_missing_song(ui, library, id, root);
+                                    ui.end_row();
+                                }
+                            });
+                    });
+            });
+        self.show_window = show_window;
+    }
+}
+
+fn show_missing_song(ui: &mut Ui, library: &mut Library, id: SongId, root: &Utf8Path) {
+    let Some(song) = library.get_song(id) else {
+        return;
+    };
+
+    let text = match &song.artist {
+        Some(artist) => format!("{} - {}", song.title, artist),
+        None => song.title.clone(),
+    };
+    ui.label(text).on_hover_text(song.path.as_str());
+    ui.weak(song.path.file_name().unwrap_or_default());
+    let song_path = song.path.clone();
+
+    ui.horizontal(|ui| {
+        if ui.button("Locate…").clicked() {
+            // Looking at the folder takes long on drives that aren't there, so not every frame.
+            let directory = song_path
+                .parent()
+                .filter(|path| path.is_dir())
+                .unwrap_or(root)
+                .to_path_buf();
+            let path = FileDialog::new()
+                .set_directory(&directory)
+                .pick_file()
+                .and_then(|path| Utf8PathBuf::from_path_buf(path).ok());
+            if let Some(path) = path {
+                relocate(library, id, path);
+            }
+        }
+        if ui.button("Remove").clicked() {
+            library.remove_song(id);
+        }
+    });
+}
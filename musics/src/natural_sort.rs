@@ -0,0 +1,84 @@
+//! Sorts names the way people number them: "2 - x" comes before "10 - y",
+//! because runs of digits are compared as numbers instead of character by character.
+
+use std::cmp::Ordering;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// Compares runs of digits by their value, and everything else ignoring case.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+
+    loop {
+        let ordering = match (a_chars.peek(), b_chars.peek()) {
+            (None, None) => break,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a), Some(b)) if a.is_ascii_digit() && b.is_ascii_digit() => {
+                let a_digits = take_digits(&mut a_chars);
+                let b_digits = take_digits(&mut b_chars);
+                // Without leading zeros, the longer number is the larger one.
+                let a_number = a_digits.trim_start_matches('0');
+                let b_number = b_digits.trim_start_matches('0');
+                a_number
+                    .len()
+                    .cmp(&b_number.len())
+                    .then_with(|| a_number.cmp(b_number))
+            }
+            (Some(a), Some(b)) => {
+                let ordering = a.to_lowercase().cmp(b.to_lowercase());
+                a_chars.next();
+                b_chars.next();
+                ordering
+            }
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+
+    // Names that only differ in case or leading zeros still get a fixed order.
+    a.cmp(b)
+}
+
+fn take_digits(chars: &mut Peekable<Chars>) -> String {
+    let mut digits = String::new();
+    while let Some(digit) = chars.next_if(|c| c.is_ascii_digit()) {
+        digits.push(digit);
+    }
+    digits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_natural_cmp() {
+        let mut names = vec![
+            "10 - y.mp3",
+            "2 - x.mp3",
+            "1 - b.mp3",
+            "Disc 10",
+            "disc 9",
+            "02 - z.mp3",
+            "a",
+            "",
+        ];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(
+            names,
+            vec![
+                "",
+                "1 - b.mp3",
+                "2 - x.mp3",
+                "02 - z.mp3",
+                "10 - y.mp3",
+                "a",
+                "disc 9",
+                "Disc 10"
+            ]
+        );
+    }
+}
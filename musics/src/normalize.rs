@@ -0,0 +1,103 @@
+//! Prepares text for searching, so that it matches regardless of how it was written.
+//!
+//! - Compatibility forms are replaced by the normal characters (NFKD):
+//!   full-width "ｂｊｏｒｋ" becomes "bjork", and half-width "ｶﾅ" becomes "カナ".
+//! - Diacritics are removed: "Björk" becomes "Bjork".
+//! - Letters of other scripts are transliterated to latin: "Кино" becomes "Kino",
+//!   and both "かな" and "カナ" become "kana".
+//! - [`normalize`] also makes everything lowercase.
+
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// Text that went through everything except making it lowercase.
+/// Keeping the case helps to find where words start, like in "CamelCase".
+pub struct NormalizedText {
+    pub chars: Vec<char>,
+    /// For every char in `chars`, the index of the char in the original text it came from.
+    /// A single original char can turn into multiple chars, or none at all.
+    pub original_indices: Vec<usize>,
+}
+
+/// Normalizes the text as described in the module docs, and makes it lowercase.
+pub fn normalize(text: &str) -> String {
+    normalize_keeping_case(text)
+        .chars
+        .into_iter()
+        .map(lowercase)
+        .collect()
+}
+
+pub fn normalize_keeping_case(text: &str) -> NormalizedText {
+    let mut normalized = NormalizedText {
+        chars: Vec::with_capacity(text.len()),
+        original_indices: Vec::with_capacity(text.len()),
+    };
+
+    for (index, c) in text.chars().enumerate() {
+        // Most titles are plain ascii, which is already normalized.
+        if c.is_ascii() {
+            normalized.chars.push(c);
+            normalized.original_indices.push(index);
+            continue;
+        }
+
+        for decomposed in std::iter::once(c).nfkd() {
+            if is_combining_mark(decomposed) {
+                continue;
+            }
+
+            // Only letters, because symbols and emoji would turn into their names.
+            let transliterated = if decomposed.is_alphabetic() && !decomposed.is_ascii() {
+                deunicode::deunicode_char(decomposed)
+            } else {
+                None
+            };
+
+            match transliterated {
+                Some(text) => {
+                    for c in text.chars() {
+                        normalized.chars.push(c);
+                        normalized.original_indices.push(index);
+                    }
+                }
+                None => {
+                    normalized.chars.push(decomposed);
+                    normalized.original_indices.push(index);
+                }
+            }
+        }
+    }
+
+    normalized
+}
+
+/// Some characters turn into multiple characters when made lowercase. Only the first is kept,
+/// so that every character still has exactly one lowercase version.
+pub fn lowercase(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("Björk"), "bjork");
+        assert_eq!(normalize("Sigur Rós"), "sigur ros");
+        assert_eq!(normalize("ＢＪÖＲＫ"), "bjork");
+        assert_eq!(normalize("Motörhead Œuvre ß"), "motorhead oeuvre ss");
+        assert_eq!(normalize("Кино"), "kino");
+        assert_eq!(normalize("かな"), normalize("カナ"));
+        assert_eq!(normalize("ｶﾅ"), normalize("カナ"));
+        assert_eq!(normalize("Blank Holes (2nd) ♪"), "blank holes (2nd) ♪");
+    }
+
+    #[test]
+    fn test_original_indices() {
+        let normalized = normalize_keeping_case("Ærø!");
+        assert_eq!(normalized.chars, vec!['A', 'E', 'r', 'o', '!']);
+        assert_eq!(normalized.original_indices, vec![0, 0, 1, 2, 3]);
+    }
+}
@@ -0,0 +1,624 @@
+//! Moves the files of songs to folders and names made from their tags, with a template like:
+//!
+//! ```text
+//! {albumartist}/{year} - {album}/{disc}-{track:02} {title}.{ext}
+//! ```
+//!
+//! - `{field}` is replaced by the value of the field: title, artist, albumartist, album, genre,
+//!   year, track, disc or ext. Missing text fields become "Unknown Artist" and so on,
+//!   missing numbers are left out.
+//! - Numbers can be padded with zeroes: `{track:02}` makes track 3 "03".
+//! - `/` separates folders. The path is relative to the library folder.
+//!
+//! Songs keep their ids when they move, so playlists keep them as well.
+
+use crate::library::{Library, Song, SongId};
+use camino::{Utf8Path, Utf8PathBuf};
+use std::collections::{HashMap, HashSet};
+use std::io;
+
+/// Characters that Windows, macOS or Linux don't allow in file names.
+const INVALID_CHARACTERS: [char; 9] = ['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+/// Names Windows doesn't allow, even with an extension.
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum TemplateField {
+    Title,
+    Artist,
+    /// Falls back to the artist, most files don't have an album artist.
+    AlbumArtist,
+    Album,
+    Genre,
+    Year,
+    Track,
+    Disc,
+    Extension,
+}
+
+impl TemplateField {
+    const ALL: [TemplateField; 9] = [
+        TemplateField::Title,
+        TemplateField::Artist,
+        TemplateField::AlbumArtist,
+        TemplateField::Album,
+        TemplateField::Genre,
+        TemplateField::Year,
+        TemplateField::Track,
+        TemplateField::Disc,
+        TemplateField::Extension,
+    ];
+
+    fn key(&self) -> &'static str {
+        match self {
+            TemplateField::Title => "title",
+            TemplateField::Artist => "artist",
+            TemplateField::AlbumArtist => "albumartist",
+            TemplateField::Album => "album",
+            TemplateField::Genre => "genre",
+            TemplateField::Year => "year",
+            TemplateField::Track => "track",
+            TemplateField::Disc => "disc",
+            TemplateField::Extension => "ext",
+        }
+    }
+
+    fn is_number(&self) -> bool {
+        matches!(
+            self,
+            TemplateField::Year | TemplateField::Track | TemplateField::Disc
+        )
+    }
+
+    /// Empty for missing numbers and extensions.
+    fn value(&self, song: &Song, width: usize) -> String {
+        let text = |text: Option<&String>, name: &str| {
+            text.cloned().unwrap_or_else(|| format!("Unknown {}", name))
+        };
+        let number = |number: Option<u32>| {
+            number
+                .map(|number| format!("{:0width$}", number, width = width))
+                .unwrap_or_default()
+        };
+        match self {
+            TemplateField::Title => song.title.clone(),
+            TemplateField::Artist => text(song.artist.as_ref(), "Artist"),
+            TemplateField::AlbumArtist => text(
+                song.album_artist.as_ref().or(song.artist.as_ref()),
+                "Artist",
+            ),
+            TemplateField::Album => text(song.album.as_ref(), "Album"),
+            TemplateField::Genre => text(song.genre.as_ref(), "Genre"),
+            TemplateField::Year => number(song.year),
+            TemplateField::Track => number(song.track_number),
+            TemplateField::Disc => number(song.disc_number),
+            TemplateField::Extension => song.path.extension().unwrap_or_default().to_string(),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Part {
+    Text(String),
+    Field { field: TemplateField, width: usize },
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].to_string()));
+            }
+            let after_start = &rest[start + 1..];
+            let Some(end) = after_start.find('}') else {
+                return Err("A } is missing, fields look like {title}.".to_string());
+            };
+
+            let (name, width) = match after_start[..end].split_once(':') {
+                Some((name, width)) => (name, Some(width)),
+                None => (&after_start[..end], None),
+            };
+            let field = TemplateField::ALL
+                .into_iter()
+                .find(|field| field.key() == name.to_lowercase())
+                .ok_or_else(|| {
+                    format!(
+                        "There is no field \"{}\". Fields are title, artist, albumartist, album, \
+                        genre, year, track, disc and ext.",
+                        name
+                    )
+                })?;
+            let width = match width {
+                None => 0,
+                Some(width) if field.is_number() => width
+                    .parse()
+                    .map_err(|_| format!("\"{}\" isn't a width, like {{track:02}}.", width))?,
+                Some(_) => return Err(format!("Only numbers can be padded, not {}.", name)),
+            };
+
+            parts.push(Part::Field { field, width });
+            rest = &after_start[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_string()));
+        }
+
+        let has_extension = parts.iter().any(|part| {
+            matches!(
+                part,
+                Part::Field {
+                    field: TemplateField::Extension,
+                    ..
+                }
+            )
+        });
+        if !has_extension {
+            return Err("The template needs {ext}, or the files lose their extension.".to_string());
+        }
+
+        Ok(Template { parts })
+    }
+
+    /// Where the song goes, relative to the library folder.
+    pub fn render(&self, song: &Song) -> Utf8PathBuf {
+        let mut path = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => path.push_str(text),
+                // Values can't make folders.
+                Part::Field { field, width } => {
+                    path.push_str(&field.value(song, *width).replace(['/', '\\'], "_"))
+                }
+            }
+        }
+
+        path.split('/')
+            .map(sanitize)
+            .filter(|name| !name.is_empty())
+            .collect()
+    }
+}
+
+/// Makes the name safe to use for a file or folder, on every filesystem.
+pub fn sanitize(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_control() || INVALID_CHARACTERS.contains(&c) {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+
+    // Windows drops dots and spaces at the end of names.
+    let name = name.trim().trim_end_matches(['.', ' ']);
+    if name.is_empty() {
+        return String::new();
+    }
+
+    let stem = name.split('.').next().unwrap_or_default();
+    if RESERVED_NAMES.contains(&stem.to_uppercase().as_str()) {
+        format!("{}_{}", stem, &name[stem.len()..])
+    } else {
+        name.to_string()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Conflict {
+    /// There is a file at the new path already, which isn't moved away.
+    /// Files of other songs that move as well don't count, they make room first.
+    FileExists,
+    /// More than one song would get the new path.
+    SamePath,
+}
+
+impl Conflict {
+    pub fn description(&self) -> &'static str {
+        match self {
+            Conflict::FileExists => "A file is already there",
+            Conflict::SamePath => "Another song goes there too",
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Move {
+    pub song: SongId,
+    pub from: Utf8PathBuf,
+    pub to: Utf8PathBuf,
+    /// Songs with a conflict aren't moved.
+    pub conflict: Option<Conflict>,
+}
+
+/// What organizing would do, to preview it.
+pub struct Plan {
+    /// Only the songs that change path, by their current path.
+    pub moves: Vec<Move>,
+    /// How many songs are already where the template puts them.
+    pub unchanged: usize,
+    /// The [`Library::version`] the plan was made for. Songs may have other tags or paths
+    /// in later versions.
+    pub library_version: u64,
+}
+
+impl Plan {
+    /// Only songs in the library folder are moved, others are left where they are.
+    pub fn new(library: &Library, root: &Utf8Path, template: &Template) -> Self {
+        let mut moves = Vec::new();
+        let mut unchanged = 0;
+        for (id, song) in library.songs() {
+            if !song.path.starts_with(root) {
+                continue;
+            }
+            let to = root.join(template.render(song));
+            if to == song.path {
+                unchanged += 1;
+            } else {
+                moves.push(Move {
+                    song: id,
+                    from: song.path.clone(),
+                    to,
+                    conflict: None,
+                });
+            }
+        }
+        moves.sort_by(|a, b| a.from.cmp(&b.from));
+
+        // Compared in lowercase, because some filesystems ignore case.
+        let mut songs_per_path: HashMap<String, usize> = HashMap::new();
+        for path in moves.iter().map(|m| &m.to) {
+            *songs_per_path
+                .entry(path.as_str().to_lowercase())
+                .or_insert(0) += 1;
+        }
+        for m in &mut moves {
+            if songs_per_path[&m.to.as_str().to_lowercase()] > 1 {
+                m.conflict = Some(Conflict::SamePath);
+            }
+        }
+
+        // A file that is in the way is fine when it moves away too, which it doesn't when its
+        // own move has a conflict. That can block the move to its path in turn.
+        let moving_away: HashSet<String> = moves
+            .iter()
+            .filter(|m| m.conflict.is_none())
+            .map(|m| m.from.as_str().to_lowercase())
+            .collect();
+        let by_target: HashMap<String, usize> = moves
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| m.conflict.is_none())
+            .map(|(index, m)| (m.to.as_str().to_lowercase(), index))
+            .collect();
+        let target_exists: Vec<bool> = moves.iter().map(|m| m.to.exists()).collect();
+        let mut blocked: Vec<usize> = (0..moves.len())
+            .filter(|index| {
+                let m = &moves[*index];
+                m.conflict.is_none()
+                    && target_exists[*index]
+                    && !moving_away.contains(&m.to.as_str().to_lowercase())
+            })
+            .collect();
+        while let Some(index) = blocked.pop() {
+            if moves[index].conflict.is_some() {
+                continue;
+            }
+            moves[index].conflict = Some(Conflict::FileExists);
+            if let Some(&other) = by_target.get(&moves[index].from.as_str().to_lowercase()) {
+                if target_exists[other] {
+                    blocked.push(other);
+                }
+            }
+        }
+
+        Plan {
+            moves,
+            unchanged,
+            library_version: library.version(),
+        }
+    }
+
+    pub fn conflicts(&self) -> usize {
+        self.moves.iter().filter(|m| m.conflict.is_some()).count()
+    }
+}
+
+/// The song, where its file was and where it is now.
+type FileMove = (SongId, Utf8PathBuf, Utf8PathBuf);
+
+/// The moves that were done, so they can be undone.
+pub struct Organization {
+    moves: Vec<FileMove>,
+}
+
+impl Organization {
+    pub fn len(&self) -> usize {
+        self.moves.len()
+    }
+
+    /// Gives the songs their new paths, once the files are moved.
+    pub fn update_library(&self, library: &mut Library) {
+        for (id, _, to) in &self.moves {
+            library.move_song(*id, to.clone());
+        }
+    }
+
+    /// Moves the files back, and removes the folders that are left empty.
+    /// Returns the moves back, to update the library with, and what went wrong for each file
+    /// that couldn't be moved back.
+    pub fn undo(&self, root: &Utf8Path) -> (Organization, Vec<String>) {
+        let back = self
+            .moves
+            .iter()
+            .map(|(id, from, to)| (*id, to.clone(), from.clone()))
+            .collect();
+        let (done, failed) = move_files(back, root);
+        let errors = failed
+            .into_iter()
+            .map(|(path, error)| format!("Could not move {} back: {}", path, error))
+            .collect();
+        (Organization { moves: done }, errors)
+    }
+}
+
+/// Moves the files without conflicts, and removes the folders that are left empty.
+/// Returns what was done, and what went wrong for each file that couldn't be moved.
+///
+/// Only touches the files, so it can run on another thread. The library is updated
+/// afterwards, with [`Organization::update_library`].
+pub fn organize(root: &Utf8Path, moves: &[Move]) -> (Organization, Vec<String>) {
+    let moves = moves
+        .iter()
+        .filter(|m| m.conflict.is_none())
+        .map(|m| (m.song, m.from.clone(), m.to.clone()))
+        .collect();
+    let (done, failed) = move_files(moves, root);
+    let errors = failed
+        .into_iter()
+        .map(|(path, error)| format!("Could not move {}: {}", path, error))
+        .collect();
+    (Organization { moves: done }, errors)
+}
+
+/// Moves every file to a temporary name next to it first, and only then to where it goes.
+/// That way the files can take each other's places, like when two songs swap track numbers.
+/// Returns the moves that were done, and the files that couldn't be moved.
+fn move_files(
+    moves: Vec<FileMove>,
+    root: &Utf8Path,
+) -> (Vec<FileMove>, Vec<(Utf8PathBuf, io::Error)>) {
+    let mut failed = Vec::new();
+    let mut moved_aside = Vec::new();
+    for (id, from, to) in moves {
+        let temporary = from.with_file_name(format!(
+            ".{}.organizing",
+            from.file_name().unwrap_or_default()
+        ));
+        match move_file(&from, &temporary) {
+            Ok(()) => moved_aside.push((id, from, temporary, to)),
+            Err(error) => failed.push((from, error)),
+        }
+    }
+
+    let mut done = Vec::new();
+    for (id, from, temporary, to) in moved_aside {
+        match move_file(&temporary, &to) {
+            Ok(()) => {
+                remove_empty_folders(&from, root);
+                done.push((id, from, to));
+            }
+            Err(error) => {
+                // Another file may have taken its place in the meantime.
+                let error = match move_file(&temporary, &from) {
+                    Ok(()) => error,
+                    Err(_) => io::Error::new(
+                        error.kind(),
+                        format!("{}, it is left at {}", error, temporary),
+                    ),
+                };
+                failed.push((from, error));
+            }
+        }
+    }
+    (done, failed)
+}
+
+/// Never replaces a file that is already there.
+fn move_file(from: &Utf8Path, to: &Utf8Path) -> io::Result<()> {
+    if to.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", to),
+        ));
+    }
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    // Renaming doesn't work across drives, copying does.
+    std::fs::rename(from, to).or_else(|error| {
+        std::fs::copy(from, to).map_err(|_| error)?;
+        std::fs::remove_file(from)
+    })
+}
+
+/// Removes the folders of the file that are empty, up to the library folder.
+fn remove_empty_folders(file: &Utf8Path, root: &Utf8Path) {
+    let mut folder = file.parent();
+    while let Some(path) = folder {
+        // Fails when the folder isn't empty.
+        if path == root || !path.starts_with(root) || std::fs::remove_dir(path).is_err() {
+            break;
+        }
+        folder = path.parent();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn test_render() {
+        let template =
+            Template::parse("{albumartist}/{year} - {album}/{disc}-{track:02} {title}.{ext}")
+                .unwrap();
+        assert_eq!(
+            template.render(
+                &Song::named("Song.")
+                    .with_artist("AC/DC")
+                    .with_album("Album: Live?")
+                    .with_year(1980)
+                    .with_track(3)
+                    .with_path("/music/song.mp3")
+            ),
+            Utf8PathBuf::from("AC_DC/1980 - Album_ Live_/-03 Song..mp3")
+        );
+
+        let template = Template::parse("{genre}/{title}.{ext}").unwrap();
+        assert_eq!(
+            template.render(&Song::named("con").with_path("/music/song.ogg")),
+            Utf8PathBuf::from("Unknown Genre/con_.ogg")
+        );
+
+        assert!(Template::parse("{title}").is_err());
+        assert!(Template::parse("{title:02}.{ext}").is_err());
+        assert!(Template::parse("{composer}.{ext}").is_err());
+        assert!(Template::parse("{title.{ext}").is_err());
+    }
+
+    #[test]
+    fn test_sanitize() {
+        assert_eq!(sanitize("What? Yes: no"), "What_ Yes_ no");
+        assert_eq!(sanitize("  Name. . "), "Name");
+        assert_eq!(sanitize("nul.mp3"), "nul_.mp3");
+        assert_eq!(sanitize("..."), "");
+    }
+
+    #[test]
+    fn test_organize_and_undo() {
+        let directory = TempDir::new("organizer");
+        let root = directory.path();
+        std::fs::create_dir_all(root.join("old")).unwrap();
+        for name in ["a.mp3", "b.mp3", "c.mp3"] {
+            std::fs::write(root.join("old").join(name), name).unwrap();
+        }
+
+        let mut library = Library::new();
+        let mut insert = |title: &str, path: &str| {
+            let song = Song::named(title).with_artist("AC/DC");
+            library.insert_song(song.with_path(root.join(path).as_str()))
+        };
+        let a = insert("First", "old/a.mp3");
+        let b = insert("Second", "old/b.mp3");
+        // Gets the same path as `a`.
+        let c = insert("First", "old/c.mp3");
+
+        let template = Template::parse("{artist}/{title}.{ext}").unwrap();
+        let plan = Plan::new(&library, root, &template);
+        assert_eq!(plan.moves.len(), 3);
+        assert_eq!(plan.conflicts(), 2);
+
+        let (organization, errors) = organize(root, &plan.moves);
+        assert!(errors.is_empty());
+        organization.update_library(&mut library);
+        assert_eq!(organization.len(), 1);
+        let new_path = root.join("AC_DC/Second.mp3");
+        assert_eq!(library.get_song(b).unwrap().path, new_path);
+        assert_eq!(std::fs::read_to_string(&new_path).unwrap(), "b.mp3");
+        assert_eq!(library.get_song(a).unwrap().path, root.join("old/a.mp3"));
+        assert_eq!(library.get_song(c).unwrap().path, root.join("old/c.mp3"));
+
+        let (back, errors) = organization.undo(root);
+        assert!(errors.is_empty());
+        back.update_library(&mut library);
+        assert_eq!(library.get_song(b).unwrap().path, root.join("old/b.mp3"));
+        assert!(root.join("old/b.mp3").is_file());
+        // The folder that was made for it is gone again.
+        assert!(!root.join("AC_DC").exists());
+    }
+
+    #[test]
+    fn test_swap_places() {
+        let directory = TempDir::new("organizer");
+        let root = directory.path();
+        for name in [
+            "02 First.mp3",
+            "03 Second.mp3",
+            "04 Third.mp3",
+            "05 Fourth.mp3",
+        ] {
+            std::fs::write(root.join(name), name).unwrap();
+        }
+
+        // The first two swap places, the third moves onto the fourth, which stays.
+        let mut library = Library::new();
+        let mut insert = |title: &str, track: u32, path: &str| {
+            let song = Song::named(title).with_track(track);
+            library.insert_song(song.with_path(root.join(path).as_str()))
+        };
+        let first = insert("First", 3, "02 First.mp3");
+        let second = insert("Second", 2, "03 Second.mp3");
+        let third = insert("Fourth", 5, "04 Third.mp3");
+        insert("Fourth", 5, "05 Fourth.mp3");
+
+        let template = Template::parse("{track:02} {title}.{ext}").unwrap();
+        let plan = Plan::new(&library, root, &template);
+        // The fourth is in place already, and is in the way of the third.
+        assert_eq!(plan.unchanged, 1);
+        assert_eq!(plan.moves.len(), 3);
+        let conflicts: Vec<_> = plan.moves.iter().map(|m| (m.song, m.conflict)).collect();
+        assert!(conflicts.contains(&(first, None)));
+        assert!(conflicts.contains(&(second, None)));
+        assert!(conflicts.contains(&(third, Some(Conflict::FileExists))));
+
+        let (organization, errors) = organize(root, &plan.moves);
+        assert!(errors.is_empty(), "{:?}", errors);
+        organization.update_library(&mut library);
+        assert_eq!(organization.len(), 2);
+        let first_path = root.join("03 First.mp3");
+        assert_eq!(library.get_song(first).unwrap().path, first_path);
+        assert_eq!(
+            std::fs::read_to_string(&first_path).unwrap(),
+            "02 First.mp3"
+        );
+        let second_path = root.join("02 Second.mp3");
+        assert_eq!(
+            std::fs::read_to_string(&second_path).unwrap(),
+            "03 Second.mp3"
+        );
+        assert_eq!(
+            std::fs::read_to_string(root.join("05 Fourth.mp3")).unwrap(),
+            "05 Fourth.mp3"
+        );
+
+        let (back, errors) = organization.undo(root);
+        assert!(errors.is_empty(), "{:?}", errors);
+        back.update_library(&mut library);
+        assert_eq!(
+            library.get_song(first).unwrap().path,
+            root.join("02 First.mp3")
+        );
+        assert_eq!(
+            std::fs::read_to_string(root.join("02 First.mp3")).unwrap(),
+            "02 First.mp3"
+        );
+        // No temporary files are left.
+        assert_eq!(std::fs::read_dir(root).unwrap().count(), 4);
+    }
+}
@@ -0,0 +1,251 @@
+use crate::library::Library;
+use crate::organizer::{organize, Organization, Plan, Template};
+use camino::Utf8Path;
+use eframe::egui;
+use eframe::egui::{Color32, Context, RichText};
+use egui_extras::{Column, TableBuilder};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::thread;
+
+/// Looking at every file and moving them takes a while, so it's done on another thread.
+enum Work {
+    Planned(Plan),
+    Organized(Organization, Vec<String>),
+    Undone(Organization, Vec<String>),
+}
+
+pub struct OrganizerView {
+    show_window: bool,
+    /// The preview, made when asked for. Moving files is only done from a preview.
+    plan: Option<Plan>,
+    /// Whether the preview was thrown away, because the library changed since.
+    plan_outdated: bool,
+    /// The last organization, to undo it.
+    last: Option<Organization>,
+    /// Why files couldn't be moved, in the last organization or undo.
+    errors: Vec<String>,
+    working: Option<Receiver<Work>>,
+}
+
+impl OrganizerView {
+    pub fn new() -> Self {
+        OrganizerView {
+            show_window: false,
+            plan: None,
+            plan_outdated: false,
+            last: None,
+            errors: Vec::new(),
+            working: None,
+        }
+    }
+
+    fn start(&mut self, ctx: &Context, work: impl FnOnce() -> Work + Send + 'static) {
+        let (sender, receiver) = channel();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            if sender.send(work()).is_ok() {
+                ctx.request_repaint();
+            }
+        });
+        self.working = Some(receiver);
+    }
+
+    /// Also when the window is closed, so moved files are never missed in the library.
+    fn receive_work(&mut self, library: &mut Library) {
+        let Some(receiver) = &self.working else {
+            return;
+        };
+        let work = match receiver.try_recv() {
+            Ok(work) => work,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => {
+                self.working = None;
+                return;
+            }
+        };
+        self.working = None;
+
+        match work {
+            Work::Planned(plan) => self.plan = Some(plan),
+            Work::Organized(organization, errors) => {
+                organization.update_library(library);
+                self.last = Some(organization);
+                self.errors = errors;
+            }
+            Work::Undone(back, errors) => {
+                back.update_library(library);
+                self.errors = errors;
+            }
+        }
+    }
+
+    pub fn open_window(&mut self) {
+        self.show_window = true;
+    }
+
+    /// `root` is the library folder, the template is relative to it.
+    pub fn show(
+        &mut self,
+        ctx: &Context,
+        library: &mut Library,
+        root: &Utf8Path,
+        template_text: &mut String,
+    ) {
+        self.receive_work(library);
+        // Tags or paths may have changed, so the preview may not be what would happen.
+        if self
+            .plan
+            .as_ref()
+            .is_some_and(|plan| plan.library_version != library.version())
+        {
+            self.plan = None;
+            self.plan_outdated = true;
+        }
+
+        let working = self.working.is_some();
+        let mut show_window = self.show_window;
+        egui::Window::new("Organize files")
+            .open(&mut show_window)
+            .default_width(700.0)
+            .default_height(500.0)
+            .show(ctx, |ui| {
+                let response = ui.add_enabled(
+                    !working,
+                    egui::TextEdit::singleline(template_text)
+                        .hint_text("{albumartist}/{year} - {album}/{disc}-{track:02} {title}.{ext}")
+                        .desired_width(f32::INFINITY),
+                );
+                if response.changed() {
+                    self.plan = None;
+                }
+                ui.weak(
+                    "Fields: {title} {artist} {albumartist} {album} {genre} {year} {track} \
+                    {disc} {ext}. Numbers can be padded with zeroes, like {track:02}.",
+                );
+
+                let template = Template::parse(template_text);
+                if let (Err(error), false) = (&template, template_text.is_empty()) {
+                    ui.colored_label(Color32::LIGHT_RED, error);
+                }
+
+                ui.horizontal(|ui| {
+                    let can_preview = template.is_ok() && root != "" && !working;
+                    if ui
+                        .add_enabled(can_preview, egui::Button::new("Preview"))
+                        .on_disabled_hover_text("Needs a template and a library folder.")
+                        .clicked()
+                    {
+                        if let Ok(template) = template.clone() {
+                            let library = library.clone();
+                            let root = root.to_owned();
+                            self.start(ui.ctx(), move || {
+                                Work::Planned(Plan::new(&library, &root, &template))
+                            });
+                            self.plan = None;
+                            self.plan_outdated = false;
+                        }
+                    }
+
+                    if let Some(plan) = &self.plan {
+                        let moving = plan.moves.len() - plan.conflicts();
+                        if ui
+                            .add_enabled(
+                                moving > 0 && !working,
+                                egui::Button::new(format!("Move {} files", moving)),
+                            )
+                            .clicked()
+                        {
+                            if let Some(plan) = self.plan.take() {
+                                let root = root.to_owned();
+                                self.start(ui.ctx(), move || {
+                                    let (organization, errors) = organize(&root, &plan.moves);
+                                    Work::Organized(organization, errors)
+                                });
+                            }
+                        }
+                    }
+
+                    if let Some(last) = &self.last {
+                        let text = format!("Undo moving {} files", last.len());
+                        if ui.add_enabled(!working, egui::Button::new(text)).clicked() {
+                            if let Some(last) = self.last.take() {
+                                let root = root.to_owned();
+                                self.start(ui.ctx(), move || {
+                                    let (back, errors) = last.undo(&root);
+                                    Work::Undone(back, errors)
+                                });
+                            }
+                            self.plan = None;
+                        }
+                    }
+
+                    if working {
+                        ui.spinner();
+                    }
+                });
+
+                if self.plan_outdated {
+                    ui.weak("The library changed since the preview, preview again.");
+                }
+
+                for error in &self.errors {
+                    ui.colored_label(Color32::LIGHT_RED, error);
+                }
+
+                if let Some(plan) = &self.plan {
+                    ui.label(format!(
+                        "{} files move, {} have conflicts and stay, {} are in place already.",
+                        plan.moves.len() - plan.conflicts(),
+                        plan.conflicts(),
+                        plan.unchanged
+                    ));
+                    show_plan(ui, plan, root);
+                }
+            });
+        self.show_window = show_window;
+    }
+}
+
+fn show_plan(ui: &mut egui::Ui, plan: &Plan, root: &Utf8Path) {
+    let relative = |path: &Utf8Path| {
+        path.strip_prefix(root)
+            .map(|path| path.to_string())
+            .unwrap_or_else(|_| path.to_string())
+    };
+    let row_height = ui.spacing().interact_size.y;
+
+    TableBuilder::new(ui)
+        .striped(true)
+        .resizable(true)
+        .auto_shrink([false, false])
+        .column(Column::initial(250.0).at_least(20.0).clip(true))
+        .column(Column::initial(300.0).at_least(20.0).clip(true))
+        .column(Column::remainder())
+        .header(row_height, |mut header| {
+            header.col(|ui| {
+                ui.strong("From");
+            });
+            header.col(|ui| {
+                ui.strong("To");
+            });
+            header.col(|ui| {
+                ui.strong("Conflict");
+            });
+        })
+        .body(|body| {
+            body.rows(row_height, plan.moves.len(), |index, mut row| {
+                let m = &plan.moves[index];
+                row.col(|ui| {
+                    ui.label(relative(&m.from));
+                });
+                row.col(|ui| {
+                    ui.label(relative(&m.to));
+                });
+                row.col(|ui| {
+                    if let Some(conflict) = m.conflict {
+                        ui.label(RichText::new(conflict.description()).color(Color32::LIGHT_RED));
+                    }
+                });
+            });
+        });
+}
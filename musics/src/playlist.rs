@@ -1,12 +1,114 @@
-use crate::library::SongId;
+use crate::library::{Library, SongId, SortKey};
+use crate::radio::{Radio, RadioSettings};
+use crate::shuffle::{self, ShuffleMode};
+use std::collections::{vec_deque, HashMap, HashSet, VecDeque};
 use std::slice::Iter;
 
-// TODO (2023-02-03): Add tests.
+/// How many edits to the playlist can be undone.
+const MAX_UNDO_EDITS: usize = 100;
+
+/// In radio mode, how many played songs are kept before the current song.
+/// Older ones are removed, so the playlist doesn't keep growing while the radio plays.
+const MAX_RADIO_HISTORY: usize = 100;
+
+/// A reversible change to the songs in a playlist.
+#[derive(Clone, Debug, PartialEq)]
+enum PlaylistEdit {
+    /// Inserts the songs so that they end up at the given indices. Sorted by index.
+    Insert(Vec<(usize, SongId)>),
+    /// Removes the songs at the given indices. Sorted by index.
+    Remove(Vec<(usize, SongId)>),
+    /// Moves the song at index `order[i]` to index `i`.
+    /// Only touches the first `order.len()` songs, because radio mode can append songs
+    /// without recording an edit.
+    Reorder(Vec<usize>),
+    /// Multiple edits that are undone together, applied in order.
+    Batch(Vec<PlaylistEdit>),
+}
+
+impl PlaylistEdit {
+    fn inverse(&self) -> PlaylistEdit {
+        match self {
+            PlaylistEdit::Insert(songs) => PlaylistEdit::Remove(songs.clone()),
+            PlaylistEdit::Remove(songs) => PlaylistEdit::Insert(songs.clone()),
+            PlaylistEdit::Reorder(order) => {
+                let mut inverse = vec![0; order.len()];
+                for (new_index, old_index) in order.iter().enumerate() {
+                    inverse[*old_index] = new_index;
+                }
+                PlaylistEdit::Reorder(inverse)
+            }
+            PlaylistEdit::Batch(edits) => {
+                PlaylistEdit::Batch(edits.iter().rev().map(PlaylistEdit::inverse).collect())
+            }
+        }
+    }
+
+    fn changes_nothing(&self) -> bool {
+        match self {
+            PlaylistEdit::Insert(songs) | PlaylistEdit::Remove(songs) => songs.is_empty(),
+            PlaylistEdit::Reorder(order) => order.iter().enumerate().all(|(i, index)| i == *index),
+            PlaylistEdit::Batch(edits) => edits.iter().all(PlaylistEdit::changes_nothing),
+        }
+    }
+
+    /// The same edit, for after the first `count` songs were removed from the playlist.
+    /// `None` if the edit touches those songs.
+    fn without_first(&self, count: usize) -> Option<PlaylistEdit> {
+        let shift = |songs: &Vec<(usize, SongId)>| {
+            songs
+                .iter()
+                .map(|(index, id)| Some((index.checked_sub(count)?, *id)))
+                .collect::<Option<Vec<_>>>()
+        };
+        match self {
+            PlaylistEdit::Insert(songs) => shift(songs).map(PlaylistEdit::Insert),
+            PlaylistEdit::Remove(songs) => shift(songs).map(PlaylistEdit::Remove),
+            PlaylistEdit::Reorder(order) => {
+                let (removed, kept) = order.split_at(count.min(order.len()));
+                if removed.iter().enumerate().any(|(i, index)| i != *index) {
+                    return None;
+                }
+                kept.iter()
+                    .map(|index| index.checked_sub(count))
+                    .collect::<Option<_>>()
+                    .map(PlaylistEdit::Reorder)
+            }
+            PlaylistEdit::Batch(edits) => edits
+                .iter()
+                .map(|edit| edit.without_first(count))
+                .collect::<Option<_>>()
+                .map(PlaylistEdit::Batch),
+        }
+    }
+}
+
+/// An edit, together with which song was current before it was made.
+/// So undoing it can restore the current song as well.
+struct UndoableEdit {
+    edit: PlaylistEdit,
+    current_song_index: Option<usize>,
+    detached_song: Option<SongId>,
+}
 
 #[derive(Default)]
 pub struct Playlist {
     songs: Vec<SongId>,
     current_song_index: Option<usize>,
+    /// When in radio mode, the playlist keeps filling itself with random songs.
+    radio: Option<Radio>,
+    /// The order of the songs from before they were shuffled, so the shuffle can be undone.
+    original_order: Option<Vec<SongId>>,
+    /// Songs that play after the current song, before the playlist continues.
+    queue: VecDeque<SongId>,
+    /// Set when the current song is not part of the playlist: it came from the queue,
+    /// or it was removed from the playlist while playing. In that case `current_song_index`
+    /// points at the song the playlist should continue after.
+    detached_song: Option<SongId>,
+    /// Most recent edit at the back.
+    undo_stack: VecDeque<UndoableEdit>,
+    /// Most recently undone edit at the back.
+    redo_stack: Vec<PlaylistEdit>,
 }
 
 impl Playlist {
@@ -22,15 +124,314 @@ impl Playlist {
         self.songs.len()
     }
 
-    pub fn append_song(&mut self, song: SongId) {
-        self.songs.push(song)
+    pub fn append_songs(&mut self, songs: &[SongId]) {
+        let start = self.songs.len();
+        self.edit(PlaylistEdit::Insert(
+            songs
+                .iter()
+                .enumerate()
+                .map(|(offset, id)| (start + offset, *id))
+                .collect(),
+        ));
     }
 
-    pub fn append_songs(&mut self, songs: &[SongId]) {
-        self.songs.extend_from_slice(songs);
+    /// Removes every song. The current song keeps playing.
+    pub fn clear(&mut self) {
+        self.edit(PlaylistEdit::Remove(
+            self.songs.iter().cloned().enumerate().collect(),
+        ));
+    }
+
+    /// Replaces every song with the given songs, as a single edit.
+    /// The current song keeps playing, and the playlist continues with the first new song.
+    pub fn replace_songs(&mut self, songs: &[SongId]) {
+        self.edit(PlaylistEdit::Batch(vec![
+            PlaylistEdit::Remove(self.songs.iter().cloned().enumerate().collect()),
+            PlaylistEdit::Insert(songs.iter().cloned().enumerate().collect()),
+        ]));
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
     }
 
+    pub fn undo(&mut self) {
+        let Some(undoable) = self.undo_stack.pop_back() else {
+            return;
+        };
+
+        let playing_song = self.current_song_id();
+        self.apply(&undoable.edit.inverse());
+
+        // If the same song is still playing, undoing should also put it back
+        // where it was in the playlist.
+        let song_before_edit = undoable.detached_song.or_else(|| {
+            undoable
+                .current_song_index
+                .and_then(|index| self.songs.get(index).cloned())
+        });
+        if playing_song == song_before_edit {
+            self.current_song_index = undoable.current_song_index;
+            self.detached_song = undoable.detached_song;
+        }
+
+        self.redo_stack.push(undoable.edit);
+    }
+
+    pub fn redo(&mut self) {
+        if let Some(edit) = self.redo_stack.pop() {
+            self.record(edit);
+        }
+    }
+
+    /// Applies the edit, and records it so it can be undone.
+    fn edit(&mut self, edit: PlaylistEdit) {
+        if edit.changes_nothing() {
+            return;
+        }
+
+        self.record(edit);
+        self.redo_stack.clear();
+    }
+
+    fn record(&mut self, edit: PlaylistEdit) {
+        let undoable = UndoableEdit {
+            current_song_index: self.current_song_index,
+            detached_song: self.detached_song,
+            edit,
+        };
+        self.apply(&undoable.edit);
+
+        self.undo_stack.push_back(undoable);
+        if self.undo_stack.len() > MAX_UNDO_EDITS {
+            self.undo_stack.pop_front();
+        }
+    }
+
+    /// Makes sure the current song stays the same, even when it is removed.
+    /// Does not record the edit.
+    fn apply(&mut self, edit: &PlaylistEdit) {
+        match edit {
+            PlaylistEdit::Insert(songs) => {
+                for (index, id) in songs {
+                    let index = (*index).min(self.songs.len());
+                    self.songs.insert(index, *id);
+
+                    match self.current_song_index {
+                        Some(current) if current >= index => {
+                            self.current_song_index = Some(current + 1);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            PlaylistEdit::Remove(songs) => {
+                // Back to front, so the indices of the songs that still need to be removed
+                // stay valid.
+                for (index, id) in songs.iter().rev() {
+                    if self.songs.get(*index) != Some(id) {
+                        continue;
+                    }
+                    self.songs.remove(*index);
+
+                    match self.current_song_index {
+                        Some(current) if current == *index => {
+                            // Keep playing the song, and continue with the song after it.
+                            if self.detached_song.is_none() {
+                                self.detached_song = Some(*id);
+                            }
+                            self.current_song_index = index.checked_sub(1);
+                        }
+                        Some(current) if current > *index => {
+                            self.current_song_index = Some(current - 1);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            PlaylistEdit::Reorder(order) => {
+                if order.len() > self.songs.len() {
+                    return;
+                }
+
+                let reordered: Vec<SongId> = order.iter().map(|index| self.songs[*index]).collect();
+                self.songs.splice(..order.len(), reordered);
+
+                self.current_song_index = self.current_song_index.map(|current| {
+                    order
+                        .iter()
+                        .position(|index| *index == current)
+                        .unwrap_or(current)
+                });
+            }
+            PlaylistEdit::Batch(edits) => {
+                for edit in edits {
+                    self.apply(edit);
+                }
+            }
+        }
+    }
+
+    pub fn queued_songs(&self) -> vec_deque::Iter<'_, SongId> {
+        self.queue.iter()
+    }
+
+    /// Queues the songs to be played after the current song, and after any songs
+    /// that were queued before them.
+    pub fn queue_songs(&mut self, songs: &[SongId]) {
+        self.queue.extend(songs);
+    }
+
+    /// Queues the songs in front of any songs that were already queued.
+    /// So selecting the next song plays the first of them right away.
+    pub fn queue_songs_first(&mut self, songs: &[SongId]) {
+        for id in songs.iter().rev() {
+            self.queue.push_front(*id);
+        }
+    }
+
+    pub fn remove_queued_song_by_index(&mut self, index: usize) {
+        self.queue.remove(index);
+    }
+
+    pub fn clear_queue(&mut self) {
+        self.queue.clear();
+    }
+
+    pub fn is_radio_enabled(&self) -> bool {
+        self.radio.is_some()
+    }
+
+    pub fn set_radio_enabled(&mut self, enabled: bool) {
+        if enabled != self.is_radio_enabled() {
+            self.radio = enabled.then(Radio::new);
+        }
+    }
+
+    /// In radio mode, appends random songs until there are at least `settings.lookahead`
+    /// songs queued after the current song, and removes the oldest played songs.
+    /// Returns how many were removed, the indices of the other songs went down by that much.
+    /// Does nothing when not in radio mode.
+    pub fn top_up_radio(&mut self, library: &Library, settings: &RadioSettings) -> usize {
+        let removed = self.remove_radio_history();
+        if let Some(radio) = &mut self.radio {
+            let upcoming_songs = match self.current_song_index {
+                Some(index) => self.songs.len().saturating_sub(index + 1),
+                None => self.songs.len(),
+            };
+
+            for _ in upcoming_songs..settings.lookahead {
+                match radio.pick_song(library, settings) {
+                    Some(id) => self.songs.push(id),
+                    // Nothing to pick from, trying again won't help.
+                    None => break,
+                }
+            }
+        }
+        removed
+    }
+
+    /// Removes the played songs that are more than [`MAX_RADIO_HISTORY`] before the current
+    /// song. The edits that can be undone are kept, unless they touch those songs.
+    fn remove_radio_history(&mut self) -> usize {
+        let Some(current) = self.current_song_index else {
+            return 0;
+        };
+        if self.radio.is_none() || current <= MAX_RADIO_HISTORY {
+            return 0;
+        }
+        let count = current - MAX_RADIO_HISTORY;
+        self.songs.drain(..count);
+        self.current_song_index = Some(MAX_RADIO_HISTORY);
+
+        // Undo and redo from the newest edit back, older edits depend on the newer ones.
+        let mut undo_stack = VecDeque::new();
+        while let Some(undoable) = self.undo_stack.pop_back() {
+            let Some(edit) = undoable.edit.without_first(count) else {
+                self.undo_stack.clear();
+                break;
+            };
+            undo_stack.push_front(UndoableEdit {
+                edit,
+                current_song_index: undoable
+                    .current_song_index
+                    .and_then(|index| index.checked_sub(count)),
+                detached_song: undoable.detached_song,
+            });
+        }
+        self.undo_stack = undo_stack;
+
+        let mut redo_stack = Vec::new();
+        while let Some(edit) = self.redo_stack.pop() {
+            let Some(edit) = edit.without_first(count) else {
+                self.redo_stack.clear();
+                break;
+            };
+            redo_stack.insert(0, edit);
+        }
+        self.redo_stack = redo_stack;
+
+        count
+    }
+
+    pub fn is_shuffled(&self) -> bool {
+        self.original_order.is_some()
+    }
+
+    /// The current song stays selected.
+    pub fn shuffle(&mut self, library: &Library, mode: ShuffleMode) {
+        // When shuffling multiple times in a row, un-shuffling should still go back
+        // to the order from before the first shuffle.
+        if self.original_order.is_none() {
+            self.original_order = Some(self.songs.clone());
+        }
+
+        let order = shuffle::shuffled_order(&self.songs, library, mode);
+        self.edit(PlaylistEdit::Reorder(order));
+    }
+
+    /// Restores the order from before the playlist was shuffled.
+    /// Songs that were added after shuffling are placed at the end.
+    /// The current song stays selected.
+    pub fn unshuffle(&mut self) {
+        let Some(original_order) = self.original_order.take() else {
+            return;
+        };
+
+        // The same song can be in the playlist multiple times,
+        // so keep track of every index a song is at.
+        let mut indices_by_song: HashMap<SongId, VecDeque<usize>> = HashMap::new();
+        for (index, id) in self.songs.iter().enumerate() {
+            indices_by_song.entry(*id).or_default().push_back(index);
+        }
+
+        let mut order: Vec<usize> = original_order
+            .iter()
+            .filter_map(|id| {
+                indices_by_song
+                    .get_mut(id)
+                    .and_then(|indices| indices.pop_front())
+            })
+            .collect();
+
+        let mut added_later: Vec<usize> = indices_by_song.into_values().flatten().collect();
+        added_later.sort();
+        order.extend(added_later);
+
+        self.edit(PlaylistEdit::Reorder(order));
+    }
+
+    /// Queued songs are played first.
     pub fn select_next_song(&mut self, wrap: bool) -> Option<SongId> {
+        self.detached_song = self.queue.pop_front();
+        if self.detached_song.is_some() {
+            return self.detached_song;
+        }
+
         // TODO (2023-02-03): Refactor this set of if statements.
         self.current_song_index = if self.songs.is_empty() {
             None
@@ -57,6 +458,12 @@ impl Playlist {
     }
 
     pub fn select_previous_song(&mut self, wrap: bool) -> Option<SongId> {
+        // The song before a detached song is the song from the playlist that played
+        // before it.
+        if self.detached_song.take().is_some() && self.current_song_index.is_some() {
+            return self.current_song_id();
+        }
+
         // TODO (2023-02-03): Refactor this set of if statements.
         self.current_song_index = if self.songs.is_empty() {
             None
@@ -84,6 +491,7 @@ impl Playlist {
 
     pub fn select_song(&mut self, index: usize) -> Option<SongId> {
         if index <= self.songs.len() {
+            self.detached_song = None;
             self.current_song_index = Some(index);
             self.songs.get(index).cloned()
         } else {
@@ -91,61 +499,591 @@ impl Playlist {
         }
     }
 
-    pub fn switch_songs_by_index(&mut self, source_index: usize, target_index: usize) {
-        if source_index >= self.songs.len() || target_index >= self.songs.len() {
-            return;
-        }
+    /// Moves the songs at the given indices in front of the song that is at `target` before
+    /// moving. A `target` equal to the song count moves the songs to the end.
+    /// The moved songs end up next to each other, in the same order they were in before.
+    pub fn move_songs(&mut self, indices: &[usize], target: usize) {
+        let mut moved: Vec<usize> = indices
+            .iter()
+            .cloned()
+            .filter(|index| *index < self.songs.len())
+            .collect();
+        moved.sort();
+        moved.dedup();
 
-        self.songs.swap(source_index, target_index);
+        let target = target.min(self.songs.len());
+        let is_moved = |index: &usize| moved.binary_search(index).is_ok();
 
-        // Make sure to swap the currently playing song as well, if necessary.
-        if let Some(current_song) = self.current_song_index {
-            if current_song == source_index {
-                self.current_song_index = Some(target_index);
-            } else if current_song == target_index {
-                self.current_song_index = Some(source_index);
-            }
-        }
+        let mut order: Vec<usize> = (0..target).filter(|index| !is_moved(index)).collect();
+        order.extend(&moved);
+        order.extend((target..self.songs.len()).filter(|index| !is_moved(index)));
+
+        self.edit(PlaylistEdit::Reorder(order));
     }
 
-    /// Returns true if the song being removed is the currently selected song.
-    /// False otherwise.
-    pub fn remove_song_by_index(&mut self, index: usize) -> bool {
-        if index >= self.songs.len() {
-            return false;
-        }
+    /// When removing the current song, it keeps playing.
+    /// The playlist then continues with the song after it.
+    pub fn remove_songs_by_index(&mut self, indices: &[usize]) {
+        let mut indices = indices.to_vec();
+        indices.sort();
+        indices.dedup();
+
+        self.edit(PlaylistEdit::Remove(
+            indices
+                .into_iter()
+                .filter_map(|index| self.songs.get(index).map(|id| (index, *id)))
+                .collect(),
+        ));
+    }
 
-        self.songs.remove(index);
+    /// Songs that are not in the library are sorted last.
+    /// The current song stays selected.
+    pub fn sort(&mut self, library: &Library, key: SortKey) {
+        let mut order: Vec<usize> = (0..self.songs.len()).collect();
+        order.sort_by(|a, b| key.compare(library, self.songs[*a], self.songs[*b]));
 
-        let mut song_removed_is_current_song = false;
+        self.edit(PlaylistEdit::Reorder(order));
+    }
 
-        if let Some(current_song) = self.current_song_index {
-            if current_song == index {
-                song_removed_is_current_song = true;
+    pub fn reverse(&mut self) {
+        self.edit(PlaylistEdit::Reorder((0..self.songs.len()).rev().collect()));
+    }
 
-                // `>= song_count` instead of `>= song_count - 1` because the song has already
-                // been removed, so the list of songs is 1 shorter.
-                if index >= self.song_count() {
-                    // Keeping the index on the current value will auto-select the next song
-                    // in the list. But if there is no next song, we should make sure to communicate
-                    // this as such.
-                    self.current_song_index = None;
-                }
-            } else if current_song > index {
-                self.current_song_index = Some(current_song - 1);
-            }
+    /// Only the first time a song is in the playlist is kept.
+    pub fn remove_duplicates(&mut self) {
+        let mut seen = HashSet::new();
+        let duplicates: Vec<usize> = self
+            .songs
+            .iter()
+            .enumerate()
+            .filter(|(_, id)| !seen.insert(**id))
+            .map(|(index, _)| index)
+            .collect();
+
+        self.remove_songs_by_index(&duplicates);
+    }
+
+    /// Removes songs that are not in the library anymore, or of which the file was gone when
+    /// the library was last checked, see [`crate::missing_files`].
+    pub fn remove_missing_songs(&mut self, library: &Library) {
+        let missing: Vec<usize> = self
+            .songs
+            .iter()
+            .enumerate()
+            .filter(|(_, id)| library.get_song(**id).is_none() || library.is_missing(**id))
+            .map(|(index, _)| index)
+            .collect();
+
+        self.remove_songs_by_index(&missing);
+    }
+
+    /// Removes every song except the ones at the given indices.
+    pub fn crop(&mut self, indices: &[usize]) {
+        let kept: HashSet<&usize> = indices.iter().collect();
+        let removed: Vec<usize> = (0..self.songs.len())
+            .filter(|index| !kept.contains(index))
+            .collect();
+
+        self.remove_songs_by_index(&removed);
+    }
+
+    /// Removes the songs that came before the current song.
+    pub fn remove_songs_before_current(&mut self) {
+        if let Some(current) = self.current_song_index {
+            // When the current song is not part of the playlist, `current_song_index` points
+            // to the song that played before it. That song should also go.
+            let end = if self.detached_song.is_some() {
+                current + 1
+            } else {
+                current
+            };
+            self.remove_songs_by_index(&(0..end).collect::<Vec<_>>());
         }
+    }
+
+    /// Removes the songs that will play after the current song.
+    pub fn remove_songs_after_current(&mut self) {
+        let start = match self.current_song_index {
+            Some(current) => current + 1,
+            // A removed song is playing, and the playlist would continue from the start.
+            None if self.detached_song.is_some() => 0,
+            None => return,
+        };
 
-        return song_removed_is_current_song;
+        self.remove_songs_by_index(&(start..self.songs.len()).collect::<Vec<_>>());
     }
 
+    /// Returns `None` while a queued or removed song is playing,
+    /// because that song is not part of the playlist.
     pub fn current_song_index(&self) -> Option<usize> {
-        self.current_song_index
+        if self.detached_song.is_some() {
+            None
+        } else {
+            self.current_song_index
+        }
     }
 
     pub fn current_song_id(&self) -> Option<SongId> {
+        if self.detached_song.is_some() {
+            return self.detached_song;
+        }
+
         self.current_song_index
             .and_then(|index| self.songs.get(index))
             .cloned()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use camino::Utf8Path;
+    use slotmap::SlotMap;
+
+    fn song_ids(count: usize) -> Vec<SongId> {
+        let mut keys: SlotMap<SongId, ()> = SlotMap::with_key();
+        (0..count).map(|_| keys.insert(())).collect()
+    }
+
+    #[test]
+    fn test_radio_keeps_lookahead() {
+        let mut library = Library::new();
+        library.insert_from_directory(Utf8Path::new("../example_audio"));
+
+        let settings = RadioSettings {
+            lookahead: 3,
+            ..Default::default()
+        };
+
+        let mut playlist = Playlist::new();
+        playlist.top_up_radio(&library, &settings);
+        assert_eq!(playlist.song_count(), 0, "Radio mode is not enabled yet");
+
+        playlist.set_radio_enabled(true);
+        playlist.top_up_radio(&library, &settings);
+        assert_eq!(playlist.song_count(), 3);
+
+        playlist.select_next_song(false);
+        playlist.top_up_radio(&library, &settings);
+        assert_eq!(playlist.song_count(), 4);
+
+        // Already enough songs queued.
+        playlist.top_up_radio(&library, &settings);
+        assert_eq!(playlist.song_count(), 4);
+    }
+
+    #[test]
+    fn test_radio_removes_old_songs() {
+        let mut library = Library::new();
+        library.insert_from_directory(Utf8Path::new("../example_audio"));
+        let settings = RadioSettings {
+            lookahead: 3,
+            ..Default::default()
+        };
+        let ids = song_ids(MAX_RADIO_HISTORY + 3);
+
+        let mut playlist = Playlist::new();
+        playlist.append_songs(&ids[..MAX_RADIO_HISTORY + 2]);
+        playlist.append_songs(&ids[MAX_RADIO_HISTORY + 2..]);
+        playlist.select_song(MAX_RADIO_HISTORY + 2);
+        assert_eq!(playlist.top_up_radio(&library, &settings), 0);
+        assert_eq!(playlist.song_count(), MAX_RADIO_HISTORY + 3);
+
+        playlist.set_radio_enabled(true);
+        assert_eq!(playlist.top_up_radio(&library, &settings), 2);
+        assert_eq!(playlist.songs().next(), Some(&ids[2]));
+        assert_eq!(playlist.current_song_id(), Some(ids[MAX_RADIO_HISTORY + 2]));
+        assert_eq!(playlist.song_count(), MAX_RADIO_HISTORY + 4);
+
+        // The last append can still be undone, the first one added removed songs.
+        playlist.undo();
+        assert_eq!(playlist.song_count(), MAX_RADIO_HISTORY + 3);
+        assert!(!playlist.songs().any(|id| *id == ids[MAX_RADIO_HISTORY + 2]));
+        assert!(!playlist.can_undo());
+    }
+
+    #[test]
+    fn test_unshuffle() {
+        let library = Library::new();
+        let ids = song_ids(20);
+
+        let mut playlist = Playlist::new();
+        playlist.append_songs(&ids[..10]);
+        playlist.select_song(3);
+
+        playlist.shuffle(&library, ShuffleMode::Uniform);
+        assert!(playlist.is_shuffled());
+        assert_eq!(playlist.current_song_id(), Some(ids[3]));
+
+        playlist.shuffle(&library, ShuffleMode::SpreadArtists);
+        playlist.append_songs(&ids[10..]);
+        let removed_index = playlist.songs().position(|id| *id == ids[5]).unwrap();
+        playlist.remove_songs_by_index(&[removed_index]);
+
+        playlist.unshuffle();
+        assert!(!playlist.is_shuffled());
+
+        let expected: Vec<SongId> = ids.iter().cloned().filter(|id| *id != ids[5]).collect();
+        assert_eq!(playlist.songs().cloned().collect::<Vec<_>>(), expected);
+        assert_eq!(playlist.current_song_id(), Some(ids[3]));
+    }
+
+    #[test]
+    fn test_queue_plays_before_playlist_continues() {
+        let ids = song_ids(5);
+
+        let mut playlist = Playlist::new();
+        playlist.append_songs(&ids[..3]);
+
+        assert_eq!(playlist.select_next_song(false), Some(ids[0]));
+
+        playlist.queue_songs(&[ids[3]]);
+        playlist.queue_songs(&[ids[4]]);
+
+        assert_eq!(playlist.select_next_song(false), Some(ids[3]));
+        assert_eq!(playlist.current_song_id(), Some(ids[3]));
+        assert_eq!(playlist.current_song_index(), None);
+
+        assert_eq!(playlist.select_next_song(false), Some(ids[4]));
+        assert_eq!(playlist.select_next_song(false), Some(ids[1]));
+        assert_eq!(playlist.current_song_index(), Some(1));
+    }
+
+    #[test]
+    fn test_queue_previous_and_remove() {
+        let ids = song_ids(4);
+
+        let mut playlist = Playlist::new();
+        playlist.append_songs(&ids[..3]);
+        playlist.select_song(1);
+
+        playlist.queue_songs(&[ids[3]]);
+        assert_eq!(playlist.select_next_song(false), Some(ids[3]));
+
+        // Going back from a queued song goes to the song that played before the queue.
+        assert_eq!(playlist.select_previous_song(false), Some(ids[1]));
+
+        playlist.queue_songs(&[ids[3]]);
+        playlist.select_next_song(false);
+
+        // Removing the song the playlist would continue from doesn't stop the queued song.
+        playlist.remove_songs_by_index(&[1]);
+        assert_eq!(playlist.current_song_id(), Some(ids[3]));
+        assert_eq!(playlist.select_next_song(false), Some(ids[2]));
+    }
+
+    #[test]
+    fn test_queue_songs_first() {
+        let ids = song_ids(5);
+
+        let mut playlist = Playlist::new();
+        playlist.append_songs(&ids[..1]);
+        playlist.queue_songs(&[ids[1]]);
+        playlist.queue_songs_first(&[ids[2], ids[3]]);
+
+        let queued: Vec<SongId> = playlist.queued_songs().cloned().collect();
+        assert_eq!(queued, vec![ids[2], ids[3], ids[1]]);
+        assert_eq!(playlist.select_next_song(false), Some(ids[2]));
+    }
+
+    #[test]
+    fn test_replace_songs() {
+        let ids = song_ids(5);
+
+        let mut playlist = Playlist::new();
+        playlist.append_songs(&ids[..3]);
+        playlist.select_song(1);
+
+        playlist.replace_songs(&ids[3..]);
+        assert_eq!(songs(&playlist), ids[3..].to_vec());
+        assert_eq!(playlist.current_song_id(), Some(ids[1]));
+        assert_eq!(playlist.select_next_song(false), Some(ids[3]));
+
+        // Undoing once brings back the old songs.
+        playlist.undo();
+        assert_eq!(songs(&playlist), ids[..3].to_vec());
+        playlist.undo();
+        assert!(songs(&playlist).is_empty());
+    }
+
+    fn songs(playlist: &Playlist) -> Vec<SongId> {
+        playlist.songs().cloned().collect()
+    }
+
+    #[test]
+    fn test_edit_inverse() {
+        let ids = song_ids(2);
+
+        let insert = PlaylistEdit::Insert(vec![(0, ids[0]), (3, ids[1])]);
+        assert_eq!(
+            insert.inverse(),
+            PlaylistEdit::Remove(vec![(0, ids[0]), (3, ids[1])])
+        );
+        assert_eq!(insert.inverse().inverse(), insert);
+
+        let reorder = PlaylistEdit::Reorder(vec![2, 0, 3, 1]);
+        assert_eq!(reorder.inverse(), PlaylistEdit::Reorder(vec![1, 3, 0, 2]));
+        assert_eq!(reorder.inverse().inverse(), reorder);
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        // Some seeds shuffle the songs back into the same order, which can't be undone.
+        fastrand::seed(3);
+        let library = Library::new();
+        let ids = song_ids(6);
+
+        let mut playlist = Playlist::new();
+        assert!(!playlist.can_undo());
+
+        playlist.append_songs(&ids[..4]);
+        playlist.append_songs(&[ids[4]]);
+        playlist.move_songs(&[3], 0);
+        playlist.move_songs(&[1], 5);
+        playlist.remove_songs_by_index(&[4]);
+        playlist.shuffle(&library, ShuffleMode::Uniform);
+        playlist.clear();
+        assert!(songs(&playlist).is_empty());
+
+        // Every state the playlist went through, latest first.
+        let history = vec![
+            vec![ids[3], ids[1], ids[2], ids[4]],
+            vec![ids[3], ids[1], ids[2], ids[4], ids[0]],
+            vec![ids[3], ids[0], ids[1], ids[2], ids[4]],
+            vec![ids[0], ids[1], ids[2], ids[3], ids[4]],
+            vec![ids[0], ids[1], ids[2], ids[3]],
+            vec![],
+        ];
+
+        playlist.undo();
+        let shuffled = songs(&playlist);
+        let mut sorted_shuffled = shuffled.clone();
+        sorted_shuffled.sort();
+        let mut sorted_expected = history[0].clone();
+        sorted_expected.sort();
+        assert_eq!(sorted_shuffled, sorted_expected);
+
+        for expected in &history {
+            playlist.undo();
+            assert_eq!(&songs(&playlist), expected);
+        }
+        assert!(!playlist.can_undo());
+
+        // Undoing with nothing left to undo does nothing.
+        playlist.undo();
+        assert!(songs(&playlist).is_empty());
+
+        for expected in history.iter().rev().skip(1) {
+            playlist.redo();
+            assert_eq!(&songs(&playlist), expected);
+        }
+        playlist.redo();
+        assert_eq!(songs(&playlist), shuffled);
+        playlist.redo();
+        assert!(songs(&playlist).is_empty());
+        assert!(!playlist.can_redo());
+
+        // A new edit can't be redone past.
+        playlist.undo();
+        playlist.append_songs(&[ids[5]]);
+        assert!(!playlist.can_redo());
+        assert_eq!(songs(&playlist).last(), Some(&ids[5]));
+    }
+
+    #[test]
+    fn test_shuffle_into_same_order_is_not_an_edit() {
+        let library = Library::new();
+        let ids = song_ids(1);
+
+        // A single song can only be shuffled into the order it's in.
+        let mut playlist = Playlist::new();
+        playlist.append_songs(&[ids[0]]);
+        playlist.shuffle(&library, ShuffleMode::Uniform);
+        assert_eq!(songs(&playlist), vec![ids[0]]);
+
+        // Only the append is undone, and the shuffle doesn't take the redo away.
+        playlist.undo();
+        assert!(songs(&playlist).is_empty());
+        assert!(!playlist.can_undo());
+        playlist.shuffle(&library, ShuffleMode::Uniform);
+        assert!(playlist.can_redo());
+        playlist.redo();
+        assert_eq!(songs(&playlist), vec![ids[0]]);
+    }
+
+    #[test]
+    fn test_undo_keeps_current_song() {
+        let ids = song_ids(4);
+
+        let mut playlist = Playlist::new();
+        playlist.append_songs(&ids);
+        playlist.select_song(2);
+
+        // Removing the current song keeps it playing.
+        playlist.remove_songs_by_index(&[2]);
+        assert_eq!(playlist.current_song_id(), Some(ids[2]));
+        assert_eq!(playlist.current_song_index(), None);
+
+        // Undoing puts it back as the current song.
+        playlist.undo();
+        assert_eq!(playlist.current_song_index(), Some(2));
+        assert_eq!(playlist.current_song_id(), Some(ids[2]));
+
+        playlist.move_songs(&[2], 0);
+        assert_eq!(playlist.current_song_index(), Some(0));
+        playlist.undo();
+        assert_eq!(playlist.current_song_index(), Some(2));
+
+        // Clearing also keeps the song playing, and the playlist continues from
+        // the next song after undoing.
+        playlist.clear();
+        assert_eq!(playlist.current_song_id(), Some(ids[2]));
+        playlist.undo();
+        assert_eq!(playlist.current_song_index(), Some(2));
+        assert_eq!(playlist.select_next_song(false), Some(ids[3]));
+    }
+
+    #[test]
+    fn test_undo_limit() {
+        let ids = song_ids(1);
+
+        let mut playlist = Playlist::new();
+        for _ in 0..MAX_UNDO_EDITS + 10 {
+            playlist.append_songs(&[ids[0]]);
+        }
+        for _ in 0..MAX_UNDO_EDITS + 10 {
+            playlist.undo();
+        }
+        assert_eq!(playlist.song_count(), 10);
+    }
+
+    #[test]
+    fn test_move_songs() {
+        let ids = song_ids(6);
+
+        let mut playlist = Playlist::new();
+        playlist.append_songs(&ids);
+        playlist.select_song(3);
+
+        // Moving down, past the current song.
+        playlist.move_songs(&[0, 2], 5);
+        assert_eq!(
+            songs(&playlist),
+            vec![ids[1], ids[3], ids[4], ids[0], ids[2], ids[5]]
+        );
+        assert_eq!(playlist.current_song_index(), Some(1));
+        assert_eq!(playlist.current_song_id(), Some(ids[3]));
+
+        // Moving up, in a different order than the indices are given.
+        playlist.move_songs(&[5, 1, 3], 1);
+        assert_eq!(
+            songs(&playlist),
+            vec![ids[1], ids[3], ids[0], ids[5], ids[4], ids[2]]
+        );
+        assert_eq!(playlist.current_song_id(), Some(ids[3]));
+
+        // Moving to the end, and ignoring indices that don't exist.
+        playlist.move_songs(&[0, 100], 100);
+        assert_eq!(
+            songs(&playlist),
+            vec![ids[3], ids[0], ids[5], ids[4], ids[2], ids[1]]
+        );
+
+        // Moving onto itself changes nothing, so there is nothing to undo.
+        let edits = playlist.undo_stack.len();
+        playlist.move_songs(&[2, 3], 3);
+        playlist.move_songs(&[2, 3], 2);
+        assert_eq!(playlist.undo_stack.len(), edits);
+    }
+
+    #[test]
+    fn test_remove_songs() {
+        let ids = song_ids(5);
+
+        let mut playlist = Playlist::new();
+        playlist.append_songs(&ids);
+        playlist.select_song(3);
+
+        playlist.remove_songs_by_index(&[4, 0, 2, 0]);
+        assert_eq!(songs(&playlist), vec![ids[1], ids[3]]);
+        assert_eq!(playlist.current_song_index(), Some(1));
+
+        playlist.undo();
+        assert_eq!(songs(&playlist), ids);
+        assert_eq!(playlist.current_song_index(), Some(3));
+    }
+
+    #[test]
+    fn test_sort_and_reverse() {
+        let mut library = Library::new();
+        library.insert_from_directory(Utf8Path::new("../example_audio"));
+        let (blank_holes, dark_mystery) = {
+            let mut ids: Vec<(SongId, &str)> = library
+                .songs()
+                .map(|(id, song)| (id, song.title.as_str()))
+                .collect();
+            ids.sort_by_key(|(_, title)| *title);
+            (ids[0].0, ids[1].0)
+        };
+
+        let mut playlist = Playlist::new();
+        playlist.append_songs(&[dark_mystery, blank_holes, dark_mystery]);
+        playlist.select_song(1);
+
+        playlist.sort(&library, SortKey::Title);
+        assert_eq!(
+            songs(&playlist),
+            vec![blank_holes, dark_mystery, dark_mystery]
+        );
+        assert_eq!(playlist.current_song_index(), Some(0));
+
+        // The shorter song first.
+        playlist.sort(&library, SortKey::Duration);
+        assert_eq!(
+            songs(&playlist),
+            vec![dark_mystery, dark_mystery, blank_holes]
+        );
+        assert_eq!(playlist.current_song_index(), Some(2));
+
+        playlist.reverse();
+        assert_eq!(
+            songs(&playlist),
+            vec![blank_holes, dark_mystery, dark_mystery]
+        );
+
+        playlist.remove_duplicates();
+        assert_eq!(songs(&playlist), vec![blank_holes, dark_mystery]);
+
+        // Songs that are not in the library are missing.
+        // The library only has two songs, so the third id is unknown.
+        let unknown_song = song_ids(3)[2];
+        playlist.append_songs(&[unknown_song]);
+        playlist.remove_missing_songs(&library);
+        assert_eq!(songs(&playlist), vec![blank_holes, dark_mystery]);
+
+        library.set_missing(dark_mystery, true);
+        playlist.remove_missing_songs(&library);
+        assert_eq!(songs(&playlist), vec![blank_holes]);
+    }
+
+    #[test]
+    fn test_crop_and_remove_around_current() {
+        let ids = song_ids(6);
+
+        let mut playlist = Playlist::new();
+        playlist.append_songs(&ids);
+        playlist.select_song(2);
+
+        playlist.crop(&[1, 2, 3, 4]);
+        assert_eq!(songs(&playlist), ids[1..5].to_vec());
+        assert_eq!(playlist.current_song_id(), Some(ids[2]));
+
+        playlist.remove_songs_before_current();
+        assert_eq!(songs(&playlist), ids[2..5].to_vec());
+        assert_eq!(playlist.current_song_index(), Some(0));
+
+        playlist.remove_songs_after_current();
+        assert_eq!(songs(&playlist), vec![ids[2]]);
+        assert_eq!(playlist.current_song_index(), Some(0));
+    }
+}
@@ -0,0 +1,370 @@
+use crate::labels::{show_label_chip, show_labels_menu, LabelChange};
+use crate::library::{Library, SongId, SortKey};
+use crate::playlist::Playlist;
+use crate::rating::{show_rating, RatingChange};
+use crate::selection::Selection;
+use eframe::egui;
+use eframe::egui::{Color32, CursorIcon, Id, Key, Modifiers, RichText, Sense, Stroke, Ui, Widget};
+
+pub struct PlaylistView {
+    /// Indices of the selected songs in the playlist.
+    selection: Selection,
+    /// Records whether the user is currently dragging the selected songs.
+    dragging: bool,
+    /// Where the dragged songs will be inserted when they are dropped.
+    /// They are inserted in front of the song currently at this index.
+    drop_target: Option<usize>,
+}
+
+impl PlaylistView {
+    pub fn new() -> Self {
+        PlaylistView {
+            selection: Selection::new(),
+            dragging: false,
+            drop_target: None,
+        }
+    }
+
+    /// Should be called when the songs in the playlist change in a way the view doesn't know
+    /// about, because the selected indices might not point to the same songs anymore.
+    pub fn clear_selection(&mut self) {
+        self.selection.clear();
+    }
+
+    #[must_use]
+    pub fn show(
+        &mut self,
+        ui: &mut Ui,
+        playlist: &Playlist,
+        library: &Library,
+    ) -> PlaylistViewCommand {
+        let mut command = self.show_queue(ui, playlist, library);
+
+        let current_song = playlist.current_song_index();
+        let song_count = playlist.song_count();
+
+        // Songs can be removed outside of the view. Forget about those.
+        self.selection.retain_below(song_count);
+
+        if self.dragging && !ui.memory().is_anything_being_dragged() {
+            // The songs were dropped.
+            self.dragging = false;
+
+            if let Some(target) = self.drop_target.take() {
+                command = self.move_selected_songs(target);
+            }
+        }
+
+        if self.dragging {
+            ui.output().cursor_icon = CursorIcon::Grabbing;
+        }
+
+        // Only handle shortcuts when the user isn't typing somewhere.
+        if ui.memory().focus().is_none() {
+            if ui.input().key_pressed(Key::Delete) && !self.selection.is_empty() {
+                command =
+                    PlaylistViewCommand::RemoveSongs(self.selection.indices().cloned().collect());
+                self.clear_selection();
+            } else if ui.input().key_pressed(Key::A)
+                && ui.input().modifiers.matches(Modifiers::COMMAND)
+            {
+                self.selection.select_all(song_count);
+            }
+        }
+
+        let button_height = ui.spacing().interact_size.y;
+        let pointer_pos = ui.input().pointer.hover_pos();
+
+        let scroll_output = egui::ScrollArea::both()
+            .auto_shrink([false, false])
+            .show_rows(ui, button_height, song_count, |ui, row_range| {
+                for (index, id) in playlist
+                    .songs()
+                    .enumerate()
+                    .skip(row_range.start)
+                    .take(row_range.len())
+                {
+                    // Songs removed from the library still get a row, so they can be
+                    // removed from the playlist as well, and the rows below don't shift.
+                    let song = library.get_song(*id);
+                    let data = library.get_song_data(*id);
+                    let row_response = ui.horizontal(|ui| {
+                        let drag_id = Id::new("playlist_drag").with(index);
+
+                        let drag_rect = ui.label("::").rect;
+                        let drag_response = ui.interact(drag_rect, drag_id, Sense::drag());
+
+                        if drag_response.drag_started() {
+                            // Dragging a song that isn't selected only drags that song.
+                            if !self.selection.contains(index) {
+                                self.selection.select_only(index);
+                            }
+                            self.dragging = true;
+                            self.drop_target = None;
+                        } else if drag_response.hovered()
+                            && !ui.memory().is_anything_being_dragged()
+                        {
+                            ui.output().cursor_icon = CursorIcon::Grab;
+                        }
+
+                        if ui.button("X").clicked() {
+                            command = PlaylistViewCommand::RemoveSongs(vec![index]);
+                            self.clear_selection();
+                        }
+
+                        if song.is_some() {
+                            let rating_response = ui.horizontal(|ui| show_rating(ui, data));
+                            if let Some(change) = rating_response.inner {
+                                command = PlaylistViewCommand::ChangeRating(*id, change);
+                            }
+                        }
+
+                        let mut title_text = match song {
+                            Some(song) => RichText::new(&song.title),
+                            None => RichText::new("Removed from the library").italics().weak(),
+                        };
+
+                        if current_song == Some(index) {
+                            title_text = title_text.color(Color32::LIGHT_GREEN);
+                        } else if self.dragging && self.selection.contains(index) {
+                            title_text = title_text.color(Color32::LIGHT_BLUE);
+                        }
+                        let missing = song.is_none() || library.is_missing(*id);
+                        if missing {
+                            title_text = title_text.strikethrough();
+                        }
+
+                        let title_response =
+                            egui::SelectableLabel::new(self.selection.contains(index), title_text)
+                                .ui(ui);
+
+                        // A plain click plays the song.
+                        if self.selection.handle_row_clicks(&title_response, index) {
+                            command = PlaylistViewCommand::PlaySong(index);
+                        }
+                        title_response.context_menu(|ui| {
+                            if let Some(menu_command) =
+                                self.show_context_menu(ui, playlist, library)
+                            {
+                                command = menu_command;
+                                ui.close_menu();
+                            }
+                        });
+
+                        if let Some(song) = song.filter(|_| missing) {
+                            ui.colored_label(Color32::LIGHT_RED, "missing")
+                                .on_hover_text(format!("The file is gone: {}", song.path));
+                        }
+                        for label in &data.labels {
+                            show_label_chip(ui, label, false);
+                        }
+                    });
+
+                    if self.dragging {
+                        let rect = row_response.response.rect;
+
+                        if let Some(pos) = pointer_pos {
+                            if pos.y >= rect.top() && pos.y < rect.bottom() {
+                                self.drop_target = if pos.y < rect.center().y {
+                                    Some(index)
+                                } else {
+                                    Some(index + 1)
+                                };
+                            }
+                        }
+
+                        // Draw a line where the songs will be inserted.
+                        let line_y = if self.drop_target == Some(index) {
+                            Some(rect.top())
+                        } else if self.drop_target == Some(index + 1) {
+                            Some(rect.bottom())
+                        } else {
+                            None
+                        };
+                        if let Some(y) = line_y {
+                            ui.painter().hline(
+                                rect.x_range(),
+                                y,
+                                Stroke::new(2.0f32, Color32::LIGHT_BLUE),
+                            );
+                        }
+                    }
+                }
+            });
+
+        // Dropping the songs anywhere outside of the list doesn't move them.
+        if self.dragging && !pointer_pos.is_some_and(|pos| scroll_output.inner_rect.contains(pos)) {
+            self.drop_target = None;
+        }
+
+        command
+    }
+
+    /// Returns the command for the clicked menu item, if any.
+    fn show_context_menu(
+        &mut self,
+        ui: &mut Ui,
+        playlist: &Playlist,
+        library: &Library,
+    ) -> Option<PlaylistViewCommand> {
+        let selected: Vec<usize> = self.selection.indices().cloned().collect();
+
+        // Changing labels or tags leaves the songs where they are, so the selection can stay.
+        let selected_songs: Vec<SongId> = playlist
+            .songs()
+            .enumerate()
+            .filter(|(index, _)| self.selection.contains(*index))
+            .map(|(_, id)| *id)
+            .collect();
+        if let Some(change) = show_labels_menu(ui, library, &selected_songs) {
+            return Some(PlaylistViewCommand::ChangeLabels(selected_songs, change));
+        }
+        if ui.button("Edit tags").clicked() {
+            return Some(PlaylistViewCommand::EditTags(selected_songs));
+        }
+        ui.separator();
+
+        let mut command = None;
+
+        if ui.button("Remove selected").clicked() {
+            command = Some(PlaylistViewCommand::RemoveSongs(selected.clone()));
+        }
+        if ui.button("Crop to selected").clicked() {
+            command = Some(PlaylistViewCommand::Crop(selected));
+        }
+        if ui.button("Remove songs before current").clicked() {
+            command = Some(PlaylistViewCommand::RemoveSongsBeforeCurrent);
+        }
+        if ui.button("Remove songs after current").clicked() {
+            command = Some(PlaylistViewCommand::RemoveSongsAfterCurrent);
+        }
+
+        ui.separator();
+
+        ui.menu_button("Sort by", |ui| {
+            for key in SortKey::ALL {
+                if ui.button(key.name()).clicked() {
+                    command = Some(PlaylistViewCommand::Sort(key));
+                }
+            }
+        });
+        if ui.button("Reverse").clicked() {
+            command = Some(PlaylistViewCommand::Reverse);
+        }
+        if ui.button("Remove duplicates").clicked() {
+            command = Some(PlaylistViewCommand::RemoveDuplicates);
+        }
+        if ui.button("Remove missing files").clicked() {
+            command = Some(PlaylistViewCommand::RemoveMissingSongs);
+        }
+
+        if command.is_some() {
+            // The indices won't point to the same songs anymore.
+            self.clear_selection();
+        }
+
+        command
+    }
+
+    /// The selection moves along with the songs.
+    fn move_selected_songs(&mut self, target: usize) -> PlaylistViewCommand {
+        let indices: Vec<usize> = self.selection.indices().cloned().collect();
+        // The selection is cleared when songs are removed while dragging.
+        if indices.is_empty() {
+            return PlaylistViewCommand::None;
+        }
+
+        let new_start = target - indices.iter().filter(|index| **index < target).count();
+        self.selection
+            .select_range(new_start..new_start + indices.len());
+
+        PlaylistViewCommand::MoveSongs { indices, target }
+    }
+
+    /// Shows the songs that will play before the playlist continues.
+    fn show_queue(
+        &self,
+        ui: &mut Ui,
+        playlist: &Playlist,
+        library: &Library,
+    ) -> PlaylistViewCommand {
+        let mut command = PlaylistViewCommand::None;
+
+        if playlist.queued_songs().len() == 0 {
+            return command;
+        }
+
+        ui.horizontal(|ui| {
+            ui.label(format!("Up next: {}", playlist.queued_songs().len()));
+            if ui.button("Clear").clicked() {
+                command = PlaylistViewCommand::ClearQueue;
+            }
+        });
+
+        for (index, id) in playlist.queued_songs().enumerate() {
+            if let Some(song) = library.get_song(*id) {
+                ui.horizontal(|ui| {
+                    if ui.button("X").clicked() {
+                        command = PlaylistViewCommand::RemoveQueuedSong(index);
+                    }
+                    egui::Label::new(&song.title).wrap(false).ui(ui);
+                });
+            }
+        }
+
+        ui.separator();
+
+        command
+    }
+}
+
+pub enum PlaylistViewCommand {
+    None,
+    PlaySong(usize),
+    RemoveSongs(Vec<usize>),
+    /// Moves the songs at `indices` in front of the song at `target`.
+    /// See [`Playlist::move_songs`].
+    MoveSongs {
+        indices: Vec<usize>,
+        target: usize,
+    },
+    /// Removes every song except the ones at the given indices.
+    Crop(Vec<usize>),
+    RemoveSongsBeforeCurrent,
+    RemoveSongsAfterCurrent,
+    Sort(SortKey),
+    Reverse,
+    RemoveDuplicates,
+    RemoveMissingSongs,
+    RemoveQueuedSong(usize),
+    ClearQueue,
+    ChangeRating(SongId, RatingChange),
+    ChangeLabels(Vec<SongId>, LabelChange),
+    EditTags(Vec<SongId>),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_move_selected_songs() {
+        let mut view = PlaylistView::new();
+        view.selection.select_range(1..3);
+        let command = view.move_selected_songs(5);
+        assert!(matches!(
+            command,
+            PlaylistViewCommand::MoveSongs { indices, target: 5 } if indices == vec![1, 2]
+        ));
+        assert_eq!(
+            view.selection.indices().cloned().collect::<Vec<_>>(),
+            vec![3, 4]
+        );
+
+        // Removing the songs while dragging them leaves nothing to drop.
+        view.clear_selection();
+        let command = view.move_selected_songs(0);
+        assert!(matches!(command, PlaylistViewCommand::None));
+        assert!(view.selection.is_empty());
+    }
+}
@@ -0,0 +1,838 @@
+//! The query language of the library search.
+//!
+//! ```text
+//! artist:radiohead year:>=2000 genre:"post rock" -live dur:<5m
+//! ```
+//!
+//! - Plain words are fuzzy matched against the title, see [`fuzzy::fuzzy_match`].
+//! - Everything is normalized before comparing, so "bjork" finds "Björk", see [`normalize`].
+//! - `field:value` only matches songs where the field contains the value.
+//!   `field:=value` only matches songs where the field is exactly the value.
+//! - Numeric fields can be compared: `year:>=2000`, `track:<3`, `dur:<5m`.
+//!   Play stats too: `plays:>10`, `skips:0`, `listened:>1h`, and `lastplayed:>30`
+//!   for songs that weren't played in the last 30 days (or never).
+//!   Ratings as well: `rating:>=4`, and `fav:1` for favourites.
+//! - `label:focus` matches songs with a label containing "focus".
+//! - `"quoted phrases"` are matched as a whole, including spaces.
+//! - `-` in front of a term excludes the songs that match it.
+//!   Like in fzf, excluded words are matched exactly instead of fuzzily.
+//! - All terms have to match, unless they are separated by `OR` or `|`.
+//! - Terms can be grouped with parentheses: `(genre:ambient OR genre:cinematic) -dark`.
+
+use crate::fuzzy;
+use crate::fuzzy::FuzzyMatch;
+use crate::library::Song;
+use crate::normalize::normalize;
+use crate::song_data::SongData;
+use std::fmt;
+use std::time::SystemTime;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    /// All queries have to match. Matches everything when empty.
+    And(Vec<Query>),
+    /// At least one of the queries has to match.
+    Or(Vec<Query>),
+    Not(Box<Query>),
+    /// Fuzzy matched against the title. Normalized.
+    Fuzzy(String),
+    Text {
+        field: TextField,
+        comparison: TextComparison,
+        /// Normalized, because the field is normalized before comparing.
+        text: String,
+    },
+    Number {
+        field: NumberField,
+        comparison: NumberComparison,
+        /// Durations are in seconds.
+        value: u64,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextField {
+    Title,
+    Artist,
+    Album,
+    Genre,
+    Path,
+    /// A song can have many labels, it matches when one of them does.
+    Label,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumberField {
+    Year,
+    Track,
+    Disc,
+    Duration,
+    Plays,
+    Skips,
+    /// Days since the song was last played.
+    LastPlayed,
+    /// Total time the song was listened to.
+    Listened,
+    /// 0 for songs without a rating.
+    Rating,
+    /// 1 for favourites, 0 for the rest.
+    Favourite,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextComparison {
+    Contains,
+    Equals,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumberComparison {
+    Less,
+    LessOrEqual,
+    Equal,
+    GreaterOrEqual,
+    Greater,
+}
+
+enum Field {
+    Text(TextField),
+    Number(NumberField),
+}
+
+/// The names that can be used in `field:value`, with the field they refer to.
+const FIELDS: [(&str, Field); 18] = [
+    ("title", Field::Text(TextField::Title)),
+    ("artist", Field::Text(TextField::Artist)),
+    ("album", Field::Text(TextField::Album)),
+    ("genre", Field::Text(TextField::Genre)),
+    ("path", Field::Text(TextField::Path)),
+    ("label", Field::Text(TextField::Label)),
+    ("year", Field::Number(NumberField::Year)),
+    ("track", Field::Number(NumberField::Track)),
+    ("disc", Field::Number(NumberField::Disc)),
+    ("dur", Field::Number(NumberField::Duration)),
+    ("duration", Field::Number(NumberField::Duration)),
+    ("plays", Field::Number(NumberField::Plays)),
+    ("skips", Field::Number(NumberField::Skips)),
+    ("lastplayed", Field::Number(NumberField::LastPlayed)),
+    ("listened", Field::Number(NumberField::Listened)),
+    ("rating", Field::Number(NumberField::Rating)),
+    ("fav", Field::Number(NumberField::Favourite)),
+    ("favourite", Field::Number(NumberField::Favourite)),
+];
+
+impl Query {
+    /// Returns `None` if the song doesn't match the query.
+    /// Otherwise returns how well the title matches the fuzzy words in the query,
+    /// to rank and highlight the search results.
+    pub fn evaluate(&self, song: &Song, data: &SongData) -> Option<FuzzyMatch> {
+        match self {
+            Query::And(queries) => {
+                let mut result = FuzzyMatch::default();
+                for query in queries {
+                    let found = query.evaluate(song, data)?;
+                    result.score += found.score;
+                    result.positions.extend(found.positions);
+                }
+                result.positions.sort();
+                result.positions.dedup();
+                Some(result)
+            }
+            Query::Or(queries) => queries
+                .iter()
+                .filter_map(|query| query.evaluate(song, data))
+                .max_by_key(|found| found.score),
+            Query::Not(query) => match query.evaluate(song, data) {
+                Some(_) => None,
+                None => Some(FuzzyMatch::default()),
+            },
+            Query::Fuzzy(word) => fuzzy::fuzzy_match(word, &song.title),
+            Query::Text {
+                field,
+                comparison,
+                text,
+            } => {
+                let matches = |value: &str| {
+                    let value = normalize(value);
+                    match comparison {
+                        TextComparison::Contains => value.contains(text.as_str()),
+                        TextComparison::Equals => value == *text,
+                    }
+                };
+                let found = match field {
+                    TextField::Label => data.labels.iter().any(|label| matches(label)),
+                    _ => matches(field.value(song)?),
+                };
+                found.then(FuzzyMatch::default)
+            }
+            Query::Number {
+                field,
+                comparison,
+                value,
+            } => {
+                let song_value = field.value(song, data)?;
+                let matches = match comparison {
+                    NumberComparison::Less => song_value < *value,
+                    NumberComparison::LessOrEqual => song_value <= *value,
+                    NumberComparison::Equal => song_value == *value,
+                    NumberComparison::GreaterOrEqual => song_value >= *value,
+                    NumberComparison::Greater => song_value > *value,
+                };
+                matches.then(FuzzyMatch::default)
+            }
+        }
+    }
+}
+
+impl Query {
+    /// Whether the query looks at the data of songs, like play stats, and not only at
+    /// their tags. Then its results change when the data does.
+    pub fn uses_song_data(&self) -> bool {
+        match self {
+            Query::And(queries) | Query::Or(queries) => queries.iter().any(Query::uses_song_data),
+            Query::Not(query) => query.uses_song_data(),
+            Query::Fuzzy(_) => false,
+            Query::Text { field, .. } => *field == TextField::Label,
+            Query::Number { field, .. } => field.uses_song_data(),
+        }
+    }
+
+    /// Whether every song that matches this query, also matches the `other` query.
+    /// Only recognizes the simple cases, like typing more characters of a word or adding
+    /// another filter. So it can return `false` even when it's true.
+    pub fn is_narrower_than(&self, other: &Query) -> bool {
+        other
+            .and_terms()
+            .iter()
+            .all(|other_term| self.and_terms().iter().any(|term| term.implies(other_term)))
+    }
+
+    fn and_terms(&self) -> &[Query] {
+        match self {
+            Query::And(queries) => queries,
+            query => std::slice::from_ref(query),
+        }
+    }
+
+    fn implies(&self, other: &Query) -> bool {
+        match (self, other) {
+            // Anything containing "dark" as a subsequence, contains "dk" as well.
+            (Query::Fuzzy(word), Query::Fuzzy(other_word)) => {
+                let mut chars = word.chars();
+                other_word.chars().all(|c| chars.any(|other| other == c))
+            }
+            (
+                Query::Text { field, text, .. },
+                Query::Text {
+                    field: other_field,
+                    comparison: TextComparison::Contains,
+                    text: other_text,
+                },
+            ) => field == other_field && text.contains(other_text.as_str()),
+            _ => self == other,
+        }
+    }
+}
+
+impl TextField {
+    pub fn value<'a>(&self, song: &'a Song) -> Option<&'a str> {
+        match self {
+            TextField::Title => Some(&song.title),
+            TextField::Artist => song.artist.as_deref(),
+            TextField::Album => song.album.as_deref(),
+            TextField::Genre => song.genre.as_deref(),
+            TextField::Path => Some(song.path.as_str()),
+            // There can be more than one, those are in the song's data.
+            TextField::Label => None,
+        }
+    }
+}
+
+impl NumberField {
+    fn uses_song_data(&self) -> bool {
+        !matches!(
+            self,
+            NumberField::Year | NumberField::Track | NumberField::Disc | NumberField::Duration
+        )
+    }
+
+    fn value(&self, song: &Song, data: &SongData) -> Option<u64> {
+        match self {
+            NumberField::Year => song.year.map(u64::from),
+            NumberField::Track => song.track_number.map(u64::from),
+            NumberField::Disc => song.disc_number.map(u64::from),
+            NumberField::Duration => song.duration.map(|duration| duration.as_secs()),
+            NumberField::Plays => Some(u64::from(data.stats.play_count)),
+            NumberField::Skips => Some(u64::from(data.stats.skip_count)),
+            NumberField::LastPlayed => Some(data.stats.days_since_last_played(SystemTime::now())),
+            NumberField::Listened => Some(data.stats.listening_time.as_secs()),
+            NumberField::Rating => Some(u64::from(data.rating)),
+            NumberField::Favourite => Some(u64::from(data.favourite)),
+        }
+    }
+}
+
+/// Explains what is wrong with the query, in a way that can be shown to the user.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseError(String);
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+pub fn parse(input: &str) -> Result<Query, ParseError> {
+    let mut parser = Parser {
+        chars: input.chars().collect(),
+        position: 0,
+        depth: 0,
+    };
+
+    parser.skip_whitespace();
+    if parser.is_at_end() {
+        return Ok(Query::And(Vec::new()));
+    }
+
+    let query = parser.parse_or()?;
+
+    // The only thing `parse_or` stops at before the end, is a closing parenthesis.
+    if !parser.is_at_end() {
+        return Err(error("There is a \")\" without a matching \"(\"."));
+    }
+
+    Ok(query)
+}
+
+fn error(message: impl Into<String>) -> ParseError {
+    ParseError(message.into())
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+    /// How many parentheses are open.
+    depth: usize,
+}
+
+impl Parser {
+    /// `a b OR c d` means `(a b) OR (c d)`.
+    fn parse_or(&mut self) -> Result<Query, ParseError> {
+        let mut alternatives = vec![self.parse_and()?];
+
+        while self.eat_or() {
+            self.skip_whitespace();
+            if self.is_at_end() || self.peek() == Some(')') {
+                return Err(error("Expected something to search for after \"OR\"."));
+            }
+            alternatives.push(self.parse_and()?);
+        }
+
+        Ok(if alternatives.len() == 1 {
+            alternatives.remove(0)
+        } else {
+            Query::Or(alternatives)
+        })
+    }
+
+    fn parse_and(&mut self) -> Result<Query, ParseError> {
+        let mut terms = Vec::new();
+
+        loop {
+            self.skip_whitespace();
+            if self.is_at_end() || self.peek() == Some(')') || self.is_at_or() {
+                break;
+            }
+            terms.push(self.parse_unary(false)?);
+        }
+
+        match terms.len() {
+            0 if self.is_at_or() => Err(error("Expected something to search for before \"OR\".")),
+            0 if self.depth == 0 => Err(error("There is a \")\" without a matching \"(\".")),
+            0 => Err(error(
+                "There is nothing to search for between \"(\" and \")\".",
+            )),
+            1 => Ok(terms.remove(0)),
+            _ => Ok(Query::And(terms)),
+        }
+    }
+
+    fn parse_unary(&mut self, negated: bool) -> Result<Query, ParseError> {
+        if self.eat('-') {
+            if self.is_at_end() || self.peek().is_some_and(char::is_whitespace) {
+                return Err(error("Expected something to exclude after \"-\"."));
+            }
+            return Ok(Query::Not(Box::new(self.parse_unary(true)?)));
+        }
+
+        if self.eat('(') {
+            self.depth += 1;
+            let query = self.parse_or()?;
+            if !self.eat(')') {
+                return Err(error("There is a \"(\" without a matching \")\"."));
+            }
+            self.depth -= 1;
+            return Ok(query);
+        }
+
+        self.parse_term(negated)
+    }
+
+    fn parse_term(&mut self, negated: bool) -> Result<Query, ParseError> {
+        if self.peek() == Some('"') {
+            return Ok(Query::Text {
+                field: TextField::Title,
+                comparison: TextComparison::Contains,
+                text: normalize_term(&self.parse_quoted()?),
+            });
+        }
+
+        let word = self.take_while(|c| !c.is_whitespace() && !"()\":".contains(c));
+
+        if self.eat(':') {
+            if word.is_empty() {
+                return Err(error(
+                    "Expected a field name before \":\", like artist:name.",
+                ));
+            }
+            return self.parse_filter(&word);
+        }
+
+        if word.is_empty() {
+            // Only a quote can follow, which is handled above.
+            return Err(error(format!(
+                "Unexpected \"{}\".",
+                self.peek().unwrap_or_default()
+            )));
+        }
+
+        Ok(if negated {
+            Query::Text {
+                field: TextField::Title,
+                comparison: TextComparison::Contains,
+                text: normalize_term(&word),
+            }
+        } else {
+            Query::Fuzzy(normalize_term(&word))
+        })
+    }
+
+    /// Parses what comes after `field:`.
+    fn parse_filter(&mut self, name: &str) -> Result<Query, ParseError> {
+        let lowercase_name = name.to_lowercase();
+        let Some((_, field)) = FIELDS.iter().find(|(field, _)| *field == lowercase_name) else {
+            return Err(unknown_field_error(name));
+        };
+
+        let comparison = self.parse_comparison();
+
+        let value = if self.peek() == Some('"') {
+            self.parse_quoted()?
+        } else {
+            self.take_while(|c| !c.is_whitespace() && c != ')' && c != '(')
+        };
+
+        if value.is_empty() {
+            return Err(error(format!(
+                "Expected a value after \"{}:\", like {}:{}.",
+                name,
+                name,
+                field.example()
+            )));
+        }
+
+        match field {
+            Field::Text(field) => {
+                let comparison = match comparison {
+                    None => TextComparison::Contains,
+                    Some(NumberComparison::Equal) => TextComparison::Equals,
+                    Some(_) => {
+                        return Err(error(format!(
+                        "\"{}\" can't be compared with < or >, only numbers like year and dur can.",
+                        name
+                    )))
+                    }
+                };
+                Ok(Query::Text {
+                    field: *field,
+                    comparison,
+                    text: normalize_term(&value),
+                })
+            }
+            Field::Number(field) => {
+                let value = match field {
+                    NumberField::Duration | NumberField::Listened => parse_duration(&value)
+                        .ok_or_else(|| {
+                            error(format!(
+                                "\"{}\" is not a duration. Try something like 3:30, 5m or 90s.",
+                                value
+                            ))
+                        })?,
+                    _ => value.parse().map_err(|_| {
+                        error(format!(
+                            "\"{}\" is not a number. Try something like {}:{}.",
+                            value,
+                            name,
+                            Field::Number(*field).example()
+                        ))
+                    })?,
+                };
+                Ok(Query::Number {
+                    field: *field,
+                    comparison: comparison.unwrap_or(NumberComparison::Equal),
+                    value,
+                })
+            }
+        }
+    }
+
+    fn parse_comparison(&mut self) -> Option<NumberComparison> {
+        if self.eat('<') {
+            Some(if self.eat('=') {
+                NumberComparison::LessOrEqual
+            } else {
+                NumberComparison::Less
+            })
+        } else if self.eat('>') {
+            Some(if self.eat('=') {
+                NumberComparison::GreaterOrEqual
+            } else {
+                NumberComparison::Greater
+            })
+        } else if self.eat('=') {
+            Some(NumberComparison::Equal)
+        } else {
+            None
+        }
+    }
+
+    /// Parses a phrase in double quotes, and returns the text in between.
+    fn parse_quoted(&mut self) -> Result<String, ParseError> {
+        self.eat('"');
+        let text = self.take_while(|c| c != '"');
+        if !self.eat('"') {
+            return Err(error(format!(
+                "The quote before \"{}\" is not closed.",
+                text
+            )));
+        }
+        Ok(text)
+    }
+
+    /// `OR` only counts as a separate word, so words like "ORBIT" can still be searched for.
+    fn is_at_or(&self) -> bool {
+        self.peek() == Some('|')
+            || (self.chars[self.position..].starts_with(&['O', 'R'])
+                && self
+                    .chars
+                    .get(self.position + 2)
+                    .is_none_or(|c| c.is_whitespace() || *c == '('))
+    }
+
+    fn eat_or(&mut self) -> bool {
+        self.skip_whitespace();
+        if self.eat('|') {
+            true
+        } else if self.is_at_or() {
+            self.position += 2;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).cloned()
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.position >= self.chars.len()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> String {
+        let start = self.position;
+        while self.peek().is_some_and(&predicate) {
+            self.position += 1;
+        }
+        self.chars[start..self.position].iter().collect()
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.take_while(char::is_whitespace);
+    }
+}
+
+impl Field {
+    fn example(&self) -> &'static str {
+        match self {
+            Field::Text(_) => "\"some text\"",
+            Field::Number(NumberField::Year) => ">=2000",
+            Field::Number(NumberField::Duration) => "<5m",
+            Field::Number(NumberField::Listened) => ">1h",
+            Field::Number(NumberField::LastPlayed) => ">30",
+            Field::Number(NumberField::Rating) => ">=4",
+            Field::Number(_) => "1",
+        }
+    }
+}
+
+/// Normalizing can add spaces around transliterated words, those shouldn't have to match.
+fn normalize_term(text: &str) -> String {
+    normalize(text).trim().to_string()
+}
+
+fn unknown_field_error(name: &str) -> ParseError {
+    // The field they meant is likely a fuzzy match, like "artst" for "artist".
+    let suggestion = FIELDS
+        .iter()
+        .filter_map(|(field, _)| fuzzy::fuzzy_match(name, field).map(|found| (found.score, field)))
+        .max_by_key(|(score, _)| *score);
+
+    match suggestion {
+        Some((_, field)) => error(format!(
+            "There is no field called \"{}\". Did you mean \"{}\"?",
+            name, field
+        )),
+        None => error(format!(
+            "There is no field called \"{}\". Try one of: {}.",
+            name,
+            FIELDS
+                .iter()
+                .map(|(field, _)| *field)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+/// Parses durations like `90`, `90s`, `5m`, `1h30m`, `3:30` or `1:02:03`, into seconds.
+fn parse_duration(text: &str) -> Option<u64> {
+    if text.contains(':') {
+        return text.split(':').try_fold(0, |total: u64, part| {
+            total.checked_mul(60)?.checked_add(part.parse().ok()?)
+        });
+    }
+
+    let mut total: u64 = 0;
+    let mut number = String::new();
+    for c in text.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+
+        let unit = match c.to_ascii_lowercase() {
+            'h' => 60 * 60,
+            'm' => 60,
+            's' => 1,
+            _ => return None,
+        };
+        let seconds = number.parse::<u64>().ok()?.checked_mul(unit)?;
+        total = total.checked_add(seconds)?;
+        number.clear();
+    }
+
+    // A number without a unit is in seconds.
+    if !number.is_empty() {
+        total = total.checked_add(number.parse().ok()?)?;
+    }
+
+    Some(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn song() -> Song {
+        Song::named("Dark Mystery (Live)")
+            .with_artist("Audionautix")
+            .with_album("YouTube Audio Library")
+            .with_genre("Post Rock")
+            .with_year(2014)
+            .with_track(3)
+            .with_duration(Duration::from_secs(4 * 60 + 10))
+            .with_path("music/dark_mystery.mp3")
+    }
+
+    fn matches(query: &str) -> bool {
+        parse(query)
+            .unwrap()
+            .evaluate(&song(), &SongData::default())
+            .is_some()
+    }
+
+    fn error_message(query: &str) -> String {
+        parse(query).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("  "), Ok(Query::And(vec![])));
+        assert_eq!(
+            parse("artist:radiohead year:>=2000 genre:\"post rock\" -live dur:<5m"),
+            Ok(Query::And(vec![
+                Query::Text {
+                    field: TextField::Artist,
+                    comparison: TextComparison::Contains,
+                    text: "radiohead".to_string(),
+                },
+                Query::Number {
+                    field: NumberField::Year,
+                    comparison: NumberComparison::GreaterOrEqual,
+                    value: 2000,
+                },
+                Query::Text {
+                    field: TextField::Genre,
+                    comparison: TextComparison::Contains,
+                    text: "post rock".to_string(),
+                },
+                Query::Not(Box::new(Query::Text {
+                    field: TextField::Title,
+                    comparison: TextComparison::Contains,
+                    text: "live".to_string(),
+                })),
+                Query::Number {
+                    field: NumberField::Duration,
+                    comparison: NumberComparison::Less,
+                    value: 300,
+                },
+            ]))
+        );
+        assert_eq!(
+            parse("a b OR c | (d)"),
+            Ok(Query::Or(vec![
+                Query::And(vec![
+                    Query::Fuzzy("a".to_string()),
+                    Query::Fuzzy("b".to_string())
+                ]),
+                Query::Fuzzy("c".to_string()),
+                Query::Fuzzy("d".to_string()),
+            ]))
+        );
+        assert_eq!(parse("orbit"), Ok(Query::Fuzzy("orbit".to_string())));
+    }
+
+    #[test]
+    fn test_evaluate() {
+        assert!(matches(""));
+        assert!(matches("dmys"));
+        assert!(matches("ＤＡＲＫ genre:\"pöst rock\""));
+        assert!(!matches("dmysx"));
+        assert!(matches("artist:audio ALBUM:=\"youtube audio library\""));
+        assert!(!matches("artist:=audio"));
+        assert!(matches(
+            "genre:\"post rock\" year:>=2000 year:<2015 dur:<5m dur:>4:00"
+        ));
+        assert!(!matches("year:2000"));
+        assert!(!matches("disc:1"));
+        assert!(matches("-disc:1 track:3 path:music/"));
+        assert!(!matches("-live"));
+        assert!(matches("-(year:2014 genre:jazz)"));
+        assert!(matches("genre:jazz OR genre:rock"));
+        assert!(!matches("genre:jazz OR (genre:rock -mystery)"));
+        // Never played.
+        assert!(matches("plays:0 skips:<1 lastplayed:>30 listened:<1m"));
+        assert!(!matches("lastplayed:<30"));
+        // Not rated.
+        assert!(matches("rating:0 fav:0"));
+        assert!(!matches("rating:>=4"));
+        assert!(!matches("label:focus"));
+        assert!(matches("-label:focus"));
+
+        let mut labelled = SongData::default();
+        labelled.labels.insert("Focus".to_string());
+        labelled.labels.insert("kids-ok".to_string());
+        let matches_labelled = |query| parse(query).unwrap().evaluate(&song(), &labelled).is_some();
+        assert!(matches_labelled("label:focus"));
+        assert!(matches_labelled("label:=kids-ok"));
+        assert!(!matches_labelled("label:=kids"));
+    }
+
+    #[test]
+    fn test_uses_song_data() {
+        let uses_song_data = |query| parse(query).unwrap().uses_song_data();
+        assert!(!uses_song_data("dark artist:audio year:>2000 dur:<5m"));
+        assert!(uses_song_data("dark -(genre:jazz OR plays:>3)"));
+        assert!(uses_song_data("label:focus"));
+        assert!(uses_song_data("rating:>=4"));
+    }
+
+    #[test]
+    fn test_fuzzy_words_are_ranked_and_highlighted() {
+        let found = parse("dark artist:audio OR live")
+            .unwrap()
+            .evaluate(&song(), &SongData::default())
+            .unwrap();
+        assert_eq!(found.positions, vec![0, 1, 2, 3]);
+        assert!(found.score > 0);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            error_message("artst:radiohead"),
+            "There is no field called \"artst\". Did you mean \"artist\"?"
+        );
+        assert!(error_message("artist:").starts_with("Expected a value"));
+        assert!(error_message("genre:\"post rock").contains("not closed"));
+        assert!(error_message("year:>two").contains("not a number"));
+        assert!(error_message("dur:<5x").contains("not a duration"));
+        assert!(error_message("title:>a").contains("can't be compared"));
+        assert!(error_message("(a b").contains("without a matching \")\""));
+        assert!(error_message("a b)").contains("without a matching \"(\""));
+        assert!(error_message(")").contains("without a matching \"(\""));
+        assert!(error_message("()").contains("nothing to search for"));
+        assert!(error_message(":a").contains("field name"));
+        assert!(error_message("a OR").contains("after \"OR\""));
+        assert!(error_message("OR a").contains("before \"OR\""));
+        assert!(error_message("a - b").contains("exclude"));
+    }
+
+    #[test]
+    fn test_is_narrower_than() {
+        let narrower = |a: &str, b: &str| parse(a).unwrap().is_narrower_than(&parse(b).unwrap());
+
+        assert!(narrower("dark", ""));
+        assert!(narrower("dark", "dk"));
+        assert!(narrower("dark myst", "dark"));
+        assert!(narrower("artist:radiohead", "artist:radio"));
+        assert!(narrower("artist:=radiohead", "artist:head"));
+        assert!(narrower("year:>2000 -live", "year:>2000"));
+        assert!(!narrower("dk", "dark"));
+        assert!(!narrower("artist:radio", "radio"));
+        assert!(!narrower("-live", "-li"));
+        assert!(!narrower("year:<20", "year:<2"));
+        assert!(!narrower("a OR b", "a"));
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90"), Some(90));
+        assert_eq!(parse_duration("90s"), Some(90));
+        assert_eq!(parse_duration("5m"), Some(300));
+        assert_eq!(parse_duration("1h30m"), Some(5400));
+        assert_eq!(parse_duration("3:30"), Some(210));
+        assert_eq!(parse_duration("1:02:03"), Some(3723));
+        assert_eq!(parse_duration("3:x"), None);
+        assert_eq!(parse_duration("5y"), None);
+        // Too long to count in seconds.
+        assert_eq!(parse_duration("99999999999999999h"), None);
+        assert_eq!(parse_duration("99999999999999999:00:00"), None);
+        assert_eq!(
+            parse_duration(&format!("{}h{}s", u64::MAX / 3600, u64::MAX)),
+            None
+        );
+        assert!(parse("dur:<99999999999999999h").is_err());
+    }
+}
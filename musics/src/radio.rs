@@ -0,0 +1,279 @@
+use crate::library::{Library, Song, SongId};
+use crate::query;
+use crate::query::Query;
+use crate::song_data::SongData;
+use camino::Utf8PathBuf;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashSet, VecDeque};
+
+#[derive(Deserialize, Serialize, Clone, PartialEq)]
+// Auto fill properties with their defaults if they are missing.
+#[serde(default)]
+pub struct RadioSettings {
+    /// How many songs the radio keeps queued after the current song.
+    pub lookahead: usize,
+    /// A song is not picked again until this many other songs have been picked.
+    pub avoid_recent_songs: usize,
+    /// An artist is not picked again until this many other songs have been picked.
+    pub avoid_recent_artists: usize,
+    /// Only songs matching this search query are picked, see [`query`].
+    /// An empty filter allows all songs, one that can't be parsed none.
+    pub filter: String,
+    /// Only songs inside this folder (or one of it's subfolders) are picked.
+    pub folder: Option<Utf8PathBuf>,
+    /// Only songs with at least one of these labels are picked.
+    /// Without labels, songs are picked regardless of their labels.
+    pub labels: BTreeSet<String>,
+}
+
+impl Default for RadioSettings {
+    fn default() -> Self {
+        RadioSettings {
+            lookahead: 10,
+            avoid_recent_songs: 100,
+            avoid_recent_artists: 3,
+            filter: String::new(),
+            folder: None,
+            labels: BTreeSet::new(),
+        }
+    }
+}
+
+impl RadioSettings {
+    /// `filter` is passed in, so it doesn't have to be parsed again for every song.
+    fn allows(&self, song: &Song, data: &SongData, filter: &Query) -> bool {
+        let in_folder = self
+            .folder
+            .as_ref()
+            .is_none_or(|folder| song.path.starts_with(folder));
+
+        let has_label =
+            self.labels.is_empty() || data.labels.iter().any(|label| self.labels.contains(label));
+
+        in_folder && has_label && filter.evaluate(song, data).is_some()
+    }
+}
+
+/// Picks random songs from the library, while trying not to repeat itself.
+#[derive(Default)]
+pub struct Radio {
+    /// Most recently picked song at the front.
+    recent_songs: VecDeque<SongId>,
+    /// Artist of the most recently picked song at the front.
+    recent_artists: VecDeque<String>,
+    /// The library versions and settings with which nothing could be picked. Picking is only
+    /// tried again once one of them changes, instead of going through the library every frame.
+    failed_for: Option<((u64, u64), RadioSettings)>,
+}
+
+impl Radio {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns `None` if no song in the library matches the settings.
+    pub fn pick_song(&mut self, library: &Library, settings: &RadioSettings) -> Option<SongId> {
+        let versions = (library.version(), library.data_version());
+        if self
+            .failed_for
+            .as_ref()
+            .is_some_and(|(failed_versions, failed_settings)| {
+                *failed_versions == versions && failed_settings == settings
+            })
+        {
+            return None;
+        }
+
+        let picked = self.pick_matching_song(library, settings);
+        if picked.is_none() {
+            self.failed_for = Some((versions, settings.clone()));
+        }
+        picked
+    }
+
+    fn pick_matching_song(
+        &mut self,
+        library: &Library,
+        settings: &RadioSettings,
+    ) -> Option<SongId> {
+        let filter = query::parse(&settings.filter).ok()?;
+        let candidates: Vec<(SongId, &Song)> = library
+            .songs()
+            .filter(|(id, song)| settings.allows(song, library.get_song_data(*id), &filter))
+            .collect();
+
+        let recent_songs: HashSet<SongId> = self
+            .recent_songs
+            .iter()
+            .take(settings.avoid_recent_songs)
+            .cloned()
+            .collect();
+        let recent_artists: HashSet<&String> = self
+            .recent_artists
+            .iter()
+            .take(settings.avoid_recent_artists)
+            .collect();
+
+        let is_recent_song = |id: &SongId| recent_songs.contains(id);
+        let is_recent_artist = |song: &Song| {
+            song.artist
+                .as_ref()
+                .is_some_and(|artist| recent_artists.contains(artist))
+        };
+
+        // When the filter is very strict, it might not be possible to avoid everything.
+        // In that case, repeating an artist or a song is better than stopping the music.
+        let (id, song) = pick_random(
+            candidates
+                .iter()
+                .filter(|(id, song)| !is_recent_song(id) && !is_recent_artist(song)),
+        )
+        .or_else(|| pick_random(candidates.iter().filter(|(id, _)| !is_recent_song(id))))
+        .or_else(|| pick_random(candidates.iter()))?;
+
+        self.recent_songs.push_front(id);
+        self.recent_songs.truncate(settings.avoid_recent_songs);
+
+        if let Some(artist) = &song.artist {
+            self.recent_artists.push_front(artist.clone());
+            self.recent_artists.truncate(settings.avoid_recent_artists);
+        }
+
+        Some(id)
+    }
+}
+
+fn pick_random<'a>(
+    candidates: impl Iterator<Item = &'a (SongId, &'a Song)>,
+) -> Option<(SongId, &'a Song)> {
+    let candidates: Vec<_> = candidates.collect();
+
+    if candidates.is_empty() {
+        None
+    } else {
+        Some(*candidates[fastrand::usize(..candidates.len())])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use camino::Utf8Path;
+
+    #[test]
+    fn test_avoids_recent_songs() {
+        let mut library = Library::new();
+        library.insert_from_directory(Utf8Path::new("../example_audio"));
+
+        let settings = RadioSettings {
+            avoid_recent_songs: 1,
+            ..Default::default()
+        };
+        let mut radio = Radio::new();
+
+        let mut previous = radio.pick_song(&library, &settings);
+        for _ in 0..10 {
+            let next = radio.pick_song(&library, &settings);
+            assert!(next.is_some());
+            assert_ne!(next, previous);
+            previous = next;
+        }
+    }
+
+    #[test]
+    fn test_folder_and_filter() {
+        let mut library = Library::new();
+        library.insert_from_directory(Utf8Path::new("../example_audio"));
+
+        let mut radio = Radio::new();
+
+        let settings = RadioSettings {
+            folder: Some(Utf8PathBuf::from("../example_audio/subfolder")),
+            ..Default::default()
+        };
+        for _ in 0..5 {
+            let id = radio.pick_song(&library, &settings).unwrap();
+            assert_eq!(library.get_song(id).unwrap().title, "Dark Mystery");
+        }
+
+        let settings = RadioSettings {
+            filter: "blank".to_string(),
+            ..Default::default()
+        };
+        for _ in 0..5 {
+            let id = radio.pick_song(&library, &settings).unwrap();
+            assert_eq!(library.get_song(id).unwrap().title, "Blank Holes");
+        }
+
+        // The filter is a search query.
+        let settings = RadioSettings {
+            filter: "title:mystery OR -holes".to_string(),
+            ..Default::default()
+        };
+        for _ in 0..5 {
+            let id = radio.pick_song(&library, &settings).unwrap();
+            assert_eq!(library.get_song(id).unwrap().title, "Dark Mystery");
+        }
+
+        for filter in ["does not exist", "(unclosed"] {
+            let settings = RadioSettings {
+                filter: filter.to_string(),
+                ..Default::default()
+            };
+            assert_eq!(radio.pick_song(&library, &settings), None);
+        }
+    }
+
+    #[test]
+    fn test_tries_again_after_changes() {
+        let mut library = Library::new();
+        library.insert_from_directory(Utf8Path::new("../example_audio"));
+        let mut radio = Radio::new();
+        let settings = RadioSettings {
+            labels: BTreeSet::from(["focus".to_string()]),
+            ..Default::default()
+        };
+
+        assert_eq!(radio.pick_song(&library, &settings), None);
+        assert!(radio.failed_for.is_some());
+
+        let (id, _) = library.songs().next().unwrap();
+        library.update_song_data(id, |data| {
+            data.labels.insert("focus".to_string());
+        });
+        assert_eq!(radio.pick_song(&library, &settings), Some(id));
+
+        let settings = RadioSettings {
+            filter: "does not exist".to_string(),
+            ..settings
+        };
+        assert_eq!(radio.pick_song(&library, &settings), None);
+        let settings = RadioSettings {
+            filter: String::new(),
+            ..settings
+        };
+        assert_eq!(radio.pick_song(&library, &settings), Some(id));
+    }
+
+    #[test]
+    fn test_labels() {
+        let mut library = Library::new();
+        library.insert_from_directory(Utf8Path::new("../example_audio"));
+        let (labelled, _) = library
+            .songs()
+            .find(|(_, song)| song.title == "Dark Mystery")
+            .unwrap();
+        library.update_song_data(labelled, |data| {
+            data.labels.insert("focus".to_string());
+        });
+
+        let mut radio = Radio::new();
+        let settings = RadioSettings {
+            labels: BTreeSet::from(["focus".to_string(), "workout".to_string()]),
+            ..Default::default()
+        };
+        for _ in 0..5 {
+            assert_eq!(radio.pick_song(&library, &settings), Some(labelled));
+        }
+    }
+}
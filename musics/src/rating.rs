@@ -0,0 +1,240 @@
+//! Star ratings and favourites of songs. Ratings can be written to the tags of the files too,
+//! so they follow the files to other players. Favourites have no common tag, so they can't.
+
+use crate::song_data::SongData;
+use crate::tag_editor::read_id3v2;
+use camino::Utf8Path;
+use eframe::egui;
+use eframe::egui::{Color32, RichText, Sense, Ui, Widget};
+use lofty::id3::v2::{Frame, FrameFlags, FrameValue, ID3v2Tag};
+use lofty::{ItemKey, ItemValue, Tag, TagExt, TagItem, TagType, TaggedFileExt};
+
+/// Ratings go from 1 to this many stars. 0 means the song isn't rated.
+pub const MAX_RATING: u8 = 5;
+
+/// The POPM rating for each number of stars. POPM ratings go from 1 to 255, these are the
+/// values Windows Media Player uses, which most other players understand as well.
+const POPM_RATINGS: [u8; MAX_RATING as usize + 1] = [0, 1, 64, 128, 196, 255];
+
+/// Written in POPM frames, to tell them apart from the ones of other players.
+/// Using the one of Windows Media Player makes more players read the rating.
+const POPM_EMAIL: &str = "Windows Media Player 9 Series";
+
+pub enum RatingChange {
+    Rating(u8),
+    Favourite(bool),
+}
+
+/// Shows the stars and the heart of the song. Clicking the star of the rating it already has,
+/// takes the rating away.
+#[must_use]
+pub fn show_rating(ui: &mut Ui, data: &SongData) -> Option<RatingChange> {
+    let mut change = None;
+
+    ui.spacing_mut().item_spacing.x = 0.0;
+    for stars in 1..=MAX_RATING {
+        let (text, color) = if stars <= data.rating {
+            ("★", Color32::GOLD)
+        } else {
+            ("☆", Color32::GRAY)
+        };
+        let response = egui::Label::new(RichText::new(text).color(color))
+            .sense(Sense::click())
+            .ui(ui);
+        if response.clicked() {
+            let rating = if stars == data.rating { 0 } else { stars };
+            change = Some(RatingChange::Rating(rating));
+        }
+    }
+
+    ui.add_space(4.0);
+    let (text, color) = if data.favourite {
+        ("♥", Color32::LIGHT_RED)
+    } else {
+        ("♡", Color32::GRAY)
+    };
+    let response = egui::Label::new(RichText::new(text).color(color))
+        .sense(Sense::click())
+        .ui(ui)
+        .on_hover_text("Favourite");
+    if response.clicked() {
+        change = Some(RatingChange::Favourite(!data.favourite));
+    }
+
+    change
+}
+
+/// Writes the rating to the main tag of the file, creating it if needed.
+/// Only ID3v2 and Vorbis comments have a common way to store ratings, other tags are left alone.
+pub fn write_rating_to_file(path: &Utf8Path, rating: u8) -> lofty::Result<()> {
+    if let Some(mut tag) = read_id3v2(path)? {
+        set_popm_frame(&mut tag, rating);
+        return tag.save_to_path(path);
+    }
+
+    let mut tagged_file = lofty::read_from_path(path)?;
+    if tagged_file.primary_tag().is_none() {
+        tagged_file.insert_tag(Tag::new(tagged_file.primary_tag_type()));
+    }
+    let Some(tag) = tagged_file.primary_tag_mut() else {
+        // The file type doesn't support tags at all.
+        return Ok(());
+    };
+
+    if set_rating_items(tag, rating) {
+        tag.save_to_path(path)?;
+    }
+    Ok(())
+}
+
+/// Replaces the POPM frame with [`POPM_EMAIL`]. The ones of other players are left alone.
+fn set_popm_frame(tag: &mut ID3v2Tag, rating: u8) {
+    let rating = rating.min(MAX_RATING);
+    tag.retain(|frame| !is_own_popm_frame(frame));
+    if rating > 0 {
+        let frame = Frame::new(
+            "POPM",
+            FrameValue::Binary(popm_frame(rating)),
+            FrameFlags::default(),
+        );
+        if let Ok(frame) = frame {
+            tag.insert(frame);
+        }
+    }
+}
+
+fn is_own_popm_frame(frame: &Frame) -> bool {
+    if frame.id_str() != "POPM" {
+        return false;
+    }
+    // `read_id3v2` keeps POPM frames as bytes.
+    match frame.content() {
+        FrameValue::Binary(bytes) => bytes
+            .strip_prefix(POPM_EMAIL.as_bytes())
+            .is_some_and(|rest| rest.first() == Some(&0)),
+        _ => false,
+    }
+}
+
+/// Returns whether the tag type supports ratings. ID3v2 tags are written by [`set_popm_frame`].
+fn set_rating_items(tag: &mut Tag, rating: u8) -> bool {
+    let rating = rating.min(MAX_RATING);
+    let fmps_key = ItemKey::Unknown("FMPS_RATING".to_string());
+
+    match tag.tag_type() {
+        TagType::VorbisComments => {
+            // FMPS_RATING goes from 0.0 to 1.0, RATING from 0 to 100.
+            tag.remove_key(&fmps_key);
+            tag.remove_key(&ItemKey::Popularimeter);
+            if rating > 0 {
+                let fraction = f32::from(rating) / f32::from(MAX_RATING);
+                // Lofty doesn't know FMPS_RATING, so it has to be inserted without checking.
+                tag.insert_item_unchecked(TagItem::new(
+                    fmps_key,
+                    ItemValue::Text(fraction.to_string()),
+                ));
+                tag.insert_text(ItemKey::Popularimeter, (u32::from(rating) * 20).to_string());
+            }
+            true
+        }
+        _ => false,
+    }
+}
+
+/// The contents of a POPM frame: the email, the rating and a play counter, which is left at 0.
+fn popm_frame(rating: u8) -> Vec<u8> {
+    let mut frame = POPM_EMAIL.as_bytes().to_vec();
+    frame.push(0);
+    frame.push(POPM_RATINGS[usize::from(rating)]);
+    frame.extend(0u32.to_be_bytes());
+    frame
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use lofty::Accessor;
+
+    #[test]
+    fn test_rating_items() {
+        let mut id3v2 = ID3v2Tag::default();
+        id3v2.insert(other_popm_frame());
+        set_popm_frame(&mut id3v2, 4);
+        set_popm_frame(&mut id3v2, 3);
+        let frames: Vec<&Frame> = (&id3v2).into_iter().collect();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0], &other_popm_frame());
+        let FrameValue::Binary(frame) = frames[1].content() else {
+            panic!("not the POPM frame of the player");
+        };
+        assert_eq!(frame[POPM_EMAIL.len() + 1], 128);
+
+        // Only the frame of the player is removed.
+        set_popm_frame(&mut id3v2, 0);
+        assert_eq!((&id3v2).into_iter().count(), 1);
+        assert!(!set_rating_items(&mut Tag::new(TagType::ID3v2), 4));
+
+        let mut vorbis = Tag::new(TagType::VorbisComments);
+        assert!(set_rating_items(&mut vorbis, 4));
+        assert_eq!(
+            vorbis.get_string(&ItemKey::Unknown("FMPS_RATING".to_string())),
+            Some("0.8")
+        );
+        assert_eq!(vorbis.get_string(&ItemKey::Popularimeter), Some("80"));
+
+        // No rating removes them again.
+        assert!(set_rating_items(&mut vorbis, 0));
+        assert_eq!(vorbis.item_count(), 0);
+
+        assert!(!set_rating_items(&mut Tag::new(TagType::MP4ilst), 4));
+    }
+
+    #[test]
+    fn test_write_rating_to_file() {
+        let original = "../example_audio/blank_holes_snippet.ogg";
+        let original_title = lofty::read_from_path(original)
+            .unwrap()
+            .primary_tag()
+            .and_then(|tag| tag.title().map(|title| title.into_owned()));
+
+        let directory = TempDir::new("rating");
+        let path = directory.path().join("song.ogg");
+        std::fs::copy(original, &path).unwrap();
+
+        write_rating_to_file(&path, 3).unwrap();
+        let tagged_file = lofty::read_from_path(&path).unwrap();
+
+        let tag = tagged_file.primary_tag().unwrap();
+        assert_eq!(
+            tag.get_string(&ItemKey::Unknown("FMPS_RATING".to_string())),
+            Some("0.6")
+        );
+        // The other tags are kept.
+        assert_eq!(tag.title().map(|title| title.into_owned()), original_title);
+
+        let path = directory.path().join("song.mp3");
+        std::fs::copy("../example_audio/subfolder/dark_mystery_snippet.mp3", &path).unwrap();
+        let mut id3v2 = read_id3v2(&path).unwrap().unwrap();
+        id3v2.insert(other_popm_frame());
+        id3v2.save_to_path(&path).unwrap();
+
+        write_rating_to_file(&path, 5).unwrap();
+        write_rating_to_file(&path, 2).unwrap();
+        let popm_frames: Vec<Frame> = read_id3v2(&path)
+            .unwrap()
+            .unwrap()
+            .into_iter()
+            .filter(|frame| frame.id_str() == "POPM")
+            .collect();
+        assert_eq!(popm_frames.len(), 2);
+        assert_eq!(popm_frames[0], other_popm_frame());
+        assert_eq!(popm_frames[1].content(), &FrameValue::Binary(popm_frame(2)));
+    }
+
+    /// Rated by another player.
+    fn other_popm_frame() -> Frame<'static> {
+        let content = b"someone@example.com\0\xc8\0\0\0\x07".to_vec();
+        Frame::new("POPM", FrameValue::Binary(content), FrameFlags::default()).unwrap()
+    }
+}
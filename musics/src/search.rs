@@ -0,0 +1,425 @@
+use crate::library::{Library, SongId};
+use crate::query::Query;
+use crate::search_index::Candidates;
+use eframe::egui;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::Arc;
+use std::thread;
+
+/// How many songs are checked between looking whether the search was cancelled.
+const CANCEL_CHECK_INTERVAL: usize = 1024;
+/// How many matches are ranked at a time, about a screenful.
+pub const RANK_AT_A_TIME: usize = 50;
+
+/// The songs that match a query. The best matches come first.
+/// Of equally good matches, the shortest title comes first.
+///
+/// Queries of one or two letters match most of a large library, and checking every song
+/// takes much longer than showing the first screenful of them. So the rest can be checked
+/// later, when scrolling down to it. The best matches then only come first within each part
+/// that was checked at once.
+#[derive(Clone)]
+pub struct SearchResults {
+    query: Query,
+    /// Checked and ranked.
+    found: Vec<SongId>,
+    /// The songs that could still match.
+    unchecked: Candidates,
+    /// All of `found` in library order, once every song has been checked.
+    /// For narrowing a next search down to.
+    all_found: Option<Arc<Vec<SongId>>>,
+}
+
+impl SearchResults {
+    /// Nothing found, for before the first search.
+    pub fn empty() -> Self {
+        SearchResults {
+            query: Query::And(Vec::new()),
+            found: Vec::new(),
+            unchecked: Candidates::none(),
+            all_found: Some(Arc::default()),
+        }
+    }
+
+    /// Checks songs until `wanted` matches are found, or every song has been checked.
+    ///
+    /// When the query is known to only match songs from the results of an earlier search,
+    /// those can be passed as `narrow_to`, see [`SearchResults::narrowing`], so the other
+    /// songs don't have to be checked.
+    ///
+    /// Returns `None` if the search was cancelled.
+    pub fn new(
+        library: &Library,
+        query: Query,
+        narrow_to: Option<Arc<Vec<SongId>>>,
+        wanted: usize,
+        cancelled: &AtomicBool,
+    ) -> Option<Self> {
+        let candidates = library.search_index().candidates(&query);
+        let unchecked = match narrow_to {
+            Some(songs) => candidates.within(songs),
+            None => candidates,
+        };
+
+        let mut results = SearchResults {
+            query,
+            found: Vec::new(),
+            unchecked,
+            all_found: None,
+        };
+        results.check(library, wanted, cancelled).then_some(results)
+    }
+
+    fn check(&mut self, library: &Library, wanted: usize, cancelled: &AtomicBool) -> bool {
+        let mut matches: Vec<(i32, usize, SongId)> = Vec::new();
+        let mut count: usize = 0;
+        while self.found.len() + matches.len() < wanted {
+            if count.is_multiple_of(CANCEL_CHECK_INTERVAL) && cancelled.load(Ordering::Relaxed) {
+                return false;
+            }
+            count += 1;
+
+            let Some(id) = self.unchecked.next() else {
+                break;
+            };
+            if let Some(song) = library.get_song(id) {
+                if let Some(found) = self.query.evaluate(song, library.get_song_data(id)) {
+                    matches.push((found.score, song.title.chars().count(), id));
+                }
+            }
+        }
+
+        // Stable sort, so equal matches keep the library order.
+        matches.sort_by(|(a_score, a_length, _), (b_score, b_length, _)| {
+            b_score.cmp(a_score).then(a_length.cmp(b_length))
+        });
+        self.found.extend(matches.into_iter().map(|(_, _, id)| id));
+        if self.unchecked.peek().is_none() {
+            let mut all_found = self.found.clone();
+            all_found.sort();
+            self.all_found = Some(Arc::new(all_found));
+        }
+        true
+    }
+
+    pub fn query(&self) -> &Query {
+        &self.query
+    }
+
+    /// The matches checked so far.
+    pub fn found(&self) -> &[SongId] {
+        &self.found
+    }
+
+    /// Whether every match has been found.
+    pub fn is_complete(&self) -> bool {
+        self.all_found.is_some()
+    }
+
+    /// Checks more songs, until at least `wanted` matches are found.
+    /// The `library` should be the one that was searched.
+    pub fn find_more(&mut self, library: &Library, wanted: usize) {
+        self.check(library, wanted, &AtomicBool::new(false));
+    }
+
+    /// Finds all matches, and returns them.
+    pub fn find_all(&mut self, library: &Library) -> &[SongId] {
+        self.find_more(library, usize::MAX);
+        &self.found
+    }
+
+    pub fn into_found(self) -> Vec<SongId> {
+        self.found
+    }
+
+    /// All found songs, for narrowing a next search down to. `None` when not all have been
+    /// found yet.
+    pub fn narrowing(&self) -> Option<Arc<Vec<SongId>>> {
+        self.all_found.clone()
+    }
+}
+
+/// Runs a search on another thread, so that typing in the search bar doesn't stutter
+/// with large libraries. Sends the first `wanted` matches as soon as they're found, see
+/// [`SearchResults::new`], and then goes on to find the rest. So a next search can be
+/// narrowed down to them.
+/// Dropping it cancels the search.
+pub struct BackgroundSearch {
+    cancelled: Arc<AtomicBool>,
+    receiver: Receiver<SearchResults>,
+}
+
+impl BackgroundSearch {
+    /// Asks for a repaint when results are ready.
+    pub fn start(
+        library: Library,
+        query: Query,
+        narrow_to: Option<Arc<Vec<SongId>>>,
+        wanted: usize,
+        ctx: egui::Context,
+    ) -> Self {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = channel();
+
+        let thread_cancelled = cancelled.clone();
+        thread::spawn(move || {
+            let Some(mut results) =
+                SearchResults::new(&library, query, narrow_to, wanted, &thread_cancelled)
+            else {
+                return;
+            };
+            let complete = results.is_complete();
+            // The receiver is gone when the search was cancelled in the meantime.
+            if sender.send(results.clone()).is_err() {
+                return;
+            }
+            ctx.request_repaint();
+
+            if !complete
+                && results.check(&library, usize::MAX, &thread_cancelled)
+                && sender.send(results).is_ok()
+            {
+                ctx.request_repaint();
+            }
+        });
+
+        BackgroundSearch {
+            cancelled,
+            receiver,
+        }
+    }
+
+    /// Returns the first results once they're found, and then all of them.
+    pub fn try_take_results(&self) -> Option<SearchResults> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+impl Drop for BackgroundSearch {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library::Song;
+    use crate::query;
+    use camino::Utf8PathBuf;
+    use std::time::{Duration, Instant};
+
+    /// Builds a library of made up songs, with made up words.
+    fn generated_library(song_count: usize) -> Library {
+        let rng = fastrand::Rng::with_seed(7);
+        let syllables = [
+            "ka", "ro", "mi", "te", "su", "la", "ne", "do", "vi", "an", "ex", "or", "ul", "pa",
+            "shi", "gro", "bli", "tha", "ve", "zu",
+        ];
+        let word = |max_syllables: usize| -> String {
+            (0..rng.usize(1..=max_syllables))
+                .map(|_| syllables[rng.usize(..syllables.len())])
+                .collect()
+        };
+        let words =
+            |count: usize| -> String { (0..count).map(|_| word(4)).collect::<Vec<_>>().join(" ") };
+
+        let artists: Vec<String> = (0..5000).map(|_| words(2)).collect();
+        let genres: Vec<String> = (0..40).map(|_| word(3)).collect();
+
+        let mut library = Library::new();
+        for _ in 0..song_count {
+            library.insert_song(Song {
+                title: words(rng.usize(1..=4)),
+                artist: Some(artists[rng.usize(..artists.len())].clone()),
+                album: Some(words(rng.usize(1..=3))),
+                genre: Some(genres[rng.usize(..genres.len())].clone()),
+                year: Some(rng.u32(1950..2023)),
+                track_number: Some(rng.u32(1..15)),
+                duration: Some(Duration::from_secs(rng.u64(60..600))),
+                path: Utf8PathBuf::from(format!("{}.mp3", word(6))),
+                ..Default::default()
+            });
+        }
+        library
+    }
+
+    fn search_all(
+        library: &Library,
+        query: &Query,
+        narrow_to: Option<Arc<Vec<SongId>>>,
+    ) -> SearchResults {
+        let not_cancelled = AtomicBool::new(false);
+        SearchResults::new(
+            library,
+            query.clone(),
+            narrow_to,
+            usize::MAX,
+            &not_cancelled,
+        )
+        .unwrap()
+    }
+
+    fn run(library: &Library, query: &str, narrow_to: Option<Arc<Vec<SongId>>>) -> Vec<SongId> {
+        let query = query::parse(query).unwrap();
+        search_all(library, &query, narrow_to).into_found()
+    }
+
+    #[test]
+    fn test_index_and_narrowing_give_the_same_results() {
+        let library = generated_library(2000);
+
+        for query in [
+            "ka",
+            "karo",
+            "k r",
+            "artist:shi",
+            "la -ro",
+            "genre:zu OR year:<1960",
+            "dur:<2m",
+        ] {
+            let query = query::parse(query).unwrap();
+
+            let mut scanned: Vec<SongId> = library
+                .songs()
+                .filter(|(id, song)| query.evaluate(song, library.get_song_data(*id)).is_some())
+                .map(|(id, _)| id)
+                .collect();
+            let mut found = search_all(&library, &query, None).into_found();
+            scanned.sort();
+            found.sort();
+            assert_eq!(found, scanned, "{:?}", query);
+        }
+
+        let broad = search_all(&library, &query::parse("ka").unwrap(), None);
+        assert_eq!(
+            run(&library, "karo", broad.narrowing()),
+            run(&library, "karo", None)
+        );
+    }
+
+    #[test]
+    fn test_results_are_found_a_part_at_a_time() {
+        let library = generated_library(2000);
+        let query = query::parse("ka").unwrap();
+        let not_cancelled = AtomicBool::new(false);
+
+        let mut results =
+            SearchResults::new(&library, query.clone(), None, 10, &not_cancelled).unwrap();
+        assert_eq!(results.found().len(), 10);
+        assert!(!results.is_complete());
+        let first_part = results.found().to_vec();
+        assert_eq!(results.narrowing(), None);
+
+        results.find_more(&library, 20);
+        assert_eq!(&results.found()[..10], first_part);
+        assert_eq!(results.found().len(), 20);
+
+        let mut found = results.find_all(&library).to_vec();
+        assert!(results.is_complete());
+        let mut all = search_all(&library, &query, None).into_found();
+        found.sort();
+        all.sort();
+        assert_eq!(found, all);
+        assert_eq!(
+            results.narrowing().map(|songs| songs.len()),
+            Some(all.len())
+        );
+    }
+
+    #[test]
+    fn test_cancel() {
+        let library = generated_library(100);
+        let query = query::parse("ka").unwrap();
+        let cancelled = AtomicBool::new(true);
+        assert!(SearchResults::new(&library, query, None, usize::MAX, &cancelled).is_none());
+    }
+
+    /// Run with `cargo test --release -- --ignored --nocapture bench`.
+    ///
+    /// Finding the first screenful of results should take less than a millisecond,
+    /// however broad the query is.
+    #[test]
+    #[ignore]
+    fn bench_search_100k_songs() {
+        let library = generated_library(100_000);
+        let not_cancelled = AtomicBool::new(false);
+        let first_screenful = |query: &Query, narrow_to: Option<Arc<Vec<SongId>>>| {
+            SearchResults::new(
+                &library,
+                query.clone(),
+                narrow_to,
+                RANK_AT_A_TIME,
+                &not_cancelled,
+            )
+            .unwrap()
+        };
+
+        let time = |query: &Query, narrow_to: &Option<Arc<Vec<SongId>>>| {
+            let runs = 20;
+            let start = Instant::now();
+            let mut found = 0;
+            for _ in 0..runs {
+                found = first_screenful(query, narrow_to.clone()).found().len();
+            }
+            let elapsed = start.elapsed() / runs;
+            assert!(
+                elapsed < Duration::from_millis(1),
+                "{:?} took {:?}",
+                query,
+                elapsed
+            );
+            (elapsed, found)
+        };
+
+        // Typing a query one character at a time, narrowing down the previous results.
+        let typed = "artist:shigro blithave";
+        let mut previous: Option<(Query, SearchResults)> = None;
+        for end in 1..=typed.len() {
+            let Ok(query) = query::parse(&typed[..end]) else {
+                continue;
+            };
+            let narrow_to = previous
+                .as_ref()
+                .filter(|(previous_query, _)| query.is_narrower_than(previous_query))
+                .and_then(|(_, results)| results.narrowing());
+
+            let (elapsed, found) = time(&query, &narrow_to);
+            println!(
+                "{:<24} {:>8} songs {:>10.3?}",
+                &typed[..end],
+                found,
+                elapsed
+            );
+
+            // The rest is found in the background, while typing the next character.
+            let results = SearchResults::new(
+                &library,
+                query.clone(),
+                narrow_to,
+                usize::MAX,
+                &not_cancelled,
+            )
+            .unwrap();
+            previous = Some((query, results));
+        }
+
+        println!();
+
+        // Queries from scratch.
+        for query in [
+            "thavezu",
+            "artist:\"gro bli\"",
+            "genre:zuve year:>2000",
+            "title:kamite",
+            "a",
+            "ka",
+            "-ka",
+            "dur:<2m",
+        ] {
+            let (elapsed, found) = time(&query::parse(query).unwrap(), &None);
+            println!("{:<24} {:>8} songs {:>10.3?}", query, found, elapsed);
+        }
+    }
+}
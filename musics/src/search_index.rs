@@ -0,0 +1,331 @@
+//! An inverted index over the text fields of the songs in the library.
+//! Searches use it to only look at the songs that can possibly match, instead of every song.
+//!
+//! Every field is normalized, see [`normalize`], and split into its characters and its trigrams (runs of three characters).
+//! A song can only contain a piece of text when it contains all of the trigrams of that text.
+//! And it can only fuzzy match a word, when it contains all of the characters of that word.
+
+use crate::library::{Song, SongId};
+use crate::normalize::normalize;
+use crate::query::{Query, TextField};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+const INDEXED_FIELDS: [TextField; 4] = [
+    TextField::Title,
+    TextField::Artist,
+    TextField::Album,
+    TextField::Genre,
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Gram {
+    Char(char),
+    Trigram([char; 3]),
+}
+
+/// The lists are shared with the searches that are still going through them, and are only
+/// copied when they change in the meantime.
+#[derive(Default, Clone)]
+pub struct SearchIndex {
+    /// The songs that contain the gram in the field, sorted.
+    postings: HashMap<(TextField, Gram), Arc<Vec<SongId>>>,
+    /// Every song, sorted. For queries that can't be narrowed down.
+    songs: Arc<Vec<SongId>>,
+}
+
+impl SearchIndex {
+    pub fn insert(&mut self, id: SongId, song: &Song) {
+        for gram in song_grams(song) {
+            insert_sorted(self.postings.entry(gram).or_default(), id);
+        }
+        insert_sorted(&mut self.songs, id);
+    }
+
+    /// `song` should be the song as it was inserted.
+    pub fn remove(&mut self, id: SongId, song: &Song) {
+        for (field, gram) in song_grams(song) {
+            if let Some(songs) = self.postings.get_mut(&(field, gram)) {
+                if let Ok(position) = songs.binary_search(&id) {
+                    Arc::make_mut(songs).remove(position);
+                }
+                if songs.is_empty() {
+                    self.postings.remove(&(field, gram));
+                }
+            }
+        }
+        if let Ok(position) = self.songs.binary_search(&id) {
+            Arc::make_mut(&mut self.songs).remove(position);
+        }
+    }
+
+    /// Returns the songs that could match the query, in library order.
+    /// They still have to be checked against the query, this only rules songs out.
+    pub fn candidates(&self, query: &Query) -> Candidates {
+        self.narrowed_candidates(query)
+            .unwrap_or_else(|| Candidates::list(self.songs.clone()))
+    }
+
+    /// Returns `None` if the index can't rule anything out, and every song has to be checked.
+    fn narrowed_candidates(&self, query: &Query) -> Option<Candidates> {
+        match query {
+            Query::And(queries) => {
+                let mut candidates: Vec<Candidates> = queries
+                    .iter()
+                    .filter_map(|q| self.narrowed_candidates(q))
+                    .collect();
+                match candidates.len() {
+                    0 => None,
+                    1 => candidates.pop(),
+                    _ => Some(Candidates::all(candidates)),
+                }
+            }
+            Query::Or(queries) => {
+                // If any of the alternatives can't be narrowed down, neither can the whole.
+                let candidates: Option<Vec<Candidates>> = queries
+                    .iter()
+                    .map(|q| self.narrowed_candidates(q))
+                    .collect();
+                Some(Candidates::Any(candidates?))
+            }
+            // Songs that don't match can be anywhere.
+            Query::Not(_) => None,
+            Query::Fuzzy(word) => {
+                // `word` is already normalized.
+                let grams = word.chars().map(Gram::Char);
+                self.songs_with_all(TextField::Title, grams)
+            }
+            Query::Text { field, text, .. } => {
+                if !INDEXED_FIELDS.contains(field) {
+                    return None;
+                }
+                // `text` is already normalized.
+                // A field with all of the trigrams also has all of the characters.
+                let grams: Vec<Gram> = text_grams(text).collect();
+                let trigrams = grams.iter().filter(|gram| matches!(gram, Gram::Trigram(_)));
+                match trigrams.clone().next() {
+                    Some(_) => self.songs_with_all(*field, trigrams.copied()),
+                    None => self.songs_with_all(*field, grams.into_iter()),
+                }
+            }
+            Query::Number { .. } => None,
+        }
+    }
+
+    fn songs_with_all(
+        &self,
+        field: TextField,
+        grams: impl Iterator<Item = Gram>,
+    ) -> Option<Candidates> {
+        let mut postings = Vec::new();
+        for gram in grams {
+            match self.postings.get(&(field, gram)) {
+                Some(songs) => postings.push(Candidates::list(songs.clone())),
+                // No song contains this gram, so none can match.
+                None => return Some(Candidates::none()),
+            }
+        }
+        match postings.len() {
+            0 => None,
+            1 => postings.pop(),
+            _ => Some(Candidates::all(postings)),
+        }
+    }
+}
+
+fn insert_sorted(songs: &mut Arc<Vec<SongId>>, id: SongId) {
+    if let Err(position) = songs.binary_search(&id) {
+        Arc::make_mut(songs).insert(position, id);
+    }
+}
+
+/// The songs that could match a query, see [`SearchIndex::candidates`].
+///
+/// They are only worked out while iterating, so the first ones are there right away, even
+/// when most of the library could match and the rest is never looked at.
+#[derive(Clone)]
+pub enum Candidates {
+    /// The songs of a sorted list, from `next` on.
+    List {
+        songs: Arc<Vec<SongId>>,
+        next: usize,
+    },
+    /// The songs that are in all of them.
+    All(Vec<Candidates>),
+    /// The songs that are in any of them.
+    Any(Vec<Candidates>),
+}
+
+impl Candidates {
+    fn list(songs: Arc<Vec<SongId>>) -> Self {
+        Candidates::List { songs, next: 0 }
+    }
+
+    pub fn none() -> Self {
+        Candidates::list(Arc::default())
+    }
+
+    /// Only the candidates that are also in `songs`, which should be sorted.
+    pub fn within(self, songs: Arc<Vec<SongId>>) -> Self {
+        Candidates::all(vec![Candidates::list(songs), self])
+    }
+
+    /// Takes apart the ones that are in all of something themselves, so the shortest list
+    /// decides which songs all of the others skip to. Instead of every group going through
+    /// its own songs first.
+    fn all(candidates: Vec<Candidates>) -> Self {
+        let mut all = Vec::new();
+        for candidates in candidates {
+            match candidates {
+                Candidates::All(inner) => all.extend(inner),
+                other => all.push(other),
+            }
+        }
+        all.sort_by_key(Candidates::max_len);
+        Candidates::All(all)
+    }
+
+    /// How many songs there are left at most.
+    fn max_len(&self) -> usize {
+        match self {
+            Candidates::List { songs, next } => songs.len() - next,
+            Candidates::All(all) => all.iter().map(Candidates::max_len).min().unwrap_or(0),
+            Candidates::Any(any) => any.iter().map(Candidates::max_len).sum(),
+        }
+    }
+
+    /// The next candidate, without going past it.
+    pub fn peek(&mut self) -> Option<SongId> {
+        match self {
+            Candidates::List { songs, next } => songs.get(*next).copied(),
+            Candidates::All(all) => {
+                // Skips every list ahead to the furthest one, until they all agree.
+                let mut target = all.first_mut()?.peek()?;
+                loop {
+                    let mut agreed = true;
+                    for candidates in all.iter_mut() {
+                        candidates.skip_to(target, false);
+                        let id = candidates.peek()?;
+                        if id != target {
+                            target = id;
+                            agreed = false;
+                        }
+                    }
+                    if agreed {
+                        return Some(target);
+                    }
+                }
+            }
+            Candidates::Any(any) => any.iter_mut().filter_map(|c| c.peek()).min(),
+        }
+    }
+
+    /// Goes past the songs before `id`, and past `id` itself too when `inclusive`.
+    fn skip_to(&mut self, id: SongId, inclusive: bool) {
+        let is_past = |other: SongId| other < id || (inclusive && other == id);
+        match self {
+            Candidates::List { songs, next } => {
+                let rest = &songs[*next..];
+                // Doubles the step size to find a range that ends past them, which is quick
+                // when only a few are skipped.
+                let mut end = 1;
+                while end < rest.len() && is_past(rest[end - 1]) {
+                    end *= 2;
+                }
+                let end = end.min(rest.len());
+                *next += rest[..end].partition_point(|id| is_past(*id));
+            }
+            Candidates::All(all) | Candidates::Any(all) => {
+                for candidates in all {
+                    candidates.skip_to(id, inclusive);
+                }
+            }
+        }
+    }
+}
+
+impl Iterator for Candidates {
+    type Item = SongId;
+
+    fn next(&mut self) -> Option<SongId> {
+        let id = self.peek()?;
+        self.skip_to(id, true);
+        Some(id)
+    }
+}
+
+fn song_grams(song: &Song) -> HashSet<(TextField, Gram)> {
+    INDEXED_FIELDS
+        .iter()
+        .filter_map(|field| field.value(song).map(|value| (*field, normalize(value))))
+        .flat_map(|(field, value)| text_grams(&value).map(move |gram| (field, gram)))
+        .collect()
+}
+
+/// All characters, and all trigrams. Texts shorter than three characters only have characters.
+fn text_grams(text: &str) -> impl Iterator<Item = Gram> {
+    let chars: Vec<char> = text.chars().collect();
+    let trigrams: Vec<Gram> = chars
+        .windows(3)
+        .map(|window| Gram::Trigram([window[0], window[1], window[2]]))
+        .collect();
+    chars.into_iter().map(Gram::Char).chain(trigrams)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query;
+    use slotmap::SlotMap;
+
+    fn candidates(index: &SearchIndex, query: &str) -> Option<Vec<SongId>> {
+        index
+            .narrowed_candidates(&query::parse(query).unwrap())
+            .map(|candidates| candidates.collect())
+    }
+
+    #[test]
+    fn test_candidates() {
+        let songs = [
+            Song::named("Dark Mystery").with_artist("Audionautix"),
+            Song::named("Blank Holes").with_artist("Jingle Punks"),
+            Song::named("Marked").with_artist("Radiohead"),
+        ];
+        let mut ids = SlotMap::<SongId, ()>::with_key();
+        let ids: Vec<SongId> = songs.iter().map(|_| ids.insert(())).collect();
+
+        let mut index = SearchIndex::default();
+        for (id, song) in ids.iter().zip(&songs) {
+            index.insert(*id, song);
+        }
+
+        assert_eq!(candidates(&index, "dark"), Some(vec![ids[0], ids[2]]));
+        assert_eq!(candidates(&index, "dmy"), Some(vec![ids[0]]));
+        assert_eq!(candidates(&index, "xyz"), Some(vec![]));
+        assert_eq!(candidates(&index, "artist:punk"), Some(vec![ids[1]]));
+        assert_eq!(candidates(&index, "artist:io"), Some(vec![ids[0], ids[2]]));
+        assert_eq!(
+            candidates(&index, "dark OR artist:punk"),
+            Some(vec![ids[0], ids[1], ids[2]])
+        );
+        assert_eq!(
+            candidates(&index, "dark year:2000"),
+            Some(vec![ids[0], ids[2]])
+        );
+        assert_eq!(candidates(&index, "year:2000"), None);
+        assert_eq!(candidates(&index, "dark OR year:2000"), None);
+        assert_eq!(candidates(&index, "-dark"), None);
+        assert_eq!(candidates(&index, "path:song"), None);
+        assert_eq!(index.candidates(&query::parse("-dark").unwrap()).count(), 3);
+        let within = index
+            .candidates(&query::parse("dark").unwrap())
+            .within(Arc::new(vec![ids[1], ids[2]]));
+        assert_eq!(within.collect::<Vec<_>>(), vec![ids[2]]);
+
+        index.remove(ids[0], &songs[0]);
+        assert_eq!(candidates(&index, "dark"), Some(vec![ids[2]]));
+        assert!(!index
+            .postings
+            .contains_key(&(TextField::Artist, Gram::Trigram(['a', 'u', 'd']))));
+    }
+}
@@ -0,0 +1,176 @@
+use crate::library::SongId;
+use crate::library_search_view::{show_songs_menu, LibraryViewCommand};
+use eframe::egui::{Modifiers, Response};
+use std::collections::btree_set::Iter;
+use std::collections::BTreeSet;
+use std::ops::Range;
+
+/// Selected rows of a list, by index.
+/// Ctrl-clicking toggles a row, shift-clicking selects everything from the last clicked row.
+#[derive(Default)]
+pub struct Selection {
+    selected: BTreeSet<usize>,
+    /// The row that was clicked last without holding shift.
+    /// Shift-clicking selects everything between this row and the clicked row.
+    anchor: Option<usize>,
+}
+
+impl Selection {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        self.selected.contains(&index)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.selected.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.selected.len()
+    }
+
+    /// In ascending order.
+    pub fn indices(&self) -> Iter<'_, usize> {
+        self.selected.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.selected.clear();
+        self.anchor = None;
+    }
+
+    pub fn select_only(&mut self, index: usize) {
+        self.selected = BTreeSet::from([index]);
+        self.anchor = Some(index);
+    }
+
+    /// The anchor is set to the start of the range.
+    pub fn select_range(&mut self, range: Range<usize>) {
+        self.anchor = Some(range.start);
+        self.selected = range.collect();
+    }
+
+    pub fn select_all(&mut self, count: usize) {
+        self.selected = (0..count).collect();
+    }
+
+    /// Forgets about rows that no longer exist.
+    pub fn retain_below(&mut self, count: usize) {
+        self.selected.retain(|index| *index < count);
+    }
+
+    /// Updates the selection for clicks on the row. Returns `true` for a plain click, like
+    /// [`Selection::click`].
+    ///
+    /// Right-clicking a row that isn't selected selects only that row, so its menu acts on
+    /// only that row.
+    pub fn handle_row_clicks(&mut self, response: &Response, index: usize) -> bool {
+        let plain_click = response.clicked() && self.click(index, response.ctx.input().modifiers);
+        if response.secondary_clicked() && !self.contains(index) {
+            self.select_only(index);
+        }
+        plain_click
+    }
+
+    /// Updates the selection for a click on the row.
+    /// Returns `true` for a plain click, which selects only the clicked row.
+    pub fn click(&mut self, index: usize, modifiers: Modifiers) -> bool {
+        if modifiers.shift {
+            let anchor = self.anchor.unwrap_or(index);
+            if !modifiers.command {
+                self.selected.clear();
+            }
+            self.selected.extend(anchor.min(index)..=anchor.max(index));
+            false
+        } else if modifiers.command {
+            if !self.selected.remove(&index) {
+                self.selected.insert(index);
+            }
+            self.anchor = Some(index);
+            false
+        } else {
+            self.select_only(index);
+            true
+        }
+    }
+
+    /// Selects everything between the anchor and `index`, like shift + arrow keys do.
+    /// When there is no anchor yet, `from` becomes the anchor.
+    pub fn extend_to(&mut self, from: usize, index: usize) {
+        let anchor = *self.anchor.get_or_insert(from);
+        self.selected = (anchor.min(index)..=anchor.max(index)).collect();
+    }
+}
+
+/// A row in a list of songs from the library. Clicking selects the row, double-clicking adds
+/// its song to the playlist, and right-clicking shows the menu for the selected songs.
+/// `songs` are the songs of the list, in the order of the rows.
+#[must_use]
+pub fn song_row_interaction(
+    selection: &mut Selection,
+    response: Response,
+    index: usize,
+    songs: &[SongId],
+) -> Option<LibraryViewCommand> {
+    let mut command = None;
+    selection.handle_row_clicks(&response, index);
+    if response.double_clicked() {
+        command = songs
+            .get(index)
+            .map(|id| LibraryViewCommand::AddSongsToPlaylist(vec![*id]));
+    }
+    response.context_menu(|ui| {
+        let selected = || {
+            selection
+                .indices()
+                .filter_map(|index| songs.get(*index))
+                .cloned()
+                .collect()
+        };
+        if let Some(menu_command) = show_songs_menu(ui, selected) {
+            command = Some(menu_command);
+            ui.close_menu();
+        }
+    });
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selected(selection: &Selection) -> Vec<usize> {
+        selection.indices().cloned().collect()
+    }
+
+    #[test]
+    fn test_click() {
+        let mut selection = Selection::new();
+
+        assert!(selection.click(2, Modifiers::NONE));
+        assert!(!selection.click(5, Modifiers::SHIFT));
+        assert_eq!(selected(&selection), vec![2, 3, 4, 5]);
+
+        assert!(!selection.click(3, Modifiers::COMMAND));
+        assert_eq!(selected(&selection), vec![2, 4, 5]);
+
+        // The ctrl-clicked row becomes the anchor, ctrl keeps the rest of the selection.
+        assert!(!selection.click(0, Modifiers::COMMAND | Modifiers::SHIFT));
+        assert_eq!(selected(&selection), vec![0, 1, 2, 3, 4, 5]);
+
+        assert!(selection.click(1, Modifiers::NONE));
+        assert_eq!(selected(&selection), vec![1]);
+    }
+
+    #[test]
+    fn test_extend_to() {
+        let mut selection = Selection::new();
+        selection.extend_to(3, 4);
+        assert_eq!(selected(&selection), vec![3, 4]);
+        selection.extend_to(4, 1);
+        assert_eq!(selected(&selection), vec![1, 2, 3]);
+    }
+}
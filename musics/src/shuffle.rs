@@ -0,0 +1,320 @@
+use crate::library::{Library, Song, SongId};
+use crate::rating::MAX_RATING;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ShuffleMode {
+    /// Every order is equally likely.
+    Uniform,
+    /// Songs by the same artist are spread out as far as possible.
+    SpreadArtists,
+    /// Songs from the same album are spread out as far as possible.
+    SpreadAlbums,
+    /// Shuffles the albums, but keeps the songs of each album together and in track order.
+    Albums,
+    /// Songs that were played less often are more likely to come first.
+    FavorLessPlayed,
+    /// Songs that were played more often, and skipped less, are more likely to come first.
+    FavorMostPlayed,
+    /// Songs with more stars are more likely to come first.
+    FavorHigherRated,
+}
+
+impl ShuffleMode {
+    pub const ALL: [ShuffleMode; 7] = [
+        ShuffleMode::Uniform,
+        ShuffleMode::SpreadArtists,
+        ShuffleMode::SpreadAlbums,
+        ShuffleMode::Albums,
+        ShuffleMode::FavorLessPlayed,
+        ShuffleMode::FavorMostPlayed,
+        ShuffleMode::FavorHigherRated,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ShuffleMode::Uniform => "Random",
+            ShuffleMode::SpreadArtists => "Spread artists",
+            ShuffleMode::SpreadAlbums => "Spread albums",
+            ShuffleMode::Albums => "Shuffle albums",
+            ShuffleMode::FavorLessPlayed => "Favor less played",
+            ShuffleMode::FavorMostPlayed => "Favor most played",
+            ShuffleMode::FavorHigherRated => "Favor higher rated",
+        }
+    }
+}
+
+/// Returns a shuffled order for the given songs, as a list of indices into `songs`.
+pub fn shuffled_order(songs: &[SongId], library: &Library, mode: ShuffleMode) -> Vec<usize> {
+    match mode {
+        ShuffleMode::Uniform => {
+            let mut order: Vec<usize> = (0..songs.len()).collect();
+            fastrand::shuffle(&mut order);
+            order
+        }
+        ShuffleMode::SpreadArtists => spread_order(
+            &songs
+                .iter()
+                .map(|id| library.get_song(*id).and_then(|song| song.artist.as_ref()))
+                .collect::<Vec<_>>(),
+        ),
+        ShuffleMode::SpreadAlbums => spread_order(
+            &songs
+                .iter()
+                .map(|id| library.get_song(*id).and_then(album_key))
+                .collect::<Vec<_>>(),
+        ),
+        ShuffleMode::Albums => album_order(
+            &songs
+                .iter()
+                .map(|id| {
+                    library.get_song(*id).and_then(|song| {
+                        album_key(song).map(|album| (album, (song.disc_number, song.track_number)))
+                    })
+                })
+                .collect::<Vec<_>>(),
+        ),
+        ShuffleMode::FavorLessPlayed | ShuffleMode::FavorMostPlayed => weighted_order(
+            &songs
+                .iter()
+                .map(|id| {
+                    let stats = &library.get_song_data(*id).stats;
+                    let plays = f64::from(stats.play_count);
+                    let skips = f64::from(stats.skip_count);
+                    if mode == ShuffleMode::FavorLessPlayed {
+                        1.0 / (1.0 + plays)
+                    } else {
+                        (1.0 + plays) / (1.0 + skips)
+                    }
+                })
+                .collect::<Vec<_>>(),
+        ),
+        ShuffleMode::FavorHigherRated => weighted_order(
+            &songs
+                .iter()
+                .map(|id| rating_weight(library.get_song_data(*id).rating))
+                .collect::<Vec<_>>(),
+        ),
+    }
+}
+
+/// Albums of different artists can have the same title, like "Greatest Hits".
+fn album_key(song: &Song) -> Option<(Option<&String>, &String)> {
+    let artist = song.album_artist.as_ref().or(song.artist.as_ref());
+    song.album.as_ref().map(|album| (artist, album))
+}
+
+/// As likely as the number of stars. Unrated songs count as in the middle, so rating a song
+/// low makes it come later, not only rating others high.
+fn rating_weight(rating: u8) -> f64 {
+    match rating {
+        0 => f64::from(MAX_RATING + 1) / 2.0,
+        stars => f64::from(stars),
+    }
+}
+
+/// Weighted random order, by Efraimidis and Spirakis: "Weighted random sampling with a
+/// reservoir". A song with twice the weight is twice as likely to be picked first, and
+/// the same goes for every next place among the remaining songs.
+///
+/// Every song gets the key `u^(1 / weight)`, for a random `u` in `0..1`, and the songs
+/// are sorted by their key, largest first. Compares `ln(u) / weight` instead,
+/// which sorts the same, but doesn't round to 0 for small weights.
+fn weighted_order(weights: &[f64]) -> Vec<usize> {
+    let mut keyed: Vec<(f64, usize)> = weights
+        .iter()
+        .enumerate()
+        .map(|(index, weight)| {
+            // `1 - f64()` is in `0..=1` without 0, of which the logarithm is finite.
+            let u = 1.0 - fastrand::f64();
+            (u.ln() / weight.max(f64::MIN_POSITIVE), index)
+        })
+        .collect();
+
+    keyed.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+    keyed.into_iter().map(|(_, index)| index).collect()
+}
+
+/// "Balanced shuffle", as described by Martin Fiedler:
+/// https://keyj.emphy.de/balanced-shuffle/
+///
+/// Every group of songs with the same key is spread out evenly over the range `0..1`,
+/// starting at a random offset and with a bit of random jitter.
+/// Sorting all songs by their position then interleaves the groups.
+/// Songs without a key are each treated as their own group.
+fn spread_order<K: Hash + Eq>(keys: &[Option<K>]) -> Vec<usize> {
+    let mut groups: HashMap<&K, Vec<usize>> = HashMap::new();
+    let mut ungrouped = Vec::new();
+
+    for (index, key) in keys.iter().enumerate() {
+        match key {
+            Some(key) => groups.entry(key).or_default().push(index),
+            None => ungrouped.push(vec![index]),
+        }
+    }
+
+    let mut positioned: Vec<(f32, usize)> = Vec::with_capacity(keys.len());
+
+    for mut group in groups.into_values().chain(ungrouped) {
+        fastrand::shuffle(&mut group);
+
+        let spacing = 1. / group.len() as f32;
+        let offset = fastrand::f32() * spacing;
+
+        for (i, index) in group.into_iter().enumerate() {
+            // Jitter of at most 10% of the spacing, so that the groups don't interleave in
+            // exactly the same pattern every time, but songs also don't move past their
+            // neighbours from the same group.
+            let jitter = (fastrand::f32() - 0.5) * 0.2 * spacing;
+            positioned.push((offset + i as f32 * spacing + jitter, index));
+        }
+    }
+
+    positioned.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+    positioned.into_iter().map(|(_, index)| index).collect()
+}
+
+/// Shuffles the albums, while keeping the songs of each album together.
+/// Within an album, the songs are sorted by the given track position, and otherwise keep
+/// their original order.
+/// Songs without an album are each treated as their own album.
+fn album_order<K: Hash + Eq, T: Ord>(keys: &[Option<(K, T)>]) -> Vec<usize> {
+    let mut albums: HashMap<&K, Vec<usize>> = HashMap::new();
+    let mut loose_songs = Vec::new();
+
+    for (index, key) in keys.iter().enumerate() {
+        match key {
+            Some((album, _)) => albums.entry(album).or_default().push(index),
+            None => loose_songs.push(vec![index]),
+        }
+    }
+
+    let mut albums: Vec<Vec<usize>> = albums.into_values().chain(loose_songs).collect();
+    fastrand::shuffle(&mut albums);
+
+    for album in albums.iter_mut() {
+        // Stable sort, so songs with the same (or no) track number keep their order.
+        album.sort_by_key(|index| keys[*index].as_ref().map(|(_, track)| track));
+    }
+
+    albums.into_iter().flatten().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_is_permutation(order: &[usize], len: usize) {
+        let mut sorted = order.to_vec();
+        sorted.sort();
+        assert_eq!(sorted, (0..len).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_spread_interleaves_groups() {
+        let keys: Vec<Option<&str>> = [["a"; 10], ["b"; 10]]
+            .concat()
+            .into_iter()
+            .map(Some)
+            .collect();
+
+        for _ in 0..100 {
+            let order = spread_order(&keys);
+            assert_is_permutation(&order, keys.len());
+
+            // Two equally large groups can never have more than two of the same
+            // songs in a row.
+            for window in order.windows(3) {
+                let all_same = window.iter().all(|index| keys[*index] == keys[window[0]]);
+                assert!(!all_same, "{:?}", order);
+            }
+        }
+    }
+
+    #[test]
+    fn test_spread_without_keys() {
+        let keys: Vec<Option<&str>> = vec![None, Some("a"), None, Some("a"), None];
+        let order = spread_order(&keys);
+        assert_is_permutation(&order, keys.len());
+    }
+
+    #[test]
+    fn test_albums_keep_track_order() {
+        let keys = vec![
+            Some(("x", 3)),
+            Some(("y", 2)),
+            Some(("x", 1)),
+            None,
+            Some(("y", 1)),
+            Some(("x", 2)),
+        ];
+
+        for _ in 0..20 {
+            let order = album_order(&keys);
+            assert_is_permutation(&order, keys.len());
+
+            let x_songs: Vec<usize> = order
+                .iter()
+                .cloned()
+                .filter(|index| matches!(keys[*index], Some(("x", _))))
+                .collect();
+            assert_eq!(x_songs, vec![2, 5, 0]);
+
+            // The album should be kept together.
+            let first_x = order.iter().position(|index| *index == 2).unwrap();
+            assert_eq!(&order[first_x..first_x + 3], &[2, 5, 0]);
+
+            let first_y = order.iter().position(|index| *index == 4).unwrap();
+            assert_eq!(&order[first_y..first_y + 2], &[4, 1]);
+        }
+    }
+
+    #[test]
+    fn test_weighted_order() {
+        let weights = [1.0, 9.0, 0.0];
+        let mut heavy_first = 0;
+        for _ in 0..1000 {
+            let order = weighted_order(&weights);
+            assert_is_permutation(&order, weights.len());
+            // A weight of zero always comes last.
+            assert_eq!(order[2], 2);
+            if order[0] == 1 {
+                heavy_first += 1;
+            }
+        }
+        // Should be about 900.
+        assert!((820..980).contains(&heavy_first), "{}", heavy_first);
+    }
+
+    #[test]
+    fn test_rating_weight() {
+        assert_eq!(rating_weight(0), rating_weight(3));
+        assert!(rating_weight(1) < rating_weight(0));
+        assert!(rating_weight(5) > rating_weight(4));
+        assert_eq!(rating_weight(5) / rating_weight(1), 5.0);
+    }
+
+    #[test]
+    fn test_album_key() {
+        let hits = Song::named("a").with_artist("Someone");
+        let other_hits = Song::named("b").with_artist("Someone Else");
+        assert_ne!(
+            album_key(&hits.with_album("Greatest Hits")),
+            album_key(&other_hits.with_album("Greatest Hits"))
+        );
+
+        // Songs of a compilation have different artists, but the same album artist.
+        let compilation = Song::named("c")
+            .with_artist("Someone")
+            .with_album("Compilation")
+            .with_album_artist("Various Artists");
+        let other_compilation = Song::named("d")
+            .with_artist("Someone Else")
+            .with_album("Compilation")
+            .with_album_artist("Various Artists");
+        assert_eq!(album_key(&compilation), album_key(&other_compilation));
+
+        assert_eq!(album_key(&Song::default()), None);
+    }
+}
@@ -0,0 +1,180 @@
+use crate::library::{Library, SongId, SortKey};
+use crate::query;
+use serde_derive::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// A playlist that is made up of the songs that match a search, instead of picked songs.
+/// Its songs change along with the library.
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct SmartPlaylist {
+    pub name: String,
+    /// In the search query language, see [`query`].
+    pub query: String,
+    pub order: SmartOrder,
+    pub limit: Limit,
+    /// The random order stays the same until reshuffled, instead of changing every time
+    /// the library changes.
+    pub seed: u64,
+}
+
+impl Default for SmartPlaylist {
+    fn default() -> Self {
+        SmartPlaylist {
+            name: "New smart playlist".to_string(),
+            query: String::new(),
+            order: SmartOrder::BestMatch,
+            limit: Limit::None,
+            seed: 0,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub enum SmartOrder {
+    /// The order the search results are in.
+    BestMatch,
+    Random,
+    Ascending(SortKey),
+    Descending(SortKey),
+}
+
+impl SmartOrder {
+    pub fn name(&self) -> String {
+        match self {
+            SmartOrder::BestMatch => "Best match".to_string(),
+            SmartOrder::Random => "Random".to_string(),
+            SmartOrder::Ascending(key) => key.name().to_string(),
+            SmartOrder::Descending(key) => format!("{} (descending)", key.name()),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub enum Limit {
+    None,
+    Songs(usize),
+    /// Songs are added for as long as they fit.
+    Duration(Duration),
+}
+
+impl SmartPlaylist {
+    /// Orders and limits the songs found by searching for the query. The search is up to
+    /// the caller, so it can run in the background.
+    pub fn arrange(&self, library: &Library, mut songs: Vec<SongId>) -> Vec<SongId> {
+        match self.order {
+            SmartOrder::BestMatch => {}
+            SmartOrder::Random => fastrand::Rng::with_seed(self.seed).shuffle(&mut songs),
+            SmartOrder::Ascending(key) | SmartOrder::Descending(key) => {
+                songs.sort_by(|a, b| key.compare(library, *a, *b));
+                if let SmartOrder::Descending(_) = self.order {
+                    songs.reverse();
+                }
+            }
+        }
+
+        match self.limit {
+            Limit::None => {}
+            Limit::Songs(count) => songs.truncate(count),
+            Limit::Duration(limit) => {
+                let mut total = Duration::ZERO;
+                let fitting = songs
+                    .iter()
+                    .take_while(|id| {
+                        let duration = library
+                            .get_song(**id)
+                            .and_then(|song| song.duration)
+                            .unwrap_or_default();
+                        total += duration;
+                        total <= limit
+                    })
+                    .count();
+                songs.truncate(fitting);
+            }
+        }
+
+        songs
+    }
+
+    /// Whether the songs are picked or ordered by their data, like play stats, so they change
+    /// when the data does.
+    pub fn uses_song_data(&self) -> bool {
+        let ordered_by_data = match self.order {
+            SmartOrder::Ascending(key) | SmartOrder::Descending(key) => key.uses_song_data(),
+            SmartOrder::BestMatch | SmartOrder::Random => false,
+        };
+        ordered_by_data || query::parse(&self.query).is_ok_and(|query| query.uses_song_data())
+    }
+
+    /// Gives the songs a new random order.
+    pub fn reshuffle(&mut self) {
+        self.seed = fastrand::u64(..);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library::Song;
+    use crate::query::ParseError;
+    use crate::search::SearchResults;
+    use std::sync::atomic::AtomicBool;
+
+    fn songs(playlist: &SmartPlaylist, library: &Library) -> Result<Vec<SongId>, ParseError> {
+        let query = query::parse(&playlist.query)?;
+        let not_cancelled = AtomicBool::new(false);
+        let found = SearchResults::new(library, query, None, usize::MAX, &not_cancelled)
+            .unwrap()
+            .into_found();
+        Ok(playlist.arrange(library, found))
+    }
+
+    #[test]
+    fn test_songs() {
+        let mut library = Library::new();
+        let long = library.insert_song(
+            Song::named("Long")
+                .with_genre("jazz")
+                .with_duration(Duration::from_secs(60 * 60)),
+        );
+        let short = library.insert_song(
+            Song::named("Short")
+                .with_genre("jazz")
+                .with_duration(Duration::from_secs(30 * 60)),
+        );
+        let medium = library.insert_song(
+            Song::named("Medium")
+                .with_genre("jazz")
+                .with_duration(Duration::from_secs(45 * 60)),
+        );
+        library.insert_song(
+            Song::named("Loud")
+                .with_genre("metal")
+                .with_duration(Duration::from_secs(5 * 60)),
+        );
+
+        let mut playlist = SmartPlaylist {
+            query: "genre:jazz".to_string(),
+            order: SmartOrder::Ascending(SortKey::Duration),
+            ..Default::default()
+        };
+        assert_eq!(songs(&playlist, &library), Ok(vec![short, medium, long]));
+
+        playlist.order = SmartOrder::Descending(SortKey::Duration);
+        playlist.limit = Limit::Duration(Duration::from_secs(2 * 60 * 60));
+        assert_eq!(songs(&playlist, &library), Ok(vec![long, medium]));
+
+        playlist.limit = Limit::Songs(1);
+        assert_eq!(songs(&playlist, &library), Ok(vec![long]));
+
+        // The random order only changes when reshuffling.
+        playlist.order = SmartOrder::Random;
+        playlist.limit = Limit::None;
+        let shuffled = songs(&playlist, &library).unwrap();
+        assert_eq!(shuffled.len(), 3);
+        assert_eq!(songs(&playlist, &library).unwrap(), shuffled);
+
+        playlist.query = "genre:".to_string();
+        assert!(songs(&playlist, &library).is_err());
+    }
+}
@@ -0,0 +1,390 @@
+use crate::library::{Library, SongId, SortKey};
+use crate::library_search_view::{show_songs_menu, LibraryViewCommand};
+use crate::query;
+use crate::search::{BackgroundSearch, SearchResults};
+use crate::selection::{song_row_interaction, Selection};
+use crate::smart_playlist::{Limit, SmartOrder, SmartPlaylist};
+use eframe::egui;
+use eframe::egui::{Color32, Context, Ui, Widget};
+use std::time::Duration;
+
+/// The songs of a smart playlist, and what they are picked for.
+struct Evaluated {
+    library_version: u64,
+    /// Only when the smart playlist uses the data of songs.
+    data_version: Option<u64>,
+    definition: SmartPlaylist,
+    /// The error, if the query couldn't be parsed.
+    songs: Result<Vec<SongId>, String>,
+    /// Picks the songs for the library and definition above. Until it's done, `songs` are
+    /// the ones picked before, so the list doesn't empty out every time the library changes.
+    search: Option<BackgroundSearch>,
+}
+
+impl Evaluated {
+    fn is_searching(&self) -> bool {
+        self.search.is_some()
+    }
+}
+
+pub struct SmartPlaylistView {
+    /// For each smart playlist in the config, by index.
+    evaluated: Vec<Option<Evaluated>>,
+    /// The index of the smart playlist being edited, `None` for a new one, and its changes.
+    editing: Option<(Option<usize>, SmartPlaylist)>,
+    /// The songs of the smart playlist being edited.
+    editing_evaluated: Option<Evaluated>,
+    /// Indices of the selected songs of the open smart playlist.
+    selection: Selection,
+}
+
+pub enum SmartPlaylistViewCommand {
+    None,
+    /// Shows the songs of the smart playlist.
+    Open(usize),
+    Library(LibraryViewCommand),
+}
+
+impl SmartPlaylistView {
+    pub fn new() -> Self {
+        SmartPlaylistView {
+            evaluated: Vec::new(),
+            editing: None,
+            editing_evaluated: None,
+            selection: Selection::new(),
+        }
+    }
+
+    /// The songs of the smart playlist in the config at `index`.
+    fn songs(
+        &mut self,
+        ctx: &Context,
+        index: usize,
+        smart_playlist: &SmartPlaylist,
+        library: &Library,
+    ) -> &Evaluated {
+        if self.evaluated.len() <= index {
+            self.evaluated.resize_with(index + 1, || None);
+        }
+        evaluate(&mut self.evaluated[index], ctx, smart_playlist, library)
+    }
+
+    /// Lists the smart playlists, next to the other views.
+    #[must_use]
+    pub fn show_list(
+        &mut self,
+        ui: &mut Ui,
+        library: &Library,
+        smart_playlists: &mut Vec<SmartPlaylist>,
+        open_index: Option<usize>,
+    ) -> SmartPlaylistViewCommand {
+        let mut command = SmartPlaylistViewCommand::None;
+
+        ui.horizontal(|ui| {
+            ui.heading("Smart playlists");
+            if ui.button("+").on_hover_text("New smart playlist").clicked() {
+                self.edit(None, SmartPlaylist::default());
+            }
+        });
+
+        let mut removed = None;
+        for (index, smart_playlist) in smart_playlists.iter().enumerate() {
+            let evaluated = self.songs(ui.ctx(), index, smart_playlist, library);
+            let song_count = match &evaluated.songs {
+                Ok(_) if evaluated.is_searching() => "…".to_string(),
+                Ok(songs) => songs.len().to_string(),
+                Err(_) => "!".to_string(),
+            };
+            let text = format!("{} ({})", smart_playlist.name, song_count);
+
+            let response = egui::SelectableLabel::new(open_index == Some(index), text).ui(ui);
+            if response.clicked() {
+                self.selection.clear();
+                command = SmartPlaylistViewCommand::Open(index);
+            }
+            response.context_menu(|ui| {
+                if let Ok(songs) = &self.songs(ui.ctx(), index, smart_playlist, library).songs {
+                    if let Some(menu_command) = show_songs_menu(ui, || songs.clone()) {
+                        command = SmartPlaylistViewCommand::Library(menu_command);
+                        ui.close_menu();
+                    }
+                }
+                ui.separator();
+                if ui.button("Edit").clicked() {
+                    self.edit(Some(index), smart_playlist.clone());
+                    ui.close_menu();
+                }
+                if ui.button("Delete").clicked() {
+                    removed = Some(index);
+                    ui.close_menu();
+                }
+            });
+        }
+
+        if let Some(index) = removed {
+            smart_playlists.remove(index);
+            // The cached songs of the ones after it moved down as well.
+            if index < self.evaluated.len() {
+                self.evaluated.remove(index);
+            }
+            // So saving doesn't overwrite another smart playlist.
+            match &mut self.editing {
+                Some((Some(editing), _)) if *editing == index => {
+                    self.editing = None;
+                    self.editing_evaluated = None;
+                }
+                Some((Some(editing), _)) if *editing > index => *editing -= 1,
+                _ => {}
+            }
+        }
+
+        command
+    }
+
+    /// Shows the songs of the smart playlist.
+    #[must_use]
+    pub fn show_songs(
+        &mut self,
+        ui: &mut Ui,
+        library: &Library,
+        index: usize,
+        smart_playlist: &mut SmartPlaylist,
+    ) -> LibraryViewCommand {
+        let mut command = LibraryViewCommand::None;
+
+        let songs = match &self.songs(ui.ctx(), index, smart_playlist, library).songs {
+            Ok(songs) => songs.clone(),
+            Err(error) => {
+                ui.colored_label(Color32::LIGHT_RED, error);
+                Vec::new()
+            }
+        };
+
+        ui.horizontal(|ui| {
+            ui.heading(&smart_playlist.name);
+            let total_duration: Duration = songs
+                .iter()
+                .filter_map(|id| library.get_song(*id).and_then(|song| song.duration))
+                .sum();
+            ui.label(format!(
+                "{} songs, {}",
+                songs.len(),
+                crate::duration_to_time_display(total_duration)
+            ));
+        });
+        ui.horizontal(|ui| {
+            if ui
+                .button("Load")
+                .on_hover_text("Replaces the songs in the playlist with these.")
+                .clicked()
+            {
+                command = LibraryViewCommand::ReplacePlaylist(songs.clone());
+            }
+            if ui.button("Add to playlist").clicked() {
+                command = LibraryViewCommand::AddSongsToPlaylist(songs.clone());
+            }
+            if ui.button("Edit").clicked() {
+                self.edit(Some(index), smart_playlist.clone());
+            }
+            if smart_playlist.order == SmartOrder::Random && ui.button("Reshuffle").clicked() {
+                smart_playlist.reshuffle();
+            }
+        });
+
+        let row_height = ui.spacing().interact_size.y;
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .show_rows(ui, row_height, songs.len(), |ui, row_range| {
+                for index in row_range {
+                    let Some(song) = library.get_song(songs[index]) else {
+                        continue;
+                    };
+
+                    let text = match &song.artist {
+                        Some(artist) => format!("{} - {}", song.title, artist),
+                        None => song.title.clone(),
+                    };
+                    let response =
+                        egui::SelectableLabel::new(self.selection.contains(index), text).ui(ui);
+                    if let Some(row_command) =
+                        song_row_interaction(&mut self.selection, response, index, &songs)
+                    {
+                        command = row_command;
+                    }
+                }
+            });
+
+        command
+    }
+
+    /// Shows the window for creating or editing a smart playlist, when one is being edited.
+    pub fn show_editor(
+        &mut self,
+        ctx: &Context,
+        library: &Library,
+        smart_playlists: &mut Vec<SmartPlaylist>,
+    ) {
+        let Some((index, smart_playlist)) = &mut self.editing else {
+            return;
+        };
+
+        let mut open = true;
+        let mut done = false;
+        egui::Window::new("Smart playlist")
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("smart_playlist_grid")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Name");
+                        ui.text_edit_singleline(&mut smart_playlist.name);
+                        ui.end_row();
+
+                        ui.label("Songs matching");
+                        egui::TextEdit::singleline(&mut smart_playlist.query)
+                            .hint_text("Search query, like: genre:jazz year:>=1960")
+                            .desired_width(300.0)
+                            .show(ui);
+                        ui.end_row();
+
+                        ui.label("Order");
+                        show_order_picker(ui, &mut smart_playlist.order);
+                        ui.end_row();
+
+                        ui.label("Limit");
+                        show_limit_picker(ui, &mut smart_playlist.limit);
+                        ui.end_row();
+                    });
+
+                let evaluated = evaluate(&mut self.editing_evaluated, ctx, smart_playlist, library);
+                match &evaluated.songs {
+                    Ok(_) if evaluated.is_searching() => ui.label("Searching…"),
+                    Ok(songs) => ui.label(format!("{} songs", songs.len())),
+                    Err(error) => ui.colored_label(Color32::LIGHT_RED, error),
+                };
+
+                ui.horizontal(|ui| {
+                    if ui.button("Save").clicked() {
+                        match index {
+                            Some(index) if *index < smart_playlists.len() => {
+                                smart_playlists[*index] = smart_playlist.clone();
+                            }
+                            _ => smart_playlists.push(smart_playlist.clone()),
+                        }
+                        done = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        done = true;
+                    }
+                });
+            });
+
+        if done || !open {
+            self.editing = None;
+            self.editing_evaluated = None;
+        }
+    }
+
+    /// `index` is `None` for a new smart playlist.
+    fn edit(&mut self, index: Option<usize>, smart_playlist: SmartPlaylist) {
+        self.editing = Some((index, smart_playlist));
+        self.editing_evaluated = None;
+    }
+}
+
+/// Picks the songs again in the background, when the library or the smart playlist changed
+/// since last time. Asks for a repaint once they're picked.
+fn evaluate<'a>(
+    evaluated: &'a mut Option<Evaluated>,
+    ctx: &Context,
+    smart_playlist: &SmartPlaylist,
+    library: &Library,
+) -> &'a Evaluated {
+    let up_to_date = evaluated.as_ref().is_some_and(|evaluated| {
+        evaluated.library_version == library.version()
+            && evaluated
+                .data_version
+                .is_none_or(|version| version == library.data_version())
+            && evaluated.definition == *smart_playlist
+    });
+    if !up_to_date {
+        let (songs, search) = match query::parse(&smart_playlist.query) {
+            Ok(query) => {
+                let songs = match evaluated.take() {
+                    Some(Evaluated {
+                        songs: Ok(songs), ..
+                    }) => songs,
+                    _ => Vec::new(),
+                };
+                let search =
+                    BackgroundSearch::start(library.clone(), query, None, usize::MAX, ctx.clone());
+                (Ok(songs), Some(search))
+            }
+            Err(error) => (Err(error.to_string()), None),
+        };
+        *evaluated = Some(Evaluated {
+            library_version: library.version(),
+            data_version: smart_playlist
+                .uses_song_data()
+                .then(|| library.data_version()),
+            definition: smart_playlist.clone(),
+            songs,
+            search,
+        });
+    }
+
+    let evaluated = evaluated.as_mut().expect("Was just evaluated");
+    let found = evaluated
+        .search
+        .as_ref()
+        .and_then(|search| search.try_take_results())
+        .map(SearchResults::into_found);
+    if let Some(found) = found {
+        evaluated.songs = Ok(evaluated.definition.arrange(library, found));
+        evaluated.search = None;
+    }
+    evaluated
+}
+
+fn show_order_picker(ui: &mut Ui, order: &mut SmartOrder) {
+    egui::ComboBox::from_id_source("smart_playlist_order")
+        .selected_text(order.name())
+        .show_ui(ui, |ui| {
+            let orders = [SmartOrder::BestMatch, SmartOrder::Random]
+                .into_iter()
+                .chain(SortKey::ALL.into_iter().map(SmartOrder::Ascending))
+                .chain(SortKey::ALL.into_iter().map(SmartOrder::Descending));
+            for option in orders {
+                let name = option.name();
+                ui.selectable_value(order, option, name);
+            }
+        });
+}
+
+fn show_limit_picker(ui: &mut Ui, limit: &mut Limit) {
+    ui.horizontal(|ui| {
+        ui.selectable_value(limit, Limit::None, "None");
+        // Clicking the kind of limit that is picked already keeps its value.
+        let songs = matches!(limit, Limit::Songs(_));
+        if ui.selectable_label(songs, "Songs").clicked() && !songs {
+            *limit = Limit::Songs(25);
+        }
+        let minutes = matches!(limit, Limit::Duration(_));
+        if ui.selectable_label(minutes, "Minutes").clicked() && !minutes {
+            *limit = Limit::Duration(Duration::from_secs(60 * 60));
+        }
+
+        match limit {
+            Limit::None => {}
+            Limit::Songs(count) => {
+                ui.add(egui::DragValue::new(count).clamp_range(1..=100_000));
+            }
+            Limit::Duration(duration) => {
+                let mut minutes = duration.as_secs() / 60;
+                ui.add(egui::DragValue::new(&mut minutes).clamp_range(1..=100_000));
+                *duration = Duration::from_secs(minutes * 60);
+            }
+        }
+    });
+}
@@ -0,0 +1,199 @@
+//! What the player knows about songs, besides what is in their files.
+//! Saved under the path of the song, which stays the same between runs, unlike [`SongId`]s.
+
+use crate::library::{Library, SongId};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::time::{Duration, Instant, SystemTime};
+
+/// A song counts as played once half of it was listened to, or this much of it.
+const PLAY_THRESHOLD: Duration = Duration::from_secs(4 * 60);
+
+#[derive(Deserialize, Serialize, Clone, Default, PartialEq, Debug)]
+#[serde(default)]
+pub struct SongData {
+    pub stats: PlayStats,
+    /// From 1 to [`MAX_RATING`](crate::rating::MAX_RATING) stars, 0 when it isn't rated.
+    pub rating: u8,
+    pub favourite: bool,
+    /// The user's own tags, see [`labels`](crate::labels).
+    pub labels: BTreeSet<String>,
+}
+
+/// The data of songs that don't have any.
+pub static EMPTY_SONG_DATA: SongData = SongData {
+    stats: PlayStats {
+        play_count: 0,
+        skip_count: 0,
+        first_played: None,
+        last_played: None,
+        listening_time: Duration::ZERO,
+    },
+    rating: 0,
+    favourite: false,
+    labels: BTreeSet::new(),
+};
+
+impl SongData {
+    /// Empty data doesn't have to be saved.
+    pub fn is_empty(&self) -> bool {
+        *self == SongData::default()
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Default, PartialEq, Debug)]
+#[serde(default)]
+pub struct PlayStats {
+    pub play_count: u32,
+    /// How often the song was skipped before it counted as played.
+    pub skip_count: u32,
+    pub first_played: Option<SystemTime>,
+    pub last_played: Option<SystemTime>,
+    /// Includes the times it was listened to too short to count as played.
+    pub listening_time: Duration,
+}
+
+impl PlayStats {
+    /// Never played songs were last played infinitely long ago.
+    pub fn days_since_last_played(&self, now: SystemTime) -> u64 {
+        self.last_played
+            .map(|last_played| {
+                now.duration_since(last_played)
+                    .unwrap_or_default()
+                    .as_secs()
+                    / (24 * 60 * 60)
+            })
+            .unwrap_or(u64::MAX)
+    }
+}
+
+/// Keeps track of how long the current song is listened to, and updates its stats.
+pub struct Listening {
+    song: SongId,
+    /// How long the song was listened to since it started playing.
+    listened: Duration,
+    /// Listening time that isn't added to the stats yet. Saving it every frame would
+    /// change the library every frame.
+    unsaved: Duration,
+    counted_as_played: bool,
+    last_update: Instant,
+}
+
+impl Listening {
+    pub fn start(song: SongId) -> Self {
+        Listening {
+            song,
+            listened: Duration::ZERO,
+            unsaved: Duration::ZERO,
+            counted_as_played: false,
+            last_update: Instant::now(),
+        }
+    }
+
+    pub fn song(&self) -> SongId {
+        self.song
+    }
+
+    /// Should be called regularly. Counts the time since the last update as listened,
+    /// if the song was playing. Time spent seeking doesn't count, only real time.
+    pub fn update(&mut self, playing: bool, library: &mut Library) {
+        let now = Instant::now();
+        if playing {
+            self.listen(now - self.last_update, library);
+        }
+        self.last_update = now;
+    }
+
+    fn listen(&mut self, time: Duration, library: &mut Library) {
+        self.listened += time;
+        self.unsaved += time;
+
+        let duration = library
+            .get_song(self.song)
+            .and_then(|song| song.duration)
+            .unwrap_or(PLAY_THRESHOLD * 2);
+        let threshold = (duration / 2).min(PLAY_THRESHOLD);
+
+        if !self.counted_as_played && self.listened >= threshold {
+            self.counted_as_played = true;
+            let now = SystemTime::now();
+            library.update_song_data(self.song, |data| {
+                data.stats.play_count += 1;
+                data.stats.first_played.get_or_insert(now);
+                data.stats.last_played = Some(now);
+            });
+            self.save(library);
+        }
+    }
+
+    /// Adds the listening time to the stats.
+    pub fn save(&mut self, library: &mut Library) {
+        if self.unsaved.is_zero() {
+            return;
+        }
+        let unsaved = std::mem::take(&mut self.unsaved);
+        library.update_song_data(self.song, |data| data.stats.listening_time += unsaved);
+    }
+
+    /// Call when the song stops playing. `skipped` when the user moved on to the next song.
+    /// Doesn't update first, because the song could have been paused since the last update.
+    pub fn finish(mut self, skipped: bool, library: &mut Library) {
+        self.save(library);
+        if skipped && !self.counted_as_played {
+            library.update_song_data(self.song, |data| data.stats.skip_count += 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library::Song;
+
+    fn stats(library: &Library, id: SongId) -> PlayStats {
+        library.get_song_data(id).stats.clone()
+    }
+
+    #[test]
+    fn test_listening() {
+        let mut library = Library::new();
+        let short =
+            library.insert_song(Song::named("Short").with_duration(Duration::from_secs(4 * 60)));
+        let long =
+            library.insert_song(Song::named("Long").with_duration(Duration::from_secs(20 * 60)));
+        let minute = Duration::from_secs(60);
+        let version = library.version();
+        let data_version = library.data_version();
+
+        // Half of a short song counts as played.
+        let mut listening = Listening::start(short);
+        listening.listen(minute, &mut library);
+        assert_eq!(stats(&library, short).play_count, 0);
+        listening.listen(minute, &mut library);
+        assert_eq!(stats(&library, short).play_count, 1);
+        assert!(stats(&library, short).last_played.is_some());
+        listening.listen(minute, &mut library);
+        listening.save(&mut library);
+        assert_eq!(stats(&library, short).play_count, 1);
+        assert_eq!(stats(&library, short).listening_time, minute * 3);
+
+        // Skipped before counting as played.
+        let mut listening = Listening::start(long);
+        listening.listen(minute * 3, &mut library);
+        listening.finish(true, &mut library);
+        assert_eq!(stats(&library, long).skip_count, 1);
+        assert_eq!(stats(&library, long).play_count, 0);
+
+        // Long songs count as played after four minutes.
+        let mut listening = Listening::start(long);
+        listening.listen(minute * 4, &mut library);
+        listening.finish(true, &mut library);
+        assert_eq!(stats(&library, long).skip_count, 1);
+        assert_eq!(stats(&library, long).play_count, 1);
+        assert!(stats(&library, long).listening_time >= minute * 7);
+
+        // Only the data changed, not the songs.
+        assert_eq!(library.version(), version);
+        assert!(library.data_version() > data_version);
+    }
+}
@@ -0,0 +1,357 @@
+//! Editing the tags of one or more songs, and writing them to their files.
+//! Lofty takes care of the tag format: ID3v2.4 for mp3, Vorbis comments for ogg and flac,
+//! and MP4 atoms for m4a.
+
+use crate::library::Song;
+use camino::Utf8Path;
+use lofty::aac::AACFile;
+use lofty::id3::v2::{Frame, FrameValue, ID3v2Tag};
+use lofty::iff::aiff::AiffFile;
+use lofty::iff::wav::WavFile;
+use lofty::mpeg::MPEGFile;
+use lofty::{Accessor, AudioFile, FileType, ParseOptions, Probe, Tag, TagExt, TaggedFileExt};
+use std::fs::File;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TagField {
+    Title,
+    Artist,
+    Album,
+    Genre,
+    Year,
+    Track,
+    Disc,
+}
+
+impl TagField {
+    pub const ALL: [TagField; 7] = [
+        TagField::Title,
+        TagField::Artist,
+        TagField::Album,
+        TagField::Genre,
+        TagField::Year,
+        TagField::Track,
+        TagField::Disc,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TagField::Title => "Title",
+            TagField::Artist => "Artist",
+            TagField::Album => "Album",
+            TagField::Genre => "Genre",
+            TagField::Year => "Year",
+            TagField::Track => "Track",
+            TagField::Disc => "Disc",
+        }
+    }
+
+    /// How the field is written in filename patterns, like `%title%`.
+    pub fn key(&self) -> &'static str {
+        match self {
+            TagField::Title => "title",
+            TagField::Artist => "artist",
+            TagField::Album => "album",
+            TagField::Genre => "genre",
+            TagField::Year => "year",
+            TagField::Track => "track",
+            TagField::Disc => "disc",
+        }
+    }
+
+    pub fn is_number(&self) -> bool {
+        matches!(self, TagField::Year | TagField::Track | TagField::Disc)
+    }
+
+    /// Empty when the song doesn't have it.
+    fn value(&self, song: &Song) -> String {
+        let number = |number: Option<u32>| number.map(|n| n.to_string()).unwrap_or_default();
+        match self {
+            TagField::Title => song.title.clone(),
+            TagField::Artist => song.artist.clone().unwrap_or_default(),
+            TagField::Album => song.album.clone().unwrap_or_default(),
+            TagField::Genre => song.genre.clone().unwrap_or_default(),
+            TagField::Year => number(song.year),
+            TagField::Track => number(song.track_number),
+            TagField::Disc => number(song.disc_number),
+        }
+    }
+
+    /// `value` is already checked, see [`TagEdit::check`]. Empty values remove the field.
+    fn write(&self, tag: &mut impl Accessor, value: &str) {
+        let number = value.trim().parse::<u32>().ok();
+        let value = value.to_string();
+        match (self, value.is_empty()) {
+            (TagField::Title, true) => tag.remove_title(),
+            (TagField::Title, false) => tag.set_title(value),
+            (TagField::Artist, true) => tag.remove_artist(),
+            (TagField::Artist, false) => tag.set_artist(value),
+            (TagField::Album, true) => tag.remove_album(),
+            (TagField::Album, false) => tag.set_album(value),
+            (TagField::Genre, true) => tag.remove_genre(),
+            (TagField::Genre, false) => tag.set_genre(value),
+            (TagField::Year, _) => match number {
+                Some(year) => tag.set_year(year),
+                None => tag.remove_year(),
+            },
+            (TagField::Track, _) => match number {
+                Some(track) => tag.set_track(track),
+                None => tag.remove_track(),
+            },
+            (TagField::Disc, _) => match number {
+                Some(disc) => tag.set_disk(disc),
+                None => tag.remove_disk(),
+            },
+        }
+    }
+}
+
+/// A field of the songs being edited.
+#[derive(Clone, PartialEq, Debug)]
+pub struct FieldEdit {
+    pub field: TagField,
+    /// `None` when the songs have different values.
+    pub original: Option<String>,
+    /// `None` keeps the value each song has.
+    pub edited: Option<String>,
+}
+
+impl FieldEdit {
+    pub fn is_changed(&self) -> bool {
+        self.edited.is_some() && self.edited != self.original
+    }
+}
+
+/// The changes to the tags of one or more songs.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TagEdit {
+    pub fields: Vec<FieldEdit>,
+}
+
+impl TagEdit {
+    /// Fields start with the value all songs have. Fields where they differ are kept.
+    pub fn from_songs(songs: &[&Song]) -> Self {
+        let fields = TagField::ALL
+            .into_iter()
+            .map(|field| {
+                let mut values = songs.iter().map(|song| field.value(song));
+                let first = values.next().unwrap_or_default();
+                let original = values.all(|value| value == first).then_some(first);
+                FieldEdit {
+                    field,
+                    edited: original.clone(),
+                    original,
+                }
+            })
+            .collect();
+        TagEdit { fields }
+    }
+
+    pub fn is_changed(&self) -> bool {
+        self.fields.iter().any(FieldEdit::is_changed)
+    }
+
+    /// Changes the field for all songs.
+    pub fn set(&mut self, field: TagField, value: &str) {
+        if let Some(field_edit) = self.fields.iter_mut().find(|edit| edit.field == field) {
+            field_edit.edited = Some(value.to_string());
+        }
+    }
+
+    /// Explains what is wrong with the first field that can't be written.
+    pub fn check(&self) -> Result<(), String> {
+        for field in &self.fields {
+            let Some(value) = &field.edited else {
+                continue;
+            };
+            if field.field.is_number()
+                && !value.trim().is_empty()
+                && value.trim().parse::<u32>().is_err()
+            {
+                return Err(format!(
+                    "{} has to be a number, not \"{}\".",
+                    field.field.name(),
+                    value
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the changed fields to the main tag of the file, creating it if needed.
+    /// Unchanged fields are left as they are, as well as everything the editor doesn't know.
+    pub fn write_to_file(&self, path: &Utf8Path) -> lofty::Result<()> {
+        if let Some(mut tag) = read_id3v2(path)? {
+            self.write_fields(&mut tag);
+            return tag.save_to_path(path);
+        }
+
+        let mut tagged_file = lofty::read_from_path(path)?;
+        if tagged_file.primary_tag().is_none() {
+            tagged_file.insert_tag(Tag::new(tagged_file.primary_tag_type()));
+        }
+        let Some(tag) = tagged_file.primary_tag_mut() else {
+            // The file type doesn't support tags at all.
+            return Ok(());
+        };
+
+        self.write_fields(tag);
+        tag.save_to_path(path)
+    }
+
+    fn write_fields(&self, tag: &mut impl Accessor) {
+        for field in self.fields.iter().filter(|field| field.is_changed()) {
+            if let Some(value) = &field.edited {
+                field.field.write(tag, value);
+            }
+        }
+    }
+}
+
+/// The ID3v2 tag of the file, or a new one when it has none yet. `None` when the main tag
+/// of the file type isn't ID3v2.
+/// Lofty's generic [`Tag`] can't hold everything an ID3v2 tag can, like comments and lyrics
+/// with a description, so saving it would lose those. Editing this tag keeps them.
+pub fn read_id3v2(path: &Utf8Path) -> lofty::Result<Option<ID3v2Tag>> {
+    let file_type = Probe::open(path)?.guess_file_type()?.file_type();
+    let file = &mut File::open(path)?;
+    let options = ParseOptions::new().read_properties(false);
+    let tag = match file_type {
+        Some(FileType::MPEG) => MPEGFile::read_from(file, options)?.remove_id3v2(),
+        Some(FileType::AAC) => AACFile::read_from(file, options)?.remove_id3v2(),
+        Some(FileType::AIFF) => AiffFile::read_from(file, options)?.remove_id3v2(),
+        Some(FileType::WAV) => WavFile::read_from(file, options)?.remove_id3v2(),
+        _ => return Ok(None),
+    };
+    let mut tag = tag.unwrap_or_default();
+
+    // Lofty reads POPM frames, but refuses to write them back, so they're kept as they were read.
+    let popm_frames: Vec<Frame<'static>> = (&tag)
+        .into_iter()
+        .filter_map(|frame| match frame.content() {
+            FrameValue::Popularimeter(popm) => Frame::new(
+                frame.id_str().to_string(),
+                FrameValue::Binary(popm.as_bytes()),
+                *frame.flags(),
+            )
+            .ok(),
+            _ => None,
+        })
+        .collect();
+    tag.retain(|frame| !matches!(frame.content(), FrameValue::Popularimeter(_)));
+    for frame in popm_frames {
+        tag.insert(frame);
+    }
+    Ok(Some(tag))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library::{Library, SongId};
+    use crate::test_util::TempDir;
+    use lofty::id3::v2::{FrameFlags, LanguageFrame};
+    use lofty::TextEncoding;
+
+    #[test]
+    fn test_edit_songs() {
+        // Writes to copies, so the example files stay the same.
+        let directory = TempDir::new("tag_editor");
+        for name in [
+            "blank_holes_snippet.ogg",
+            "subfolder/dark_mystery_snippet.mp3",
+        ] {
+            let example = Utf8Path::new("../example_audio").join(name);
+            std::fs::copy(
+                &example,
+                directory.path().join(example.file_name().unwrap()),
+            )
+            .unwrap();
+        }
+
+        let mut library = Library::new();
+        library.insert_from_directory(directory.path());
+        let ids: Vec<SongId> = library.songs().map(|(id, _)| id).collect();
+        assert_eq!(ids.len(), 2);
+
+        let songs: Vec<&Song> = ids
+            .iter()
+            .map(|id| library.get_song(*id).unwrap())
+            .collect();
+        let mut edit = TagEdit::from_songs(&songs);
+        let title = edit.fields.iter().find(|f| f.field == TagField::Title);
+        assert_eq!(title.unwrap().original, None);
+        assert!(!edit.is_changed());
+
+        edit.set(TagField::Year, "twenty");
+        assert!(edit.check().is_err());
+        edit.set(TagField::Year, "1999");
+        edit.set(TagField::Artist, "Someone Else");
+        edit.set(TagField::Genre, "");
+        assert!(edit.check().is_ok());
+
+        let titles: Vec<String> = songs.iter().map(|song| song.title.clone()).collect();
+        for id in &ids {
+            let path = library.get_song(*id).unwrap().path.clone();
+            edit.write_to_file(&path).unwrap();
+            library.reload_song(*id);
+        }
+
+        for (id, title) in ids.iter().zip(titles) {
+            let song = library.get_song(*id).unwrap();
+            assert_eq!(song.title, title);
+            assert_eq!(song.artist.as_deref(), Some("Someone Else"));
+            assert_eq!(song.year, Some(1999));
+            assert_eq!(song.genre, None);
+        }
+    }
+
+    #[test]
+    fn test_keep_other_frames() {
+        let directory = TempDir::new("tag_editor_comments");
+        let path = directory.path().join("song.mp3");
+        std::fs::copy("../example_audio/subfolder/dark_mystery_snippet.mp3", &path).unwrap();
+
+        // Lofty's generic tag only knows comments without a description.
+        let comment = LanguageFrame {
+            encoding: TextEncoding::UTF8,
+            language: *b"eng",
+            description: "Mood".to_string(),
+            content: "Gloomy".to_string(),
+        };
+        let mut tag = read_id3v2(&path).unwrap().unwrap();
+        tag.insert(
+            Frame::new(
+                "COMM",
+                FrameValue::Comment(comment.clone()),
+                FrameFlags::default(),
+            )
+            .unwrap(),
+        );
+        // Rated by another player.
+        let popm = b"someone@example.com\0\xc8\0\0\0\x07".to_vec();
+        tag.insert(Frame::new("POPM", FrameValue::Binary(popm), FrameFlags::default()).unwrap());
+        tag.save_to_path(&path).unwrap();
+
+        let song = Song::named("song").with_path(path.as_str());
+        let mut edit = TagEdit::from_songs(&[&song]);
+        edit.set(TagField::Title, "Another title");
+        edit.write_to_file(&path).unwrap();
+
+        let tag = read_id3v2(&path).unwrap().unwrap();
+        assert_eq!(tag.title().as_deref(), Some("Another title"));
+        let kept = (&tag).into_iter().any(|frame| match frame.content() {
+            FrameValue::Comment(kept) => {
+                kept.description == comment.description && kept.content == comment.content
+            }
+            _ => false,
+        });
+        assert!(kept);
+        assert!(tag.get("POPM").is_some());
+
+        assert_eq!(
+            read_id3v2(Utf8Path::new("../example_audio/blank_holes_snippet.ogg")).unwrap(),
+            None
+        );
+    }
+}
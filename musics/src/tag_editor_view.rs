@@ -0,0 +1,240 @@
+use crate::filename_pattern::FilenamePattern;
+use crate::library::{Library, Song, SongId};
+use crate::tag_editor::TagEdit;
+use eframe::egui;
+use eframe::egui::{Color32, Context, RichText, Ui};
+
+pub struct TagEditorView {
+    /// The songs being edited, and the changes to them.
+    editing: Option<(Vec<SongId>, TagEdit)>,
+    /// Why the last save didn't work, for some or all songs.
+    errors: Vec<String>,
+    /// To fill in the tags from the file names, see [`FilenamePattern`].
+    filename_pattern: String,
+}
+
+impl TagEditorView {
+    pub fn new() -> Self {
+        TagEditorView {
+            editing: None,
+            errors: Vec::new(),
+            filename_pattern: String::new(),
+        }
+    }
+
+    /// Starts editing the songs, instead of the ones that were being edited.
+    pub fn open(&mut self, library: &Library, songs: Vec<SongId>) {
+        let song_refs: Vec<&Song> = songs
+            .iter()
+            .filter_map(|id| library.get_song(*id))
+            .collect();
+        if song_refs.is_empty() {
+            return;
+        }
+        let edit = TagEdit::from_songs(&song_refs);
+        self.editing = Some((songs, edit));
+        self.errors.clear();
+    }
+
+    /// Saving writes the tags to the files, and reads the songs into the library again.
+    pub fn show(&mut self, ctx: &Context, library: &mut Library) {
+        let Some((songs, edit)) = &mut self.editing else {
+            return;
+        };
+
+        let mut open = true;
+        let mut done = false;
+        let mut applied_pattern = false;
+        let title = match songs.len() {
+            1 => "Edit tags".to_string(),
+            count => format!("Edit tags of {} songs", count),
+        };
+        egui::Window::new(title)
+            .id(egui::Id::new("tag_editor"))
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("tag_editor_grid")
+                    .num_columns(3)
+                    .show(ui, |ui| {
+                        for field in &mut edit.fields {
+                            ui.label(field.field.name());
+
+                            // Typing in a kept field changes it for all songs.
+                            let mut text = field.edited.clone().unwrap_or_default();
+                            let hint = if field.edited.is_none() { "keep" } else { "" };
+                            let response = egui::TextEdit::singleline(&mut text)
+                                .hint_text(hint)
+                                .desired_width(300.0)
+                                .show(ui)
+                                .response;
+                            if response.changed() {
+                                field.edited = Some(text);
+                            }
+
+                            // Only fields that differ between the songs can be kept.
+                            if field.original.is_none() {
+                                let mut keep = field.edited.is_none();
+                                if ui
+                                    .checkbox(&mut keep, "Keep")
+                                    .on_hover_text("The songs have different values, keep them.")
+                                    .changed()
+                                {
+                                    field.edited = if keep { None } else { Some(String::new()) };
+                                }
+                            }
+                            ui.end_row();
+                        }
+                    });
+
+                egui::CollapsingHeader::new("From file names").show(ui, |ui| {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.filename_pattern)
+                            .hint_text("%artist% - %album%/%track% %title%")
+                            .desired_width(400.0),
+                    );
+                    if self.filename_pattern.is_empty() {
+                        return;
+                    }
+                    match FilenamePattern::parse(&self.filename_pattern) {
+                        Ok(pattern) => {
+                            let matching = show_pattern_preview(ui, library, songs, &pattern);
+                            let text = format!("Apply to {} songs", matching);
+                            if ui
+                                .add_enabled(matching > 0, egui::Button::new(text))
+                                .on_hover_text("Writes the tags to the files right away.")
+                                .clicked()
+                            {
+                                self.errors = apply_pattern(library, songs, &pattern);
+                                applied_pattern = true;
+                            }
+                        }
+                        Err(error) => {
+                            ui.colored_label(Color32::LIGHT_RED, error);
+                        }
+                    }
+                });
+
+                if let Err(error) = edit.check() {
+                    ui.colored_label(Color32::LIGHT_RED, error);
+                }
+                for error in &self.errors {
+                    ui.colored_label(Color32::LIGHT_RED, error);
+                }
+
+                ui.horizontal(|ui| {
+                    let can_save = edit.is_changed() && edit.check().is_ok();
+                    if ui
+                        .add_enabled(can_save, egui::Button::new("Save"))
+                        .clicked()
+                    {
+                        self.errors = save(library, songs, edit);
+                        done = self.errors.is_empty();
+                    }
+                    if ui.button("Cancel").clicked() {
+                        done = true;
+                    }
+                });
+            });
+
+        if done || !open {
+            self.editing = None;
+        } else if applied_pattern {
+            // The songs changed, so the fields have to start from their new values.
+            let errors = std::mem::take(&mut self.errors);
+            let songs = songs.clone();
+            self.open(library, songs);
+            self.errors = errors;
+        }
+    }
+}
+
+/// Shows what each song gets from its file name. Returns how many songs match the pattern.
+fn show_pattern_preview(
+    ui: &mut Ui,
+    library: &Library,
+    songs: &[SongId],
+    pattern: &FilenamePattern,
+) -> usize {
+    let fields = pattern.fields();
+    let mut matching = 0;
+
+    egui::ScrollArea::both().max_height(200.0).show(ui, |ui| {
+        egui::Grid::new("filename_pattern_preview")
+            .striped(true)
+            .num_columns(fields.len() + 1)
+            .show(ui, |ui| {
+                ui.strong("File");
+                for field in &fields {
+                    ui.strong(field.name());
+                }
+                ui.end_row();
+
+                for song in songs.iter().filter_map(|id| library.get_song(*id)) {
+                    ui.label(song.path.file_name().unwrap_or_default());
+                    match pattern.extract(&song.path) {
+                        Some(values) => {
+                            matching += 1;
+                            for (_, value) in values {
+                                ui.label(value);
+                            }
+                        }
+                        None => {
+                            ui.label(RichText::new("Doesn't match").weak());
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+    });
+
+    matching
+}
+
+/// Writes the tags from the file names of the songs that match the pattern.
+/// Returns what went wrong, for each song that couldn't be saved.
+fn apply_pattern(
+    library: &mut Library,
+    songs: &[SongId],
+    pattern: &FilenamePattern,
+) -> Vec<String> {
+    let mut errors = Vec::new();
+    for id in songs {
+        let Some(song) = library.get_song(*id) else {
+            continue;
+        };
+        let Some(values) = pattern.extract(&song.path) else {
+            continue;
+        };
+
+        let mut edit = TagEdit::from_songs(&[song]);
+        for (field, value) in values.iter().filter(|(_, value)| !value.is_empty()) {
+            edit.set(*field, value);
+        }
+        let path = song.path.clone();
+        if let Err(error) = edit.check() {
+            errors.push(format!("Could not save {}: {}", path, error));
+            continue;
+        }
+        match edit.write_to_file(&path) {
+            Ok(()) => library.reload_song(*id),
+            Err(error) => errors.push(format!("Could not save {}: {}", path, error)),
+        }
+    }
+    errors
+}
+
+/// Returns what went wrong, for each song that couldn't be saved.
+fn save(library: &mut Library, songs: &[SongId], edit: &TagEdit) -> Vec<String> {
+    let mut errors = Vec::new();
+    for id in songs {
+        let Some(path) = library.get_song(*id).map(|song| song.path.clone()) else {
+            continue;
+        };
+        match edit.write_to_file(&path) {
+            Ok(()) => library.reload_song(*id),
+            Err(error) => errors.push(format!("Could not save {}: {}", path, error)),
+        }
+    }
+    errors
+}
@@ -0,0 +1,29 @@
+//! What the tests of several modules need.
+
+use camino::{Utf8Path, Utf8PathBuf};
+
+/// A new directory in the temp directory, removed again with everything in it when it's
+/// dropped. Also when the test fails halfway.
+pub struct TempDir {
+    path: Utf8PathBuf,
+}
+
+impl TempDir {
+    /// The name is part of the directory name, to tell which test left it if removing fails.
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("musics_{}_{}", name, fastrand::u64(..)));
+        let path = Utf8PathBuf::from_path_buf(path).expect("Temp dir is not a utf-8 path");
+        std::fs::create_dir_all(&path).expect("Could not create temp dir");
+        TempDir { path }
+    }
+
+    pub fn path(&self) -> &Utf8Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
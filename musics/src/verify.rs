@@ -0,0 +1,287 @@
+//! Decodes every song from start to end, to find broken files before they cut off
+//! in the middle of a song.
+
+use crate::duration_to_time_display;
+use crate::history::csv_field;
+use crate::library::{Library, SongId};
+use camino::Utf8PathBuf;
+use eframe::egui;
+use sound::{DecodeSummary, DecoderError, PlayError};
+use std::panic;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// How much the decoded length can differ from what the header says, before it's a problem.
+/// Headers of variable bitrate mp3s are often a bit off.
+const DURATION_TOLERANCE: Duration = Duration::from_secs(1);
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Problem {
+    /// The file can't be opened.
+    Unreadable(String),
+    /// The format or codec isn't supported.
+    Unsupported,
+    /// The file is supported, but decoding can't even start.
+    Undecodable(String),
+    /// How many packets couldn't be decoded, and are skipped when playing.
+    DecodeErrors(usize),
+    StoppedEarly(String),
+    NoAudio,
+    /// There is less audio than the header says, usually because the file was cut off.
+    Truncated {
+        decoded: Duration,
+        header: Duration,
+    },
+    LongerThanHeader {
+        decoded: Duration,
+        header: Duration,
+    },
+}
+
+impl Problem {
+    pub fn description(&self) -> String {
+        match self {
+            Problem::Unreadable(error) => format!("Can't be read: {}", error),
+            Problem::Unsupported => "Unsupported format or codec".to_string(),
+            Problem::Undecodable(error) => format!("Can't be decoded: {}", error),
+            Problem::DecodeErrors(count) => format!("{} packets can't be decoded", count),
+            Problem::StoppedEarly(error) => format!("Decoding stopped early: {}", error),
+            Problem::NoAudio => "No audio".to_string(),
+            Problem::Truncated { decoded, header } => format!(
+                "Cut off at {} of {}",
+                duration_to_time_display(*decoded),
+                duration_to_time_display(*header)
+            ),
+            Problem::LongerThanHeader { decoded, header } => format!(
+                "Plays {}, but the header says {}",
+                duration_to_time_display(*decoded),
+                duration_to_time_display(*header)
+            ),
+        }
+    }
+}
+
+/// What is wrong with the file, from decoding all of it.
+pub fn problems(result: Result<DecodeSummary, PlayError>) -> Vec<Problem> {
+    let summary = match result {
+        Ok(summary) => summary,
+        Err(PlayError::Open(error)) => return vec![Problem::Unreadable(error.to_string())],
+        Err(PlayError::Decode(DecoderError::UnrecognizedFormat)) => {
+            return vec![Problem::Unsupported]
+        }
+        Err(PlayError::Decode(error)) => return vec![Problem::Undecodable(error.to_string())],
+    };
+
+    let mut problems = Vec::new();
+    if summary.decode_errors > 0 {
+        problems.push(Problem::DecodeErrors(summary.decode_errors));
+    }
+    if let Some(error) = summary.stopped_early {
+        problems.push(Problem::StoppedEarly(error));
+    }
+
+    let decoded = summary.decoded_duration;
+    if decoded.is_zero() {
+        problems.push(Problem::NoAudio);
+    } else if let Some(header) = summary.header_duration {
+        if decoded + DURATION_TOLERANCE < header {
+            problems.push(Problem::Truncated { decoded, header });
+        } else if decoded > header + DURATION_TOLERANCE {
+            problems.push(Problem::LongerThanHeader { decoded, header });
+        }
+    }
+    problems
+}
+
+/// A song with something wrong with its file.
+pub struct Finding {
+    pub song: SongId,
+    pub path: Utf8PathBuf,
+    pub problems: Vec<Problem>,
+}
+
+/// Verifies the songs on other threads, one per core, so it goes as fast as it can while
+/// the app stays responsive.
+/// Dropping it cancels the verification.
+pub struct BackgroundVerification {
+    cancelled: Arc<AtomicBool>,
+    receiver: Receiver<Option<Finding>>,
+    total: usize,
+    checked: usize,
+    findings: Vec<Finding>,
+}
+
+impl BackgroundVerification {
+    /// Asks for a repaint every time a song is verified.
+    pub fn start(library: &Library, ctx: egui::Context) -> Self {
+        let songs: Arc<Vec<(SongId, Utf8PathBuf)>> = Arc::new(
+            library
+                .songs()
+                .map(|(id, song)| (id, song.path.clone()))
+                .collect(),
+        );
+        let total = songs.len();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let next_song = Arc::new(AtomicUsize::new(0));
+        let (sender, receiver) = channel();
+
+        let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+        for _ in 0..threads.min(total) {
+            let songs = songs.clone();
+            let cancelled = cancelled.clone();
+            let next_song = next_song.clone();
+            let sender = sender.clone();
+            let ctx = ctx.clone();
+            thread::spawn(move || {
+                while !cancelled.load(Ordering::Relaxed) {
+                    let Some((id, path)) = songs.get(next_song.fetch_add(1, Ordering::Relaxed))
+                    else {
+                        break;
+                    };
+                    // Broken files are what this is looking for, so a decoder that panics on one
+                    // shouldn't take the rest of the verification with it.
+                    let problems = match panic::catch_unwind(|| sound::verify_file(path)) {
+                        Ok(result) => problems(result),
+                        Err(_) => vec![Problem::Undecodable("The decoder crashed".to_string())],
+                    };
+                    let finding = (!problems.is_empty()).then(|| Finding {
+                        song: *id,
+                        path: path.clone(),
+                        problems,
+                    });
+                    // The receiver is gone when the verification was cancelled.
+                    if sender.send(finding).is_err() {
+                        break;
+                    }
+                    ctx.request_repaint();
+                }
+            });
+        }
+
+        BackgroundVerification {
+            cancelled,
+            receiver,
+            total,
+            checked: 0,
+            findings: Vec::new(),
+        }
+    }
+
+    /// Takes in the songs that were verified since the last update.
+    pub fn update(&mut self) {
+        while let Ok(finding) = self.receiver.try_recv() {
+            self.checked += 1;
+            if let Some(finding) = finding {
+                self.findings.push(finding);
+            }
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.checked == self.total
+    }
+
+    pub fn checked(&self) -> usize {
+        self.checked
+    }
+
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Only the songs with problems, in the order they were verified.
+    pub fn findings(&self) -> &[Finding] {
+        &self.findings
+    }
+
+    /// One row per problem.
+    pub fn export_csv(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::write(path, to_csv(&self.findings))
+    }
+}
+
+impl Drop for BackgroundVerification {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+fn to_csv(findings: &[Finding]) -> String {
+    let mut csv = String::from("path,problem\n");
+    for finding in findings {
+        for problem in &finding.problems {
+            let fields = [finding.path.to_string(), problem.description()];
+            let fields: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+            csv.push_str(&fields.join(","));
+            csv.push('\n');
+        }
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(decoded: u64, header: Option<u64>, decode_errors: usize) -> DecodeSummary {
+        DecodeSummary {
+            header_duration: header.map(Duration::from_secs),
+            decoded_duration: Duration::from_secs(decoded),
+            decode_errors,
+            stopped_early: None,
+        }
+    }
+
+    #[test]
+    fn test_problems() {
+        assert_eq!(problems(Ok(summary(180, Some(180), 0))), vec![]);
+        // Headers without a length are fine.
+        assert_eq!(problems(Ok(summary(180, None, 0))), vec![]);
+        assert_eq!(
+            problems(Ok(summary(60, Some(180), 2))),
+            vec![
+                Problem::DecodeErrors(2),
+                Problem::Truncated {
+                    decoded: Duration::from_secs(60),
+                    header: Duration::from_secs(180)
+                }
+            ]
+        );
+        assert_eq!(
+            problems(Ok(summary(0, Some(180), 0))),
+            vec![Problem::NoAudio]
+        );
+        assert_eq!(
+            problems(Err(PlayError::Decode(DecoderError::UnrecognizedFormat))),
+            vec![Problem::Unsupported]
+        );
+
+        let finding = Finding {
+            song: SongId::default(),
+            path: Utf8PathBuf::from("a, b.mp3"),
+            problems: vec![Problem::NoAudio, Problem::Unsupported],
+        };
+        assert_eq!(
+            to_csv(&[finding]),
+            "path,problem\n\"a, b.mp3\",No audio\n\"a, b.mp3\",Unsupported format or codec\n"
+        );
+    }
+
+    #[test]
+    fn test_verify_examples() {
+        let mut library = Library::new();
+        library.insert_from_directory(camino::Utf8Path::new("../example_audio"));
+        for (_, song) in library.songs() {
+            assert_eq!(
+                problems(sound::verify_file(&song.path)),
+                vec![],
+                "{}",
+                song.path
+            );
+        }
+    }
+}
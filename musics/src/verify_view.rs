@@ -0,0 +1,153 @@
+use crate::library::Library;
+use crate::verify::BackgroundVerification;
+use eframe::egui;
+use eframe::egui::{Color32, Context, ProgressBar};
+use egui_extras::{Column, TableBuilder};
+use rfd::FileDialog;
+
+pub struct VerifyView {
+    show_window: bool,
+    /// The running or last verification.
+    verification: Option<BackgroundVerification>,
+    /// Why the report couldn't be exported.
+    error: Option<String>,
+}
+
+impl VerifyView {
+    pub fn new() -> Self {
+        VerifyView {
+            show_window: false,
+            verification: None,
+            error: None,
+        }
+    }
+
+    pub fn open_window(&mut self) {
+        self.show_window = true;
+    }
+
+    /// The verification keeps going when the window is closed.
+    pub fn show(&mut self, ctx: &Context, library: &Library) {
+        if let Some(verification) = &mut self.verification {
+            verification.update();
+        }
+
+        let mut show_window = self.show_window;
+        egui::Window::new("Verify library")
+            .open(&mut show_window)
+            .default_width(700.0)
+            .default_height(400.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    let running = self
+                        .verification
+                        .as_ref()
+                        .is_some_and(|verification| !verification.is_done());
+                    if running {
+                        if ui.button("Cancel").clicked() {
+                            self.verification = None;
+                        }
+                    } else if ui
+                        .button("Verify")
+                        .on_hover_text(
+                            "Decodes every song from start to end, to find files that are \
+                            broken, cut off or unsupported.",
+                        )
+                        .clicked()
+                    {
+                        self.verification =
+                            Some(BackgroundVerification::start(library, ctx.clone()));
+                    }
+
+                    if let Some(verification) = &self.verification {
+                        if ui
+                            .add_enabled(!running, egui::Button::new("Export CSV"))
+                            .clicked()
+                        {
+                            self.error = export(verification).err();
+                        }
+                    }
+                });
+                if let Some(error) = &self.error {
+                    ui.colored_label(Color32::LIGHT_RED, error);
+                }
+
+                let Some(verification) = &self.verification else {
+                    return;
+                };
+                let fraction = match verification.total() {
+                    0 => 1.0,
+                    total => verification.checked() as f32 / total as f32,
+                };
+                let text = format!(
+                    "{} of {} songs, {} with problems",
+                    verification.checked(),
+                    verification.total(),
+                    verification.findings().len()
+                );
+                ui.add(ProgressBar::new(fraction).text(text));
+
+                show_findings(ui, library, verification);
+            });
+        self.show_window = show_window;
+    }
+}
+
+fn show_findings(ui: &mut egui::Ui, library: &Library, verification: &BackgroundVerification) {
+    let row_height = ui.spacing().interact_size.y;
+    TableBuilder::new(ui)
+        .striped(true)
+        .resizable(true)
+        .auto_shrink([false, false])
+        .column(Column::initial(200.0).at_least(20.0).clip(true))
+        .column(Column::initial(250.0).at_least(20.0).clip(true))
+        .column(Column::remainder())
+        .header(row_height, |mut header| {
+            header.col(|ui| {
+                ui.strong("Title");
+            });
+            header.col(|ui| {
+                ui.strong("Path");
+            });
+            header.col(|ui| {
+                ui.strong("Problems");
+            });
+        })
+        .body(|body| {
+            let findings = verification.findings();
+            body.rows(row_height, findings.len(), |index, mut row| {
+                let finding = &findings[index];
+                let problems: Vec<String> = finding
+                    .problems
+                    .iter()
+                    .map(|problem| problem.description())
+                    .collect();
+                row.col(|ui| {
+                    // The song can be gone from the library since it was verified.
+                    if let Some(song) = library.get_song(finding.song) {
+                        ui.label(&song.title);
+                    }
+                });
+                row.col(|ui| {
+                    ui.label(finding.path.as_str())
+                        .on_hover_text(finding.path.as_str());
+                });
+                row.col(|ui| {
+                    ui.colored_label(Color32::LIGHT_RED, problems.join(", "));
+                });
+            });
+        });
+}
+
+fn export(verification: &BackgroundVerification) -> Result<(), String> {
+    let Some(path) = FileDialog::new()
+        .add_filter("csv", &["csv"])
+        .set_file_name("verify_library.csv")
+        .save_file()
+    else {
+        return Ok(());
+    };
+    verification
+        .export_csv(&path)
+        .map_err(|error| format!("Could not export: {}", error))
+}
@@ -4,6 +4,7 @@
 
 use rodio::decoder::DecoderError;
 use rodio::Source;
+use std::io::ErrorKind;
 use std::sync::{Arc, RwLock};
 use std::time::Duration;
 use symphonia::{
@@ -73,6 +74,19 @@ impl TimeControl {
     }
 }
 
+/// What decoding a whole file found, see [`SymphoniaDecoder::decode_all`].
+#[derive(Clone, Debug)]
+pub struct DecodeSummary {
+    /// How long the audio is according to the header, if it says.
+    pub header_duration: Option<Duration>,
+    /// How much audio could actually be decoded.
+    pub decoded_duration: Duration,
+    /// Packets that couldn't be decoded, and were skipped.
+    pub decode_errors: usize,
+    /// Why decoding stopped before the end of the file, if it did.
+    pub stopped_early: Option<String>,
+}
+
 // Decoder errors are not considered fatal.
 // The correct action is to just get a new packet and try again.
 // But a decode error in more than 3 consecutive packets is fatal.
@@ -166,6 +180,57 @@ impl SymphoniaDecoder {
         }))
     }
 
+    /// Decodes the rest of the file as fast as it can, without making samples of it.
+    /// Unlike playing, this keeps track of what went wrong on the way.
+    pub fn decode_all(mut self) -> DecodeSummary {
+        let track = self.format.default_track();
+        let track_id = track.map(|track| track.id);
+        let header_duration = track.and_then(|track| {
+            let params = &track.codec_params;
+            let time = params.time_base?.calc_time(params.n_frames?);
+            Some(Duration::from_secs(time.seconds) + Duration::from_secs_f64(time.frac))
+        });
+
+        // The first packet was decoded when the decoder was made.
+        let channels = self.spec.channels.count().max(1);
+        let mut frames = (self.buffer.len() / channels) as u64;
+        let mut decode_errors = 0;
+        let mut stopped_early = None;
+        loop {
+            let packet = match self.format.next_packet() {
+                Ok(packet) => packet,
+                // This is how the end of the file is reported.
+                Err(Error::IoError(error)) if error.kind() == ErrorKind::UnexpectedEof => break,
+                Err(error) => {
+                    stopped_early = Some(error.to_string());
+                    break;
+                }
+            };
+            if Some(packet.track_id()) != track_id {
+                continue;
+            }
+            match self.decoder.decode(&packet) {
+                Ok(decoded) => frames += decoded.frames() as u64,
+                Err(Error::DecodeError(_)) => decode_errors += 1,
+                Err(error) => {
+                    stopped_early = Some(error.to_string());
+                    break;
+                }
+            }
+        }
+
+        let decoded_duration = match self.spec.rate {
+            0 => Duration::ZERO,
+            rate => Duration::from_secs_f64(frames as f64 / f64::from(rate)),
+        };
+        DecodeSummary {
+            header_duration,
+            decoded_duration,
+            decode_errors,
+            stopped_early,
+        }
+    }
+
     fn get_duration(params: &CodecParameters) -> Duration {
         params.n_frames.map_or_else(
             || {
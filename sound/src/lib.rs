@@ -3,9 +3,12 @@
 
 mod decoder;
 
+pub use crate::decoder::DecodeSummary;
 use crate::decoder::{SymphoniaDecoder, TimeControl};
 use camino::Utf8Path;
+pub use rodio::decoder::DecoderError;
 use rodio::{OutputStream, OutputStreamHandle, Sink};
+use std::fmt;
 use std::fs::File;
 use std::time::Duration;
 use symphonia::core::io::MediaSourceStream;
@@ -13,6 +16,24 @@ use symphonia::core::io::MediaSourceStream;
 // TODO (2023-02-03): Add all the supported extensions.
 pub const SUPPORTED_EXTENSIONS: &[&str] = &["ogg", "mp3"];
 
+#[derive(Debug)]
+pub enum PlayError {
+    /// The file is gone, or can't be read.
+    Open(std::io::Error),
+    Decode(DecoderError),
+}
+
+impl fmt::Display for PlayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlayError::Open(error) => write!(f, "Could not open the file: {}", error),
+            PlayError::Decode(error) => write!(f, "Could not decode the file: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for PlayError {}
+
 pub struct Player {
     /// Hard reference kept to prevent it from going out of scope.
     _stream: OutputStream,
@@ -48,18 +69,16 @@ impl Player {
     }
 
     /// Loads and plays the given file, replacing anything else that is currently playing.
-    pub fn play_file(&mut self, path: &Utf8Path) {
-        // TODO (2023-02-03): Error handling.
-        let audio_file = File::open(path).expect("Could not open file");
-        let stream = MediaSourceStream::new(Box::new(audio_file), Default::default());
-
-        let decoder = SymphoniaDecoder::new(stream).expect("Creating decoder should work");
+    /// When the file can't be played, whatever was playing keeps playing.
+    pub fn play_file(&mut self, path: &Utf8Path) -> Result<(), PlayError> {
+        let decoder = open_decoder(path)?;
         self.time_control = decoder.get_control();
 
         self.replace_sink();
 
         self.sink.append(decoder);
         self.sink.play();
+        Ok(())
     }
 
     pub fn stop(&mut self) {
@@ -137,6 +156,18 @@ impl Player {
     }
 }
 
+/// Decodes the whole file as fast as possible, to find out whether it plays to the end.
+/// Doesn't need an audio output.
+pub fn verify_file(path: &Utf8Path) -> Result<DecodeSummary, PlayError> {
+    Ok(open_decoder(path)?.decode_all())
+}
+
+fn open_decoder(path: &Utf8Path) -> Result<SymphoniaDecoder, PlayError> {
+    let audio_file = File::open(path).map_err(PlayError::Open)?;
+    let stream = MediaSourceStream::new(Box::new(audio_file), Default::default());
+    SymphoniaDecoder::new(stream).map_err(PlayError::Decode)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -147,7 +178,9 @@ mod tests {
     fn playing_test() {
         let mut player = Player::new();
 
-        player.play_file(Utf8Path::new("../example_audio/blank_holes_snippet.ogg"));
+        player
+            .play_file(Utf8Path::new("../example_audio/blank_holes_snippet.ogg"))
+            .unwrap();
         let duration = player.song_duration().as_secs();
         assert_eq!(duration, 17);
 
@@ -172,4 +205,27 @@ mod tests {
         );
         assert!(player.empty(), "Player should be empty, because the song is only 17 seconds, and we asked it to seek beyond that.")
     }
+
+    #[test]
+    fn verify_test() {
+        let summary =
+            verify_file(Utf8Path::new("../example_audio/blank_holes_snippet.ogg")).unwrap();
+        assert_eq!(summary.decode_errors, 0);
+        assert_eq!(summary.stopped_early, None);
+        assert_eq!(summary.decoded_duration.as_secs(), 17);
+        let header_duration = summary.header_duration.unwrap();
+        assert!(header_duration.abs_diff(summary.decoded_duration) < Duration::from_millis(100));
+
+        assert!(matches!(
+            verify_file(Utf8Path::new("../example_audio/missing.ogg")),
+            Err(PlayError::Open(_))
+        ));
+    }
+
+    #[test]
+    fn missing_file_test() {
+        let mut player = Player::new();
+        let result = player.play_file(Utf8Path::new("../example_audio/missing.ogg"));
+        assert!(matches!(result, Err(PlayError::Open(_))));
+    }
 }